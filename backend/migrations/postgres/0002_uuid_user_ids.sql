@@ -0,0 +1,14 @@
+-- Switch user ids from sequential integers to UUIDs. Existing rows get a
+-- random (v4) UUID, new rows get a UUIDv7 from the application; the old
+-- numeric id is kept in legacy_id so it can still be traced.
+ALTER TABLE users ADD COLUMN uuid UUID NOT NULL DEFAULT gen_random_uuid();
+
+ALTER TABLE users DROP CONSTRAINT users_pkey;
+ALTER TABLE users ALTER COLUMN id DROP DEFAULT;
+ALTER TABLE users ALTER COLUMN id DROP NOT NULL;
+DROP SEQUENCE IF EXISTS users_id_seq;
+ALTER TABLE users RENAME COLUMN id TO legacy_id;
+
+ALTER TABLE users RENAME COLUMN uuid TO id;
+ALTER TABLE users ALTER COLUMN id DROP DEFAULT;
+ALTER TABLE users ADD PRIMARY KEY (id);
@@ -0,0 +1,10 @@
+-- Keyset used to sign and verify access tokens, rotated at runtime
+CREATE TABLE IF NOT EXISTS signing_keys (
+    kid TEXT PRIMARY KEY NOT NULL,
+    algorithm TEXT NOT NULL,
+    private_key TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    activated_at TIMESTAMPTZ,
+    retired_at TIMESTAMPTZ,
+    verify_until TIMESTAMPTZ
+);
@@ -0,0 +1,3 @@
+-- The one token a subject revocation leaves valid, such as the session that
+-- replaced the revoked ones
+ALTER TABLE revoked_subjects ADD COLUMN spared_jti TEXT;
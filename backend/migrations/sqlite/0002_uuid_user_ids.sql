@@ -0,0 +1,29 @@
+-- Switch user ids from sequential integers to UUIDs. Existing rows get a
+-- random (v4) UUID, new rows get a UUIDv7 from the application; the old
+-- numeric id is kept in legacy_id so it can still be traced.
+CREATE TABLE users_new (
+    id TEXT PRIMARY KEY NOT NULL,
+    legacy_id INTEGER,
+    email TEXT NOT NULL,
+    first_name TEXT NOT NULL,
+    last_name TEXT NOT NULL,
+    password TEXT NOT NULL,
+    role TEXT NOT NULL
+);
+
+INSERT INTO users_new (id, legacy_id, email, first_name, last_name, password, role)
+SELECT
+    lower(
+        hex(randomblob(4)) || '-' ||
+        hex(randomblob(2)) || '-' ||
+        '4' || substr(hex(randomblob(2)), 2) || '-' ||
+        substr('89ab', 1 + (abs(random()) % 4), 1) || substr(hex(randomblob(2)), 2) || '-' ||
+        hex(randomblob(6))
+    ),
+    id, email, first_name, last_name, password, role
+FROM users;
+
+DROP TABLE users;
+ALTER TABLE users_new RENAME TO users;
+
+CREATE UNIQUE INDEX users_email_idx ON users (email);
@@ -0,0 +1,12 @@
+-- Access tokens revoked before their expiry, by jti
+CREATE TABLE IF NOT EXISTS revoked_tokens (
+    jti TEXT PRIMARY KEY NOT NULL,
+    expires_at TEXT NOT NULL
+);
+
+-- Every access token of a subject issued at or before revoked_at is rejected
+CREATE TABLE IF NOT EXISTS revoked_subjects (
+    subject TEXT PRIMARY KEY NOT NULL,
+    revoked_at TEXT NOT NULL,
+    expires_at TEXT NOT NULL
+);
@@ -0,0 +1,10 @@
+-- Keyset used to sign and verify access tokens, rotated at runtime
+CREATE TABLE IF NOT EXISTS signing_keys (
+    kid TEXT PRIMARY KEY NOT NULL,
+    algorithm TEXT NOT NULL,
+    private_key TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    activated_at TEXT,
+    retired_at TEXT,
+    verify_until TEXT
+);
@@ -0,0 +1,2 @@
+-- OpenID Connect: echoed back in the ID token issued for the code
+ALTER TABLE oauth_authorization_codes ADD COLUMN nonce TEXT;
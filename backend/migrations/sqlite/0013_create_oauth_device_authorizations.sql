@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS oauth_device_authorizations (
+    device_code_hash TEXT PRIMARY KEY NOT NULL,
+    user_code_hash TEXT NOT NULL UNIQUE,
+    client_id TEXT NOT NULL,
+    scope TEXT NOT NULL,
+    user_id TEXT REFERENCES users (id) ON DELETE CASCADE,
+    approved BOOLEAN,
+    interval_secs INTEGER NOT NULL,
+    last_polled_at TEXT,
+    created_at TEXT NOT NULL,
+    expires_at TEXT NOT NULL,
+    used_at TEXT
+);
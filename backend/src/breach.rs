@@ -0,0 +1,331 @@
+use sha1::{Digest, Sha1};
+use std::{
+    fmt,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Read, Write},
+};
+
+#[derive(Debug)]
+pub struct BreachError(pub String);
+
+impl fmt::Display for BreachError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "breached password corpus error: {}", self.0)
+    }
+}
+
+impl std::error::Error for BreachError {}
+
+const FILTER_MAGIC: &[u8; 8] = b"PWNDBLM1";
+/// Probe count (u32) and bit count (u64) following the magic bytes.
+const FILTER_HEADER_LEN: u64 = 12;
+
+/// Passwords known from data breaches, looked up by SHA-1 like the HIBP
+/// Pwned Passwords corpus. Only the first 64 bits of each hash are used.
+#[derive(Debug)]
+pub enum BreachedPasswords {
+    /// No corpus configured; nothing is reported as breached.
+    Disabled,
+    /// Hash prefixes from a text file, sorted for binary search.
+    Hashes(Vec<u64>),
+    /// A Bloom filter built with `build-breach-filter`, for corpora too large to keep as a list.
+    Filter(BloomFilter),
+}
+
+impl BreachedPasswords {
+    /// Loads either a filter written by `build-breach-filter` or a text file
+    /// with one hex SHA-1 hash (or prefix of at least 16 digits) per line,
+    /// optionally followed by `:count` as in the HIBP downloads.
+    pub fn load(path: &str) -> Result<Self, BreachError> {
+        let mut file = File::open(path).map_err(|e| BreachError(format!("cannot open {}: {}", path, e)))?;
+        let mut magic = [0u8; 8];
+        let is_filter = file.read_exact(&mut magic).is_ok() && &magic == FILTER_MAGIC;
+
+        if is_filter {
+            let len = file.metadata().map_err(|e| BreachError(format!("cannot read {}: {}", path, e)))?.len();
+            let remaining = len.saturating_sub(FILTER_MAGIC.len() as u64);
+            return BloomFilter::read_from(&mut BufReader::new(file), remaining).map(BreachedPasswords::Filter);
+        }
+
+        let mut hashes = Vec::new();
+        for_each_hash(path, |key| hashes.push(key))?;
+        hashes.sort_unstable();
+        hashes.dedup();
+        Ok(BreachedPasswords::Hashes(hashes))
+    }
+
+    pub fn contains(&self, password: &str) -> bool {
+        let key = password_key(password);
+        match self {
+            BreachedPasswords::Disabled => false,
+            BreachedPasswords::Hashes(hashes) => hashes.binary_search(&key).is_ok(),
+            BreachedPasswords::Filter(filter) => filter.contains(key),
+        }
+    }
+
+    /// Short description for the startup log.
+    pub fn describe(&self) -> String {
+        match self {
+            BreachedPasswords::Disabled => "disabled".to_string(),
+            BreachedPasswords::Hashes(hashes) => format!("{} hashes", hashes.len()),
+            BreachedPasswords::Filter(filter) => {
+                format!("Bloom filter, {} bits, {} probes", filter.num_bits, filter.num_hashes)
+            }
+        }
+    }
+}
+
+/// First 64 bits of the SHA-1 of the password.
+pub(crate) fn password_key(password: &str) -> u64 {
+    let digest = Sha1::digest(password.as_bytes());
+    u64::from_be_bytes(digest[..8].try_into().expect("SHA-1 digests are 20 bytes"))
+}
+
+/// Calls `f` with the 64-bit prefix of every hash listed in a text corpus.
+fn for_each_hash(path: &str, mut f: impl FnMut(u64)) -> Result<(), BreachError> {
+    let file = File::open(path).map_err(|e| BreachError(format!("cannot open {}: {}", path, e)))?;
+
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| BreachError(format!("cannot read {}: {}", path, e)))?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let hex = line.split(':').next().unwrap_or_default();
+        let key = hex
+            .get(..16)
+            .and_then(|prefix| u64::from_str_radix(prefix, 16).ok())
+            .ok_or_else(|| BreachError(format!("{}:{}: expected a hex SHA-1 hash", path, number + 1)))?;
+        f(key);
+    }
+    Ok(())
+}
+
+/// A Bloom filter over 64-bit hash prefixes. The prefixes are already
+/// uniformly distributed, so probe positions come from double hashing the
+/// prefix itself.
+#[derive(Debug)]
+pub struct BloomFilter {
+    num_hashes: u32,
+    num_bits: u64,
+    words: Vec<u64>,
+}
+
+impl BloomFilter {
+    /// Sizes a filter for `items` entries at the given false positive rate.
+    pub fn with_capacity(items: u64, false_positive_rate: f64) -> Self {
+        let items = items.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = ((-items * false_positive_rate.ln()) / (ln2 * ln2)).ceil().max(64.0) as u64;
+        let num_hashes = ((num_bits as f64 / items) * ln2).round().clamp(1.0, 30.0) as u32;
+
+        Self {
+            num_hashes,
+            num_bits,
+            words: vec![0; num_bits.div_ceil(64) as usize],
+        }
+    }
+
+    fn positions(&self, key: u64) -> impl Iterator<Item = u64> + '_ {
+        let step = splitmix64(key) | 1;
+        (0..self.num_hashes as u64).map(move |i| key.wrapping_add(i.wrapping_mul(step)) % self.num_bits)
+    }
+
+    pub fn insert(&mut self, key: u64) {
+        for bit in self.positions(key).collect::<Vec<_>>() {
+            self.words[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+    }
+
+    pub fn contains(&self, key: u64) -> bool {
+        self.positions(key)
+            .all(|bit| self.words[(bit / 64) as usize] & (1 << (bit % 64)) != 0)
+    }
+
+    pub fn write_to(&self, out: &mut impl Write) -> std::io::Result<()> {
+        out.write_all(FILTER_MAGIC)?;
+        out.write_all(&self.num_hashes.to_le_bytes())?;
+        out.write_all(&self.num_bits.to_le_bytes())?;
+        for word in &self.words {
+            out.write_all(&word.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Reads a filter whose magic bytes have already been consumed, from the
+    /// `len` bytes left in the file. The header must account for exactly
+    /// those bytes, so a corrupt one can't make us allocate more.
+    fn read_from(input: &mut impl Read, len: u64) -> Result<Self, BreachError> {
+        let truncated = |_| BreachError("truncated filter file".to_string());
+
+        let mut u32_buf = [0u8; 4];
+        let mut u64_buf = [0u8; 8];
+        input.read_exact(&mut u32_buf).map_err(truncated)?;
+        input.read_exact(&mut u64_buf).map_err(truncated)?;
+        let num_hashes = u32::from_le_bytes(u32_buf);
+        let num_bits = u64::from_le_bytes(u64_buf);
+        if num_hashes == 0 || num_bits == 0 {
+            return Err(BreachError("corrupt filter header".to_string()));
+        }
+        let num_words = num_bits.div_ceil(64);
+        if num_words.checked_mul(8).and_then(|bytes| bytes.checked_add(FILTER_HEADER_LEN)) != Some(len) {
+            return Err(BreachError(format!(
+                "corrupt filter header: {} bits don't fit a file of {} bytes",
+                num_bits,
+                len + FILTER_MAGIC.len() as u64
+            )));
+        }
+
+        let mut words = Vec::with_capacity(num_words as usize);
+        for _ in 0..num_words {
+            input.read_exact(&mut u64_buf).map_err(truncated)?;
+            words.push(u64::from_le_bytes(u64_buf));
+        }
+
+        Ok(Self {
+            num_hashes,
+            num_bits,
+            words,
+        })
+    }
+}
+
+fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+/// `auth_api build-breach-filter <hashes.txt> <filter.bin> [false-positive-rate]`
+///
+/// Builds a Bloom filter from a text corpus such as the HIBP Pwned Passwords
+/// SHA-1 download. The input is read twice (to size the filter, then to fill
+/// it), so it is never held in memory.
+pub fn build_filter_command(args: &[String]) -> Result<(), BreachError> {
+    let (input, output) = match args {
+        [input, output, ..] => (input, output),
+        _ => {
+            return Err(BreachError(
+                "usage: auth_api build-breach-filter <hashes.txt> <filter.bin> [false-positive-rate]".to_string(),
+            ))
+        }
+    };
+    let rate = match args.get(2) {
+        Some(rate) => rate
+            .parse::<f64>()
+            .ok()
+            .filter(|r| *r > 0.0 && *r < 1.0)
+            .ok_or_else(|| BreachError(format!("invalid false positive rate '{}'", rate)))?,
+        None => 0.001,
+    };
+
+    let mut count = 0u64;
+    for_each_hash(input, |_| count += 1)?;
+
+    let mut filter = BloomFilter::with_capacity(count, rate);
+    for_each_hash(input, |key| filter.insert(key))?;
+
+    let file = File::create(output).map_err(|e| BreachError(format!("cannot create {}: {}", output, e)))?;
+    let mut writer = BufWriter::new(file);
+    filter
+        .write_to(&mut writer)
+        .and_then(|_| writer.flush())
+        .map_err(|e| BreachError(format!("cannot write {}: {}", output, e)))?;
+
+    println!(
+        "Wrote {} hashes to {} ({} bits, {} probes, false positive rate {})",
+        count, output, filter.num_bits, filter.num_hashes, rate
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data_encoding::HEXUPPER;
+    use std::path::PathBuf;
+
+    /// A file under the temp directory, removed when dropped.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(contents: &[u8]) -> Self {
+            let path = std::env::temp_dir().join(format!("auth-api-breach-{}", uuid::Uuid::new_v4()));
+            std::fs::write(&path, contents).expect("failed to write the corpus");
+            Self(path)
+        }
+
+        fn path(&self) -> &str {
+            self.0.to_str().unwrap()
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn sha1_hex(password: &str) -> String {
+        HEXUPPER.encode(&Sha1::digest(password.as_bytes()))
+    }
+
+    #[test]
+    fn looks_up_a_hash_list() {
+        let corpus = format!(
+            "# HIBP excerpt\n{}:3861493\n\n{}\n{}\n",
+            sha1_hex("password"),
+            sha1_hex("letmein").to_lowercase(),
+            &sha1_hex("dragon")[..16]
+        );
+        let file = TempFile::new(corpus.as_bytes());
+
+        let corpus = BreachedPasswords::load(file.path()).unwrap();
+        assert!(matches!(corpus, BreachedPasswords::Hashes(ref hashes) if hashes.len() == 3));
+        assert!(corpus.contains("password"));
+        assert!(corpus.contains("letmein"));
+        assert!(corpus.contains("dragon"));
+        assert!(!corpus.contains("correct horse battery staple"));
+    }
+
+    #[test]
+    fn rejects_lines_that_are_not_hashes() {
+        let file = TempFile::new(format!("{}\nhunter2\n", sha1_hex("password")).as_bytes());
+        let err = BreachedPasswords::load(file.path()).unwrap_err();
+        assert!(err.0.ends_with(":2: expected a hex SHA-1 hash"), "{}", err.0);
+    }
+
+    #[test]
+    fn filter_survives_a_write_and_read() {
+        let breached = ["password", "123456", "qwerty", "letmein"];
+        let list = TempFile::new(breached.iter().map(|p| sha1_hex(p) + "\n").collect::<String>().as_bytes());
+        let filter = TempFile::new(b"");
+        build_filter_command(&[list.path().to_string(), filter.path().to_string()]).unwrap();
+
+        let corpus = BreachedPasswords::load(filter.path()).unwrap();
+        assert!(matches!(corpus, BreachedPasswords::Filter(_)));
+        for password in breached {
+            assert!(corpus.contains(password), "{} not found", password);
+        }
+        assert!(!corpus.contains("correct horse battery staple"));
+    }
+
+    #[test]
+    fn refuses_a_filter_header_that_does_not_match_the_file() {
+        let mut filter = Vec::new();
+        BloomFilter::with_capacity(10, 0.01).write_to(&mut filter).unwrap();
+
+        let truncated = TempFile::new(&filter[..filter.len() - 8]);
+        assert!(BreachedPasswords::load(truncated.path()).is_err());
+
+        // A header claiming an exabyte of bits, with nothing behind it
+        let mut huge = FILTER_MAGIC.to_vec();
+        huge.extend_from_slice(&7u32.to_le_bytes());
+        huge.extend_from_slice(&u64::MAX.to_le_bytes());
+        let huge = TempFile::new(&huge);
+        let err = BreachedPasswords::load(huge.path()).unwrap_err();
+        assert!(err.0.starts_with("corrupt filter header"), "{}", err.0);
+    }
+}
@@ -0,0 +1,224 @@
+use std::{
+    fmt,
+    net::{Ipv4Addr, Ipv6Addr},
+};
+use unicode_normalization::UnicodeNormalization;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmailError(pub String);
+
+impl fmt::Display for EmailError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid email address: {}", self.0)
+    }
+}
+
+impl std::error::Error for EmailError {}
+
+/// How the local part (before the `@`) is compared, selected with
+/// `EMAIL_LOCAL_PART_FOLDING`. RFC 5321 leaves its case to the receiving
+/// server, but almost every provider ignores it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LocalPartFolding {
+    /// Keep the local part exactly as typed, as the RFC allows servers to.
+    None,
+    /// Lowercase the local part, so `Jane.Doe@` and `jane.doe@` are one account.
+    Lowercase,
+}
+
+const MAX_LOCAL_PART_OCTETS: usize = 64;
+const MAX_ADDRESS_OCTETS: usize = 254;
+
+/// Parses an `addr-spec` (RFC 5322 section 3.4.1, with the UTF-8 extensions of
+/// RFC 6531) and returns the canonical form accounts are stored and looked up
+/// under:
+///
+/// - the local part is NFC-normalized, unquoted when quoting is unnecessary,
+///   and folded according to `folding`;
+/// - the domain is mapped with UTS #46 and stored in its lowercase ASCII
+///   (punycode) form, so `Bücher.Example` and `xn--bcher-kva.example` match;
+/// - address literals such as `[192.0.2.1]` are kept in canonical notation.
+pub fn normalize_email(input: &str, folding: LocalPartFolding) -> Result<String, EmailError> {
+    let input = input.trim();
+    let at = input.rfind('@').ok_or_else(|| EmailError("missing @".to_string()))?;
+    let (local, domain) = (&input[..at], &input[at + 1..]);
+
+    let local = normalize_local_part(local, folding)?;
+    let domain = normalize_domain(domain)?;
+
+    let address = format!("{}@{}", local, domain);
+    if address.len() > MAX_ADDRESS_OCTETS {
+        return Err(EmailError(format!("longer than {} octets", MAX_ADDRESS_OCTETS)));
+    }
+    Ok(address)
+}
+
+/// `atext` of RFC 5322, extended with any non-ASCII character by RFC 6531.
+fn is_atext(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-/=?^_`{|}~".contains(c) || !c.is_ascii()
+}
+
+fn is_dot_atom(text: &str) -> bool {
+    !text.is_empty() && text.split('.').all(|atom| !atom.is_empty() && atom.chars().all(is_atext))
+}
+
+fn normalize_local_part(local: &str, folding: LocalPartFolding) -> Result<String, EmailError> {
+    if local.is_empty() {
+        return Err(EmailError("empty local part".to_string()));
+    }
+
+    let local: String = local.nfc().collect();
+    let canonical = match local.strip_prefix('"').and_then(|l| l.strip_suffix('"')) {
+        Some(quoted) => {
+            let content = unquote(quoted)?;
+            // "john.doe" and john.doe are the same mailbox (RFC 5321 section 4.1.2)
+            if is_dot_atom(&content) {
+                content
+            } else {
+                format!("\"{}\"", content.replace('\\', "\\\\").replace('"', "\\\""))
+            }
+        }
+        None if is_dot_atom(&local) => local,
+        None => return Err(EmailError("local part contains characters that must be quoted".to_string())),
+    };
+    // Folded before the length check, since lowercasing can add octets (`İ` becomes `i̇`)
+    let canonical = match folding {
+        LocalPartFolding::None => canonical,
+        LocalPartFolding::Lowercase => canonical.to_lowercase(),
+    };
+
+    if canonical.len() > MAX_LOCAL_PART_OCTETS {
+        return Err(EmailError(format!("local part longer than {} octets", MAX_LOCAL_PART_OCTETS)));
+    }
+    Ok(canonical)
+}
+
+/// Resolves the quoted pairs of a `quoted-string`'s content.
+fn unquote(quoted: &str) -> Result<String, EmailError> {
+    let mut content = String::with_capacity(quoted.len());
+    let mut chars = quoted.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(escaped) if escaped == ' ' || escaped.is_ascii_graphic() => content.push(escaped),
+                _ => return Err(EmailError("invalid escape in quoted local part".to_string())),
+            },
+            '"' => return Err(EmailError("unescaped quote in local part".to_string())),
+            // qtext and the space the RFC allows between words
+            c if c == ' ' || c.is_ascii_graphic() || !c.is_ascii() => content.push(c),
+            _ => return Err(EmailError("control character in local part".to_string())),
+        }
+    }
+    Ok(content)
+}
+
+fn normalize_domain(domain: &str) -> Result<String, EmailError> {
+    if let Some(literal) = domain.strip_prefix('[').and_then(|d| d.strip_suffix(']')) {
+        return normalize_address_literal(literal);
+    }
+
+    // A trailing dot (fully qualified form) names the same domain
+    let domain = domain.strip_suffix('.').unwrap_or(domain);
+    if domain.is_empty() {
+        return Err(EmailError("empty domain".to_string()));
+    }
+
+    let ascii = idna::domain_to_ascii_strict(domain).map_err(|_| EmailError(format!("invalid domain '{}'", domain)))?;
+    // Addresses at a bare host name can't be reached from the public internet
+    if !ascii.contains('.') {
+        return Err(EmailError("domain must contain a dot".to_string()));
+    }
+    Ok(ascii)
+}
+
+fn normalize_address_literal(literal: &str) -> Result<String, EmailError> {
+    let invalid = || EmailError(format!("invalid address literal '[{}]'", literal));
+
+    if let Some(v6) = literal.get(..5).filter(|tag| tag.eq_ignore_ascii_case("IPv6:")).map(|_| &literal[5..]) {
+        let ip: Ipv6Addr = v6.parse().map_err(|_| invalid())?;
+        return Ok(format!("[IPv6:{}]", ip));
+    }
+    let ip: Ipv4Addr = literal.parse().map_err(|_| invalid())?;
+    Ok(format!("[{}]", ip))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalize(input: &str) -> Result<String, EmailError> {
+        normalize_email(input, LocalPartFolding::Lowercase)
+    }
+
+    #[test]
+    fn folds_the_local_part_only_when_asked() {
+        assert_eq!(normalize("  Jane.Doe@Example.COM ").unwrap(), "jane.doe@example.com");
+        assert_eq!(normalize_email("Jane.Doe@Example.COM", LocalPartFolding::None).unwrap(), "Jane.Doe@example.com");
+        // Composed and decomposed forms are one mailbox
+        assert_eq!(normalize("Jose\u{301}@example.com").unwrap(), normalize("Jos\u{e9}@example.com").unwrap());
+    }
+
+    #[test]
+    fn stores_international_domains_as_punycode() {
+        assert_eq!(normalize("user@Bücher.Example").unwrap(), "user@xn--bcher-kva.example");
+        assert_eq!(normalize("user@XN--BCHER-KVA.example").unwrap(), "user@xn--bcher-kva.example");
+        assert_eq!(normalize("user@example.com.").unwrap(), "user@example.com");
+        assert_eq!(normalize("josé@example.com").unwrap(), "josé@example.com");
+    }
+
+    #[test]
+    fn unquotes_local_parts_that_need_no_quoting() {
+        assert_eq!(normalize("\"John.Doe\"@example.com").unwrap(), "john.doe@example.com");
+        assert_eq!(normalize("\"j\\ohn\"@example.com").unwrap(), "john@example.com");
+        assert_eq!(normalize("\"John Doe\"@example.com").unwrap(), "\"john doe\"@example.com");
+        assert_eq!(normalize("\"a\\\"b\"@example.com").unwrap(), "\"a\\\"b\"@example.com");
+        assert_eq!(normalize("\"a@b\"@example.com").unwrap(), "\"a@b\"@example.com");
+    }
+
+    #[test]
+    fn keeps_address_literals_in_canonical_notation() {
+        assert_eq!(normalize("user@[192.0.2.1]").unwrap(), "user@[192.0.2.1]");
+        assert_eq!(normalize("user@[ipv6:2001:DB8:0::1]").unwrap(), "user@[IPv6:2001:db8::1]");
+        assert!(normalize("user@[192.0.2.256]").is_err());
+        assert!(normalize("user@[IPv6:192.0.2.1]").is_err());
+    }
+
+    #[test]
+    fn enforces_length_limits() {
+        assert!(normalize(&format!("{}@example.com", "a".repeat(64))).is_ok());
+        assert!(normalize(&format!("{}@example.com", "a".repeat(65))).is_err());
+
+        // 64 octets as typed, 65 once lowercased
+        let dotted_capital = format!("{}\u{130}@example.com", "a".repeat(62));
+        assert!(normalize_email(&dotted_capital, LocalPartFolding::None).is_ok());
+        assert!(normalize(&dotted_capital).is_err());
+
+        let domain = format!("{}.{}.{}.com", "a".repeat(63), "b".repeat(63), "c".repeat(63));
+        assert!(normalize(&format!("{}@{}", "x".repeat(58), domain)).is_ok());
+        assert!(normalize(&format!("{}@{}", "x".repeat(59), domain)).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_addresses() {
+        for input in [
+            "",
+            "user",
+            "@example.com",
+            "user@",
+            "user@localhost",
+            "user@.",
+            ".user@example.com",
+            "us..er@example.com",
+            "us er@example.com",
+            "us\"er@example.com",
+            "\"unterminated@example.com",
+            "\"bad\\\u{7}\"@example.com",
+            "user@exa mple.com",
+            "user@-example.com",
+            "user@[not-an-ip]",
+        ] {
+            assert!(normalize(input).is_err(), "accepted {:?}", input);
+        }
+    }
+}
@@ -0,0 +1,673 @@
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Nonce,
+};
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use jsonwebtoken::{
+    decode, encode,
+    errors::Result as JwtResult,
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters,
+        EllipticCurveKeyType, Jwk, KeyAlgorithm, OctetKeyPairParameters, OctetKeyPairType,
+        PublicKeyUse, RSAKeyParameters, RSAKeyType,
+    },
+    Algorithm, DecodingKey, EncodingKey, Header, TokenData, Validation,
+};
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fmt,
+    str::FromStr,
+    sync::{Arc, PoisonError, RwLock},
+};
+
+use crate::models::{KeyStatus, SigningKeyInfo, SigningKeyRecord};
+use crate::store::{SigningKeyStore, StoreError};
+use crate::utils::{Config, StorageBackend};
+
+/// How often every instance reloads the keyset from the store, in seconds.
+pub const RELOAD_INTERVAL_SECS: u64 = 60;
+
+/// Marks key material encrypted with `SIGNING_KEY_ENCRYPTION_KEY`.
+const SEALED_PREFIX: &str = "sealed:v1:";
+const NONCE_LENGTH: usize = 12;
+
+#[derive(Debug)]
+pub struct KeyError(pub String);
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "signing key error: {}", self.0)
+    }
+}
+
+impl std::error::Error for KeyError {}
+
+impl From<StoreError> for KeyError {
+    fn from(err: StoreError) -> Self {
+        KeyError(err.to_string())
+    }
+}
+
+/// A key that signs and verifies access tokens, identified by its `kid`.
+/// Asymmetric keys also expose their public half as a JWK, so other
+/// services can verify tokens without being able to mint them.
+#[derive(Clone)]
+pub struct SigningKey {
+    pub kid: String,
+    pub algorithm: Algorithm,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    public_jwk: Option<Jwk>,
+}
+
+impl fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Never print key material
+        f.debug_struct("SigningKey")
+            .field("kid", &self.kid)
+            .field("algorithm", &self.algorithm)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SigningKey {
+    /// An HS256 key. The secret is shared with every verifier, so it is never published.
+    pub fn from_secret(kid: String, secret: &[u8]) -> Self {
+        Self {
+            kid,
+            algorithm: Algorithm::HS256,
+            encoding: EncodingKey::from_secret(secret),
+            decoding: DecodingKey::from_secret(secret),
+            public_jwk: None,
+        }
+    }
+
+    /// An asymmetric key from a PKCS#8 private key PEM (PKCS#1 is also accepted
+    /// for RSA). Without an explicit `kid`, the RFC 7638 thumbprint of the
+    /// public key is used.
+    pub fn from_pem(algorithm: Algorithm, pem: &[u8], kid: Option<String>) -> Result<Self, KeyError> {
+        let pem_str = std::str::from_utf8(pem).map_err(|_| KeyError("PEM is not valid UTF-8".to_string()))?;
+
+        let (encoding, params, key_algorithm) = match algorithm {
+            Algorithm::RS256 => (
+                EncodingKey::from_rsa_pem(pem).map_err(|e| KeyError(e.to_string()))?,
+                rsa_public_params(pem_str)?,
+                KeyAlgorithm::RS256,
+            ),
+            Algorithm::ES256 => (
+                EncodingKey::from_ec_pem(pem).map_err(|e| KeyError(e.to_string()))?,
+                p256_public_params(pem_str)?,
+                KeyAlgorithm::ES256,
+            ),
+            Algorithm::EdDSA => (
+                EncodingKey::from_ed_pem(pem).map_err(|e| KeyError(e.to_string()))?,
+                ed25519_public_params(pem_str)?,
+                KeyAlgorithm::EdDSA,
+            ),
+            other => return Err(KeyError(format!("{:?} is not a supported asymmetric algorithm", other))),
+        };
+
+        let kid = kid.unwrap_or_else(|| thumbprint(&params));
+        let public_jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(key_algorithm),
+                key_id: Some(kid.clone()),
+                ..Default::default()
+            },
+            algorithm: params,
+        };
+        let decoding = DecodingKey::from_jwk(&public_jwk).map_err(|e| KeyError(e.to_string()))?;
+
+        Ok(Self {
+            kid,
+            algorithm,
+            encoding,
+            decoding,
+            public_jwk: Some(public_jwk),
+        })
+    }
+
+    /// Rebuilds a key from its keyset entry.
+    pub fn from_record(record: &SigningKeyRecord) -> Result<Self, KeyError> {
+        match parse_algorithm(&record.algorithm)? {
+            Algorithm::HS256 => {
+                let secret = URL_SAFE_NO_PAD
+                    .decode(&record.private_key)
+                    .map_err(|_| KeyError(format!("secret of key {} is not base64url", record.kid)))?;
+                Ok(Self::from_secret(record.kid.clone(), &secret))
+            }
+            alg => Self::from_pem(alg, record.private_key.as_bytes(), Some(record.kid.clone())),
+        }
+    }
+
+    /// The public key as a JWK; `None` for symmetric keys.
+    pub fn public_jwk(&self) -> Option<&Jwk> {
+        self.public_jwk.as_ref()
+    }
+
+    /// Signs `claims` with a header carrying this key's `alg` and `kid`.
+    pub fn encode<T: Serialize>(&self, claims: &T) -> JwtResult<String> {
+        let mut header = Header::new(self.algorithm);
+        header.kid = Some(self.kid.clone());
+        encode(&header, claims, &self.encoding)
+    }
+
+    /// Verifies a token signed with this key. Only this key's algorithm is accepted.
+    pub fn decode<T: DeserializeOwned>(&self, token: &str, validation: &Validation) -> JwtResult<TokenData<T>> {
+        let mut validation = validation.clone();
+        validation.algorithms = vec![self.algorithm];
+        decode(token, &self.decoding, &validation)
+    }
+}
+
+fn rsa_public_params(pem: &str) -> Result<AlgorithmParameters, KeyError> {
+    use rsa::pkcs1::DecodeRsaPrivateKey;
+    use rsa::pkcs8::DecodePrivateKey;
+    use rsa::traits::PublicKeyParts;
+
+    let key = rsa::RsaPrivateKey::from_pkcs8_pem(pem)
+        .or_else(|_| rsa::RsaPrivateKey::from_pkcs1_pem(pem))
+        .map_err(|e| KeyError(format!("invalid RSA private key: {}", e)))?;
+
+    Ok(AlgorithmParameters::RSA(RSAKeyParameters {
+        key_type: RSAKeyType::RSA,
+        n: URL_SAFE_NO_PAD.encode(key.n().to_bytes_be()),
+        e: URL_SAFE_NO_PAD.encode(key.e().to_bytes_be()),
+    }))
+}
+
+fn p256_public_params(pem: &str) -> Result<AlgorithmParameters, KeyError> {
+    use p256::elliptic_curve::sec1::ToEncodedPoint;
+    use p256::pkcs8::DecodePrivateKey;
+
+    let key = p256::SecretKey::from_pkcs8_pem(pem)
+        .map_err(|e| KeyError(format!("invalid P-256 private key (PKCS#8 expected): {}", e)))?;
+    let point = key.public_key().to_encoded_point(false);
+    let (Some(x), Some(y)) = (point.x(), point.y()) else {
+        return Err(KeyError("P-256 public key is the identity point".to_string()));
+    };
+
+    Ok(AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+        key_type: EllipticCurveKeyType::EC,
+        curve: EllipticCurve::P256,
+        x: URL_SAFE_NO_PAD.encode(x),
+        y: URL_SAFE_NO_PAD.encode(y),
+    }))
+}
+
+fn ed25519_public_params(pem: &str) -> Result<AlgorithmParameters, KeyError> {
+    use ed25519_dalek::pkcs8::DecodePrivateKey;
+
+    let key = ed25519_dalek::SigningKey::from_pkcs8_pem(pem)
+        .map_err(|e| KeyError(format!("invalid Ed25519 private key: {}", e)))?;
+
+    Ok(AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+        key_type: OctetKeyPairType::OctetKeyPair,
+        curve: EllipticCurve::Ed25519,
+        x: URL_SAFE_NO_PAD.encode(key.verifying_key().as_bytes()),
+    }))
+}
+
+/// RFC 7638 JWK thumbprint: SHA-256 over the required public members, in
+/// lexicographic order and without whitespace.
+fn thumbprint(params: &AlgorithmParameters) -> String {
+    let canonical = match params {
+        AlgorithmParameters::RSA(p) => format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, p.e, p.n),
+        AlgorithmParameters::EllipticCurve(p) => {
+            format!(r#"{{"crv":"P-256","kty":"EC","x":"{}","y":"{}"}}"#, p.x, p.y)
+        }
+        AlgorithmParameters::OctetKeyPair(p) => format!(r#"{{"crv":"Ed25519","kty":"OKP","x":"{}"}}"#, p.x),
+        AlgorithmParameters::OctetKey(_) => String::new(),
+    };
+    URL_SAFE_NO_PAD.encode(Sha256::digest(canonical.as_bytes()))
+}
+
+/// Parses an algorithm name as used in `JWT_ALGORITHM`, limited to the ones we can sign with.
+pub fn parse_algorithm(name: &str) -> Result<Algorithm, KeyError> {
+    match Algorithm::from_str(name) {
+        Ok(alg @ (Algorithm::HS256 | Algorithm::RS256 | Algorithm::ES256 | Algorithm::EdDSA)) => Ok(alg),
+        _ => Err(KeyError(format!("unsupported algorithm '{}', expected HS256, RS256, ES256 or EdDSA", name))),
+    }
+}
+
+/// Generates fresh key material for `algorithm`, encoded the way
+/// `SigningKeyRecord::private_key` stores it. RSA keys are 2048 bits.
+pub fn generate_key_material(algorithm: Algorithm) -> Result<String, KeyError> {
+    use rsa::pkcs8::{EncodePrivateKey, LineEnding};
+
+    let mut rng = rand::thread_rng();
+    let pem = match algorithm {
+        Algorithm::HS256 => {
+            let mut secret = [0u8; 32];
+            rng.fill_bytes(&mut secret);
+            return Ok(URL_SAFE_NO_PAD.encode(secret));
+        }
+        Algorithm::RS256 => rsa::RsaPrivateKey::new(&mut rng, 2048)
+            .map_err(|e| KeyError(e.to_string()))?
+            .to_pkcs8_pem(LineEnding::LF),
+        Algorithm::ES256 => p256::SecretKey::random(&mut rng).to_pkcs8_pem(LineEnding::LF),
+        Algorithm::EdDSA => {
+            let mut seed = [0u8; 32];
+            rng.fill_bytes(&mut seed);
+            ed25519_dalek::SigningKey::from_bytes(&seed).to_pkcs8_pem(LineEnding::LF)
+        }
+        other => return Err(KeyError(format!("{:?} is not a supported algorithm", other))),
+    };
+
+    pem.map(|pem| pem.to_string()).map_err(|e| KeyError(e.to_string()))
+}
+
+/// The keys usable at one point in time: every key that still verifies,
+/// indexed by `kid`, and the one that signs new tokens.
+#[derive(Debug)]
+pub struct KeySet {
+    signer: Arc<SigningKey>,
+    keys: HashMap<String, Arc<SigningKey>>,
+}
+
+impl KeySet {
+    /// Builds the keyset from stored entries, skipping retired keys past
+    /// `verify_until`. The most recently promoted, unretired key signs.
+    pub fn from_records(records: &[SigningKeyRecord], now: DateTime<Utc>) -> Result<Self, KeyError> {
+        let mut keys = HashMap::new();
+        let mut signer: Option<(DateTime<Utc>, Arc<SigningKey>)> = None;
+
+        for record in records {
+            if record.verify_until.is_some_and(|until| until <= now) {
+                continue;
+            }
+            let key = Arc::new(SigningKey::from_record(record)?);
+
+            if let (Some(activated_at), None) = (record.activated_at, record.retired_at) {
+                if signer.as_ref().is_none_or(|(newest, _)| activated_at > *newest) {
+                    signer = Some((activated_at, key.clone()));
+                }
+            }
+            keys.insert(record.kid.clone(), key);
+        }
+
+        let (_, signer) = signer.ok_or_else(|| KeyError("keyset has no promoted key to sign with".to_string()))?;
+        Ok(Self { signer, keys })
+    }
+
+    /// The key that signs new tokens.
+    pub fn signer(&self) -> &Arc<SigningKey> {
+        &self.signer
+    }
+
+    /// The key that signs ID tokens: the signing key, unless it is HS256.
+    /// Clients don't hold that secret, so they couldn't verify what it signs.
+    pub fn id_token_signer(&self) -> Option<&Arc<SigningKey>> {
+        Some(&self.signer).filter(|key| key.public_jwk().is_some())
+    }
+
+    /// The key that verifies a token with this `kid`. Tokens issued before
+    /// keys carried a `kid` are checked against the signing key.
+    pub fn verifier(&self, kid: Option<&str>) -> Option<&Arc<SigningKey>> {
+        match kid {
+            Some(kid) => self.keys.get(kid),
+            None => Some(&self.signer),
+        }
+    }
+
+    /// Public halves of every asymmetric key that still verifies, as published in the JWKS.
+    pub fn public_jwks(&self) -> Vec<Jwk> {
+        let mut keys: Vec<Jwk> = self.keys.values().filter_map(|k| k.public_jwk().cloned()).collect();
+        keys.sort_by(|a, b| a.common.key_id.cmp(&b.common.key_id));
+        keys
+    }
+}
+
+/// Encrypts key material before it is stored, with AES-256-GCM under
+/// `SIGNING_KEY_ENCRYPTION_KEY`. The `kid` is authenticated along with it,
+/// so a ciphertext can't be copied onto another key. TOTP secrets are
+/// sealed the same way, labelled with their user instead of a `kid`.
+pub struct KeyCipher(Aes256Gcm);
+
+impl fmt::Debug for KeyCipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Never print key material
+        f.debug_struct("KeyCipher").finish_non_exhaustive()
+    }
+}
+
+impl KeyCipher {
+    /// A cipher from a base64-encoded 256-bit key, e.g. `openssl rand -base64 32`.
+    pub fn from_base64(key: &str) -> Result<Self, KeyError> {
+        let bytes = STANDARD
+            .decode(key.trim())
+            .map_err(|_| KeyError("SIGNING_KEY_ENCRYPTION_KEY is not valid base64".to_string()))?;
+        Aes256Gcm::new_from_slice(&bytes)
+            .map(Self)
+            .map_err(|_| KeyError("SIGNING_KEY_ENCRYPTION_KEY must be 32 bytes".to_string()))
+    }
+
+    /// Encrypts the material of key `kid` for storage.
+    pub fn seal(&self, kid: &str, material: &str) -> String {
+        let mut nonce = [0u8; NONCE_LENGTH];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let payload = Payload { msg: material.as_bytes(), aad: kid.as_bytes() };
+        let ciphertext = self.0.encrypt(Nonce::from_slice(&nonce), payload).expect("AES-GCM cannot fail to encrypt a key");
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        format!("{}{}", SEALED_PREFIX, URL_SAFE_NO_PAD.encode(sealed))
+    }
+
+    /// Decrypts what `seal` stored for key `kid`.
+    pub fn open(&self, kid: &str, stored: &str) -> Result<String, KeyError> {
+        let undecryptable = || KeyError(format!("cannot decrypt key {}; check SIGNING_KEY_ENCRYPTION_KEY", kid));
+        let sealed = stored
+            .strip_prefix(SEALED_PREFIX)
+            .and_then(|s| URL_SAFE_NO_PAD.decode(s).ok())
+            .filter(|s| s.len() > NONCE_LENGTH)
+            .ok_or_else(undecryptable)?;
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LENGTH);
+        let payload = Payload { msg: ciphertext, aad: kid.as_bytes() };
+        let material = self.0.decrypt(Nonce::from_slice(nonce), payload).map_err(|_| undecryptable())?;
+        String::from_utf8(material).map_err(|_| undecryptable())
+    }
+}
+
+/// The live keyset, shared by every request and reloaded from the store when
+/// keys are rotated, here or on another instance. Key material goes through
+/// the cipher on its way in and out of the store; only the in-memory store
+/// may do without one.
+#[derive(Debug)]
+pub struct KeyRing {
+    store: Arc<dyn SigningKeyStore>,
+    cipher: Option<KeyCipher>,
+    current: RwLock<Arc<KeySet>>,
+}
+
+impl KeyRing {
+    /// Seeds an empty keyset with the key from the configuration, then loads
+    /// it. Once keys are rotated through the admin API the configured key is
+    /// only a starting point, so a retired one is never brought back.
+    pub async fn bootstrap(config: &Config, store: Arc<dyn SigningKeyStore>) -> Result<Self, KeyError> {
+        let cipher = match (&config.signing_key_encryption_key, &config.storage) {
+            (Some(key), _) => Some(KeyCipher::from_base64(key)?),
+            (None, StorageBackend::Memory) => None,
+            (None, storage) => {
+                return Err(KeyError(format!(
+                    "SIGNING_KEY_ENCRYPTION_KEY is required to keep signing keys in {}",
+                    storage.name()
+                )));
+            }
+        };
+        let mut configured = configured_key(config)?;
+        let records = store.list().await?;
+        let now = Utc::now();
+
+        if records.is_empty() {
+            configured.activated_at = Some(now);
+            if let Some(cipher) = &cipher {
+                configured.private_key = cipher.seal(&configured.kid, &configured.private_key);
+            }
+
+            match store.insert(configured).await {
+                // Another instance sharing the database may have seeded it first
+                Ok(()) | Err(StoreError::Conflict) => {}
+                Err(e) => return Err(e.into()),
+            }
+        } else if let Some(stored) = records.iter().find(|r| r.kid == configured.kid) {
+            // Changing JWT_SECRET or the key file in place would otherwise be ignored without a word
+            let stored = open_records(cipher.as_ref(), vec![stored.clone()])?.remove(0);
+            if stored.algorithm != configured.algorithm || stored.private_key.trim() != configured.private_key.trim() {
+                return Err(KeyError(format!(
+                    "the configured signing key differs from the stored key {}; rotate keys through /admin/keys, \
+                     or restore the configured key",
+                    configured.kid
+                )));
+            }
+        } else {
+            tracing::warn!(
+                "Configured signing key {} is not in the keyset and is ignored; add it through /admin/keys to use it",
+                configured.kid
+            );
+        }
+
+        // Keys stored before encryption was configured are encrypted in place
+        if let Some(cipher) = &cipher {
+            for record in records.iter().filter(|r| !r.private_key.starts_with(SEALED_PREFIX)) {
+                store.replace_private_key(&record.kid, &cipher.seal(&record.kid, &record.private_key)).await?;
+                tracing::info!("Encrypted signing key {} at rest", record.kid);
+            }
+        }
+
+        let keyset = KeySet::from_records(&open_records(cipher.as_ref(), store.list().await?)?, now)?;
+        Ok(Self {
+            store,
+            cipher,
+            current: RwLock::new(Arc::new(keyset)),
+        })
+    }
+
+    /// The keyset as of the last reload.
+    pub fn current(&self) -> Arc<KeySet> {
+        self.current.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Rebuilds the keyset from the store. On failure the previous keyset stays in use.
+    pub async fn reload(&self) -> Result<(), KeyError> {
+        let records = open_records(self.cipher.as_ref(), self.store.list().await?)?;
+        let keyset = KeySet::from_records(&records, Utc::now())?;
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(keyset);
+        Ok(())
+    }
+
+    /// Encrypts another secret kept at rest, such as a TOTP secret, bound to
+    /// `label`. Without a cipher (the in-memory store) it is kept as is.
+    pub fn seal_secret(&self, label: &str, secret: &str) -> String {
+        match &self.cipher {
+            Some(cipher) => cipher.seal(label, secret),
+            None => secret.to_string(),
+        }
+    }
+
+    /// Decrypts what `seal_secret` stored under `label`. Secrets stored in
+    /// the clear are passed through, until they are sealed at startup.
+    pub fn open_secret(&self, label: &str, stored: &str) -> Result<String, KeyError> {
+        if !stored.starts_with(SEALED_PREFIX) {
+            return Ok(stored.to_string());
+        }
+        let cipher = self
+            .cipher
+            .as_ref()
+            .ok_or_else(|| KeyError(format!("{} is encrypted; set SIGNING_KEY_ENCRYPTION_KEY", label)))?;
+        cipher.open(label, stored)
+    }
+
+    /// Whether a secret stored as `stored` should be sealed: it is in the
+    /// clear and there is a cipher to seal it with.
+    pub fn needs_sealing(&self, stored: &str) -> bool {
+        self.cipher.is_some() && !stored.starts_with(SEALED_PREFIX)
+    }
+
+    pub fn store(&self) -> &Arc<dyn SigningKeyStore> {
+        &self.store
+    }
+
+    /// Stores a new key, encrypting its material. Fails with
+    /// `StoreError::Conflict` if the `kid` is already in use.
+    pub async fn insert(&self, mut record: SigningKeyRecord) -> Result<(), StoreError> {
+        if let Some(cipher) = &self.cipher {
+            record.private_key = cipher.seal(&record.kid, &record.private_key);
+        }
+        self.store.insert(record).await
+    }
+
+    /// Describes a stored key for the admin API.
+    pub fn describe(&self, record: &SigningKeyRecord) -> SigningKeyInfo {
+        let status = if record.retired_at.is_some() {
+            KeyStatus::Retired
+        } else if record.kid == self.current().signer().kid {
+            KeyStatus::Current
+        } else if record.activated_at.is_some() {
+            KeyStatus::Previous
+        } else {
+            KeyStatus::Pending
+        };
+
+        SigningKeyInfo {
+            kid: record.kid.clone(),
+            algorithm: record.algorithm.clone(),
+            status,
+            created_at: record.created_at,
+            activated_at: record.activated_at,
+            retired_at: record.retired_at,
+            verify_until: record.verify_until,
+        }
+    }
+}
+
+/// Decrypts the material of stored keys. Keys stored in the clear are
+/// passed through, until `bootstrap` encrypts them.
+fn open_records(cipher: Option<&KeyCipher>, records: Vec<SigningKeyRecord>) -> Result<Vec<SigningKeyRecord>, KeyError> {
+    records
+        .into_iter()
+        .map(|mut record| {
+            if record.private_key.starts_with(SEALED_PREFIX) {
+                let cipher = cipher
+                    .ok_or_else(|| KeyError(format!("key {} is encrypted; set SIGNING_KEY_ENCRYPTION_KEY", record.kid)))?;
+                record.private_key = cipher.open(&record.kid, &record.private_key)?;
+            }
+            Ok(record)
+        })
+        .collect()
+}
+
+/// The key described by the configuration: the PEM file at
+/// `JWT_PRIVATE_KEY_PATH` for asymmetric algorithms, `JWT_SECRET` for HS256.
+fn configured_key(config: &Config) -> Result<SigningKeyRecord, KeyError> {
+    let (kid, private_key) = if config.jwt_algorithm == Algorithm::HS256 {
+        let kid = config.jwt_key_id.clone().unwrap_or_else(|| "default".to_string());
+        (kid, URL_SAFE_NO_PAD.encode(config.jwt_secret.as_bytes()))
+    } else {
+        let path = config
+            .jwt_private_key_path
+            .as_ref()
+            .ok_or_else(|| KeyError(format!("JWT_PRIVATE_KEY_PATH is required for {:?}", config.jwt_algorithm)))?;
+        let pem = std::fs::read_to_string(path).map_err(|e| KeyError(format!("cannot read {}: {}", path, e)))?;
+        let key = SigningKey::from_pem(config.jwt_algorithm, pem.as_bytes(), config.jwt_key_id.clone())?;
+        (key.kid, pem)
+    };
+
+    Ok(SigningKeyRecord {
+        kid,
+        algorithm: format!("{:?}", config.jwt_algorithm),
+        private_key,
+        created_at: Utc::now(),
+        activated_at: None,
+        retired_at: None,
+        verify_until: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{store::SqliteStore, test_support};
+
+    const ENCRYPTION_KEY: &str = "MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY=";
+
+    fn sqlite_config() -> Config {
+        let mut config = test_support::config(StorageBackend::Sqlite { path: "sqlite::memory:".to_string() });
+        config.signing_key_encryption_key = Some(ENCRYPTION_KEY.to_string());
+        config
+    }
+
+    async fn sqlite_store() -> Arc<dyn SigningKeyStore> {
+        Arc::new(SqliteStore::connect("sqlite::memory:").await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn stores_keys_encrypted() {
+        let config = sqlite_config();
+        let store = sqlite_store().await;
+        let ring = KeyRing::bootstrap(&config, store.clone()).await.unwrap();
+
+        let stored = store.list().await.unwrap().remove(0);
+        assert!(stored.private_key.starts_with(SEALED_PREFIX));
+        assert!(!stored.private_key.contains(&URL_SAFE_NO_PAD.encode(config.jwt_secret.as_bytes())));
+
+        let pem = generate_key_material(Algorithm::ES256).unwrap();
+        ring.insert(SigningKeyRecord {
+            kid: "next".to_string(),
+            algorithm: "ES256".to_string(),
+            private_key: pem.clone(),
+            created_at: Utc::now(),
+            activated_at: None,
+            retired_at: None,
+            verify_until: None,
+        })
+        .await
+        .unwrap();
+        let stored = store.list().await.unwrap().into_iter().find(|k| k.kid == "next").unwrap();
+        assert!(!stored.private_key.contains("PRIVATE KEY"));
+
+        ring.reload().await.unwrap();
+        assert!(ring.current().verifier(Some("next")).is_some());
+    }
+
+    #[tokio::test]
+    async fn encrypts_keys_stored_in_the_clear() {
+        // As stored by a version that didn't encrypt keys
+        let store = sqlite_store().await;
+        KeyRing::bootstrap(&test_support::config(StorageBackend::Memory), store.clone()).await.unwrap();
+        assert!(!store.list().await.unwrap()[0].private_key.starts_with(SEALED_PREFIX));
+
+        let ring = KeyRing::bootstrap(&sqlite_config(), store.clone()).await.unwrap();
+        assert!(store.list().await.unwrap()[0].private_key.starts_with(SEALED_PREFIX));
+        assert_eq!(ring.current().signer().kid, "default");
+    }
+
+    #[tokio::test]
+    async fn refuses_persistent_store_without_encryption_key() {
+        let mut config = sqlite_config();
+        config.signing_key_encryption_key = None;
+        assert!(KeyRing::bootstrap(&config, sqlite_store().await).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn refuses_the_wrong_encryption_key() {
+        let store = sqlite_store().await;
+        KeyRing::bootstrap(&sqlite_config(), store.clone()).await.unwrap();
+
+        let mut config = sqlite_config();
+        config.signing_key_encryption_key = Some(STANDARD.encode([7u8; 32]));
+        assert!(KeyRing::bootstrap(&config, store).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn refuses_a_changed_configured_key() {
+        let store = sqlite_store().await;
+        KeyRing::bootstrap(&sqlite_config(), store.clone()).await.unwrap();
+        KeyRing::bootstrap(&sqlite_config(), store.clone()).await.unwrap();
+
+        let mut config = sqlite_config();
+        config.jwt_secret = "another-secret-that-is-long-enough-for-hs256".to_string();
+        assert!(KeyRing::bootstrap(&config, store.clone()).await.is_err());
+
+        // A new kid is a different key, which only /admin/keys adds
+        config.jwt_key_id = Some("next".to_string());
+        let ring = KeyRing::bootstrap(&config, store).await.unwrap();
+        assert_eq!(ring.current().signer().kid, "default");
+    }
+
+    #[test]
+    fn sealed_material_is_bound_to_its_kid() {
+        let cipher = KeyCipher::from_base64(ENCRYPTION_KEY).unwrap();
+        let sealed = cipher.seal("a", "secret");
+        assert_eq!(cipher.open("a", &sealed).unwrap(), "secret");
+        assert!(cipher.open("b", &sealed).is_err());
+    }
+}
@@ -0,0 +1,184 @@
+use async_trait::async_trait;
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+use tokio::io::AsyncWriteExt;
+use utoipa::ToSchema;
+
+use crate::utils::{MailBackend, SmtpSettings, SmtpTls};
+
+#[derive(Debug)]
+pub struct MailError(pub String);
+
+impl fmt::Display for MailError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "mail error: {}", self.0)
+    }
+}
+
+impl std::error::Error for MailError {}
+
+/// A plain-text message to a single recipient.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Delivers account notices. Handlers only talk to this trait, so delivery
+/// can be swapped without touching route logic.
+#[async_trait]
+pub trait Mailer: Send + Sync + fmt::Debug {
+    async fn send(&self, email: Email) -> Result<(), MailError>;
+
+    /// Messages sent so far, oldest first, for mailers that keep them in
+    /// memory; `None` for those that hand them off.
+    fn outbox(&self) -> Option<Vec<Email>> {
+        None
+    }
+}
+
+/// Writes messages to the log instead of delivering them. For development and demos.
+#[derive(Debug, Default)]
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, email: Email) -> Result<(), MailError> {
+        tracing::info!("Mail to {}: {}\n{}", email.to, email.subject, email.body);
+        Ok(())
+    }
+}
+
+/// Appends each message as a JSON line to a file, for inspection by hand or by tests.
+#[derive(Debug)]
+pub struct FileOutbox {
+    path: String,
+    // Serializes appends so lines from concurrent sends never interleave
+    write: tokio::sync::Mutex<()>,
+}
+
+impl FileOutbox {
+    pub fn new(path: String) -> Self {
+        Self {
+            path,
+            write: tokio::sync::Mutex::new(()),
+        }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileOutbox {
+    async fn send(&self, email: Email) -> Result<(), MailError> {
+        let mut line = serde_json::to_string(&email).map_err(|e| MailError(e.to_string()))?;
+        line.push('\n');
+
+        let _guard = self.write.lock().await;
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .map_err(|e| MailError(format!("cannot open {}: {}", self.path, e)))?;
+        file.write_all(line.as_bytes())
+            .await
+            .map_err(|e| MailError(format!("cannot write {}: {}", self.path, e)))?;
+        Ok(())
+    }
+}
+
+/// Keeps every message in process memory, so a local setup can read reset
+/// and verification links through `GET /admin/outbox`.
+#[derive(Debug, Default)]
+pub struct MemoryOutbox {
+    sent: Mutex<Vec<Email>>,
+}
+
+#[async_trait]
+impl Mailer for MemoryOutbox {
+    async fn send(&self, email: Email) -> Result<(), MailError> {
+        self.sent
+            .lock()
+            .map_err(|_| MailError("outbox lock poisoned".to_string()))?
+            .push(email);
+        Ok(())
+    }
+
+    fn outbox(&self) -> Option<Vec<Email>> {
+        self.sent.lock().ok().map(|sent| sent.clone())
+    }
+}
+
+/// Delivers messages through an SMTP relay. Connections are pooled and
+/// reused between messages.
+#[derive(Debug)]
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    pub fn new(settings: &SmtpSettings, from: &str) -> Result<Self, MailError> {
+        let smtp_error = |e: lettre::transport::smtp::Error| MailError(e.to_string());
+
+        let builder = match settings.tls {
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&settings.host).map_err(smtp_error)?,
+            SmtpTls::Wrapper => AsyncSmtpTransport::<Tokio1Executor>::relay(&settings.host).map_err(smtp_error)?,
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&settings.host),
+        };
+        let builder = match (&settings.username, &settings.password) {
+            (Some(username), Some(password)) => {
+                builder.credentials(Credentials::new(username.clone(), password.clone()))
+            }
+            _ => builder,
+        };
+
+        Ok(Self {
+            transport: builder.port(settings.port).build(),
+            from: from
+                .parse()
+                .map_err(|e| MailError(format!("invalid sender '{}': {}", from, e)))?,
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, email: Email) -> Result<(), MailError> {
+        let to: Mailbox = email
+            .to
+            .parse()
+            .map_err(|e| MailError(format!("invalid recipient '{}': {}", email.to, e)))?;
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(email.subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(email.body)
+            .map_err(|e| MailError(e.to_string()))?;
+
+        self.transport
+            .send(message)
+            .await
+            .map_err(|e| MailError(e.to_string()))?;
+        Ok(())
+    }
+}
+
+/// Builds the mailer selected in the configuration.
+pub fn connect(backend: &MailBackend, from: &str) -> Result<Arc<dyn Mailer>, MailError> {
+    Ok(match backend {
+        MailBackend::Log => Arc::new(LogMailer),
+        MailBackend::File { path } => Arc::new(FileOutbox::new(path.clone())),
+        MailBackend::Memory => Arc::new(MemoryOutbox::default()),
+        MailBackend::Smtp(settings) => Arc::new(SmtpMailer::new(settings, from)?),
+    })
+}
@@ -1,32 +1,180 @@
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
 use axum::{
-    routing::{get, post},
+    routing::{delete, get, post},
     Router,
-    Extension,
     Json
 };
 use tower_http::cors::CorsLayer;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
-use tracing_subscriber;
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
 use serde_json::json;
-use chrono;
 
+pub mod breach;
+pub mod email;
+pub mod keys;
+pub mod mail;
 pub mod middleware;
 pub mod models;
+pub mod oauth;
+pub mod password;
 pub mod routes;
+#[cfg(any(test, feature = "soft-authenticator"))]
+pub mod soft_authenticator;
+pub mod store;
+#[cfg(test)]
+mod test_support;
+pub mod tokens;
+pub mod totp;
 pub mod utils;
+pub mod webauthn;
 
 use crate::{
+    breach::BreachedPasswords,
+    email::{normalize_email, LocalPartFolding},
     middleware::auth::auth_middleware,
-    routes::{auth, protected},
+    keys::KeyRing,
+    mail::Mailer,
+    password::PasswordPool,
+    routes::{
+        auth, keys as key_routes, mfa, oauth as oauth_routes, passkeys, password as password_routes, protected,
+        verification, well_known,
+    },
+    store::{
+        ActionTokenStore, MfaStore, OAuthStore, PasskeyStore, RefreshTokenStore, RevocationStore, StoreError, UserStore,
+    },
     utils::load_env,
     models::*,
 };
 
+/// Seeds the demo accounts documented in the README when the store is empty.
+async fn seed_default_users(users: &dyn UserStore, passwords: &PasswordPool) -> Result<(), StoreError> {
+    if !users.list().await?.is_empty() {
+        return Ok(());
+    }
+
+    let defaults = [
+        ("admin@example.com", "Nyengka", "Prosper", Role::Admin),
+        ("user@example.com", "King", "Joshua", Role::User),
+    ];
+    for (email, first_name, last_name, role) in defaults {
+        let seeded = users
+            .insert(NewUser {
+                email: email.to_string(),
+                first_name: first_name.to_string(),
+                last_name: last_name.to_string(),
+                password: passwords.hash("password").await.unwrap(),
+                role,
+                email_verified: true,
+            })
+            .await;
+
+        // Another instance sharing the database may have seeded it first
+        match seeded {
+            Ok(_) | Err(StoreError::Conflict) => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    tracing::info!("Seeded default admin and user accounts");
+    Ok(())
+}
+
+/// Registers the public clients configured with `OAUTH_CLIENTS` that the
+/// registry doesn't know yet. Registered clients are left as they are, so
+/// changes made through the admin endpoints survive restarts.
+async fn register_configured_clients(oauth: &dyn OAuthStore, clients: &[OAuthClient]) -> Result<(), StoreError> {
+    for client in clients {
+        match oauth.insert_client(client.clone()).await {
+            Ok(()) => tracing::info!("Registered OAuth client {} from OAUTH_CLIENTS", client.client_id),
+            Err(StoreError::Conflict) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Rewrites emails stored before addresses were normalized into their
+/// canonical form, so lookups by the normalized address find them.
+async fn normalize_stored_emails(users: &dyn UserStore, folding: LocalPartFolding) -> Result<(), StoreError> {
+    for mut user in users.list().await? {
+        let email = match normalize_email(&user.email, folding) {
+            Ok(email) if email != user.email => email,
+            Ok(_) => continue,
+            Err(e) => {
+                tracing::warn!("User {} has an unparseable email, leaving it as is: {}", user.id, e);
+                continue;
+            }
+        };
+
+        let original = std::mem::replace(&mut user.email, email);
+        match users.update(&user).await {
+            Ok(()) => tracing::info!("Normalized email of user {}", user.id),
+            Err(StoreError::Conflict) => tracing::warn!(
+                "Email of user {} ({}) normalizes to that of another account; merge them by hand",
+                user.id,
+                original
+            ),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Periodically drops refresh tokens and revocation entries that can no longer matter.
+async fn purge_expired_tokens(state: AppState) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+    loop {
+        interval.tick().await;
+        let now = chrono::Utc::now();
+
+        match state.refresh_tokens.purge_expired(now).await {
+            Ok(0) => {}
+            Ok(count) => tracing::info!("Purged {} expired refresh tokens", count),
+            Err(e) => tracing::warn!("Failed to purge expired refresh tokens: {}", e),
+        }
+        match state.revocations.purge_expired(now).await {
+            Ok(0) => {}
+            Ok(count) => tracing::info!("Purged {} expired revocation entries", count),
+            Err(e) => tracing::warn!("Failed to purge expired revocation entries: {}", e),
+        }
+        match state.action_tokens.purge_expired(now).await {
+            Ok(0) => {}
+            Ok(count) => tracing::info!("Purged {} expired single-use tokens", count),
+            Err(e) => tracing::warn!("Failed to purge expired single-use tokens: {}", e),
+        }
+        match state.passkeys.purge_expired(now).await {
+            Ok(0) => {}
+            Ok(count) => tracing::info!("Purged {} expired passkey challenges", count),
+            Err(e) => tracing::warn!("Failed to purge expired passkey challenges: {}", e),
+        }
+        match state.oauth.purge_expired(now).await {
+            Ok(0) => {}
+            Ok(count) => tracing::info!("Purged {} expired authorization and device codes", count),
+            Err(e) => tracing::warn!("Failed to purge expired authorization and device codes: {}", e),
+        }
+        match state.keys.store().purge_expired(now).await {
+            Ok(0) => {}
+            Ok(count) => tracing::info!("Purged {} retired signing keys", count),
+            Err(e) => tracing::warn!("Failed to purge retired signing keys: {}", e),
+        }
+    }
+}
+
+/// Picks up keys added, promoted or retired through another instance.
+async fn refresh_signing_keys(state: AppState) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(keys::RELOAD_INTERVAL_SECS));
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = state.keys.reload().await {
+            tracing::warn!("Failed to reload signing keys: {}", e);
+        }
+    }
+}
+
 async fn health_check() -> impl IntoResponse {
     (StatusCode::OK, Json(json!({"status": "healthy", "timestamp": chrono::Utc::now()})))
 }
@@ -34,7 +182,17 @@ async fn health_check() -> impl IntoResponse {
 #[derive(Debug, Clone)]
 pub struct AppState {
     pub config: Arc<utils::Config>,
-    pub users: Arc<Mutex<Vec<User>>>,
+    pub keys: Arc<KeyRing>,
+    pub passwords: Arc<PasswordPool>,
+    pub breached_passwords: Arc<BreachedPasswords>,
+    pub mailer: Arc<dyn Mailer>,
+    pub users: Arc<dyn UserStore>,
+    pub refresh_tokens: Arc<dyn RefreshTokenStore>,
+    pub revocations: Arc<dyn RevocationStore>,
+    pub action_tokens: Arc<dyn ActionTokenStore>,
+    pub mfa: Arc<dyn MfaStore>,
+    pub passkeys: Arc<dyn PasskeyStore>,
+    pub oauth: Arc<dyn OAuthStore>,
 }
 
 #[derive(OpenApi)]
@@ -48,9 +206,51 @@ pub struct AppState {
     paths(
         auth::login,
         auth::register,
+        auth::refresh,
+        mfa::login_mfa,
+        passkeys::login_options,
+        passkeys::login_passkey,
+        oauth_routes::authorize,
+        oauth_routes::device_authorization,
+        oauth_routes::token,
+        oauth_routes::introspect,
+        oauth_routes::revoke,
+        password_routes::forgot_password,
+        password_routes::reset_password,
+        verification::verify_email,
+        verification::resend_verification,
         protected::admin_dashboard,
         protected::register_admin,
-        protected::user_profile
+        protected::list_clients,
+        protected::register_client,
+        protected::rotate_client_secret,
+        protected::delete_client,
+        protected::user_profile,
+        protected::change_password,
+        mfa::mfa_status,
+        mfa::enroll_totp,
+        mfa::confirm_totp,
+        mfa::disable_totp,
+        mfa::regenerate_recovery_codes,
+        passkeys::registration_options,
+        passkeys::register_passkey,
+        passkeys::list_passkeys,
+        passkeys::delete_passkey,
+        oauth_routes::decide_authorization,
+        oauth_routes::device_request,
+        oauth_routes::decide_device,
+        oauth_routes::userinfo,
+        protected::logout,
+        protected::revoke_sessions,
+        protected::disable_user,
+        protected::enable_user,
+        protected::outbox,
+        key_routes::list_keys,
+        key_routes::add_key,
+        key_routes::promote_key,
+        key_routes::retire_key,
+        well_known::jwks,
+        well_known::openid_configuration
     ),
     components(
         schemas(
@@ -60,7 +260,56 @@ pub struct AppState {
             LoginRequest,
             LoginResponse,
             RegisterRequest,
-            RegisterResponse
+            RegisterResponse,
+            ChangePasswordRequest,
+            ChangePasswordResponse,
+            PasswordRule,
+            PasswordViolation,
+            PasswordPolicyError,
+            RefreshRequest,
+            ForgotPasswordRequest,
+            ResetPasswordRequest,
+            VerifyEmailRequest,
+            ResendVerificationRequest,
+            MfaChallengeResponse,
+            MfaLoginRequest,
+            MfaStatus,
+            MfaCodeRequest,
+            TotpEnrollRequest,
+            TotpEnrollment,
+            RecoveryCodesResponse,
+            DisableTotpRequest,
+            PasskeyCreationOptions,
+            PasskeyRequestOptions,
+            PasskeyRegistrationRequest,
+            PasskeyLoginOptionsRequest,
+            PasskeyLoginRequest,
+            PasskeyInfo,
+            AuthorizeParams,
+            AuthorizeDecision,
+            AuthorizeRedirect,
+            DeviceAuthorizationRequest,
+            DeviceAuthorizationResponse,
+            DeviceRequestInfo,
+            DeviceDecision,
+            TokenRequest,
+            TokenResponse,
+            OAuthErrorResponse,
+            IntrospectionRequest,
+            IntrospectionResponse,
+            RevocationRequest,
+            UserInfo,
+            OpenIdConfiguration,
+            GrantType,
+            OAuthClientInfo,
+            RegisterClientRequest,
+            RegisteredClient,
+            ClientSecretResponse,
+            mail::Email,
+            LogoutRequest,
+            KeyStatus,
+            SigningKeyInfo,
+            AddSigningKeyRequest
         )
     ),
     security(
@@ -68,25 +317,125 @@ pub struct AppState {
     ),
     tags(
         (name = "auth", description = "Authentication endpoints"),
-        (name = "protected", description = "Protected endpoints requiring Bearer token authentication")
+        (name = "protected", description = "Protected endpoints requiring Bearer token authentication"),
+        (name = "oauth", description = "OAuth 2.0 authorization server and OpenID Connect provider"),
+        (name = "keys", description = "Signing key rotation, admin only"),
+        (name = "well-known", description = "Public discovery documents")
     )
 )]
 struct ApiDoc;
 
 #[tokio::main]
 async fn main() {
+    // Offline maintenance commands run instead of the server
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("build-breach-filter") {
+        if let Err(e) = breach::build_filter_command(&args[2..]) {
+            eprintln!("{}", e.0);
+            std::process::exit(1);
+        }
+        return;
+    }
+    #[cfg(feature = "soft-authenticator")]
+    if args.get(1).map(String::as_str) == Some("soft-authenticator") {
+        if let Err(e) = soft_authenticator::command(&args[2..]) {
+            eprintln!("{}", e.0);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     // Initialize logging
     tracing_subscriber::fmt::init();
     tracing::info!("Starting Auth API server...");
 
     // Load configuration
-    let config = match std::panic::catch_unwind(|| load_env()) {
+    let config = match std::panic::catch_unwind(load_env) {
         Ok(config) => {
             tracing::info!("Configuration loaded successfully");
             config
         }
         Err(e) => {
-            tracing::error!("Failed to load configuration: {:?}", e);
+            // The panic message says which setting is wrong; it was printed above too
+            let message = e
+                .downcast_ref::<String>()
+                .map(String::as_str)
+                .or_else(|| e.downcast_ref::<&str>().copied())
+                .unwrap_or("invalid settings");
+            tracing::error!("Failed to load configuration: {}", message);
+            std::process::exit(1);
+        }
+    };
+
+    // Initialize storage
+    let stores = match store::connect(&config.storage).await {
+        Ok(stores) => {
+            tracing::info!("Storage ready ({})", config.storage.name());
+            stores
+        }
+        Err(e) => {
+            tracing::error!("Failed to initialize storage: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(e) = normalize_stored_emails(stores.users.as_ref(), config.email_local_part_folding).await {
+        tracing::error!("Failed to normalize stored emails: {}", e);
+        std::process::exit(1);
+    }
+
+    let passwords = match PasswordPool::new(config.argon2.clone(), config.password_hash_concurrency) {
+        Ok(passwords) => passwords,
+        Err(e) => {
+            tracing::error!("Failed to set up password hashing: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(e) = seed_default_users(stores.users.as_ref(), &passwords).await {
+        tracing::error!("Failed to seed default users: {}", e);
+        std::process::exit(1);
+    }
+
+    if let Err(e) = register_configured_clients(stores.oauth.as_ref(), &config.oauth_clients).await {
+        tracing::error!("Failed to register OAuth clients: {}", e);
+        std::process::exit(1);
+    }
+
+    // Load the signing keyset, seeding it with the configured key
+    let keys = match KeyRing::bootstrap(&config, stores.signing_keys).await {
+        Ok(keys) => {
+            let signer = keys.current().signer().clone();
+            tracing::info!("Signing access tokens with {:?} key {}", signer.algorithm, signer.kid);
+            keys
+        }
+        Err(e) => {
+            tracing::error!("Failed to load signing keys: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(e) = mfa::seal_stored_secrets(stores.mfa.as_ref(), &keys).await {
+        tracing::error!("Failed to encrypt stored TOTP secrets: {}", e);
+        std::process::exit(1);
+    }
+
+    let breached_passwords = match config.breached_passwords_path.as_deref().map(BreachedPasswords::load) {
+        None => BreachedPasswords::Disabled,
+        Some(Ok(corpus)) => {
+            tracing::info!("Loaded breached password corpus ({})", corpus.describe());
+            corpus
+        }
+        Some(Err(e)) => {
+            tracing::error!("Failed to load breached passwords: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let mailer = match mail::connect(&config.mail, &config.mail_from) {
+        Ok(mailer) => mailer,
+        Err(e) => {
+            tracing::error!("Failed to set up the mailer: {}", e);
             std::process::exit(1);
         }
     };
@@ -94,43 +443,76 @@ async fn main() {
     // Initialize application state
     let state = AppState {
         config: Arc::new(config),
-        users: Arc::new(Mutex::new(vec![
-            User {
-                id: 1,
-                email: "admin@example.com".to_string(),
-                first_name: "Nyengka".to_string(),
-                last_name: "Prosper".to_string(),
-                password: bcrypt::hash("password", bcrypt::DEFAULT_COST).unwrap(),
-                role: Role::Admin,
-            },
-            User {
-                id: 2,
-                email: "user@example.com".to_string(),
-                first_name: "King".to_string(),
-                last_name: "Joshua".to_string(),
-                password: bcrypt::hash("password", bcrypt::DEFAULT_COST).unwrap(),
-                role: Role::User,
-            }
-        ])),
+        keys: Arc::new(keys),
+        passwords: Arc::new(passwords),
+        breached_passwords: Arc::new(breached_passwords),
+        mailer,
+        users: stores.users,
+        refresh_tokens: stores.refresh_tokens,
+        revocations: stores.revocations,
+        action_tokens: stores.action_tokens,
+        mfa: stores.mfa,
+        passkeys: stores.passkeys,
+        oauth: stores.oauth,
     };
 
+    tokio::spawn(purge_expired_tokens(state.clone()));
+    tokio::spawn(refresh_signing_keys(state.clone()));
+
     // Create public router (no auth required)
     let public_router = Router::new()
         .route("/health", get(health_check))
         .route("/login", post(auth::login))
         .route("/register", post(auth::register))
+        .route("/login/mfa", post(mfa::login_mfa))
+        .route("/login/passkey/options", post(passkeys::login_options))
+        .route("/login/passkey", post(passkeys::login_passkey))
+        .route("/token/refresh", post(auth::refresh))
+        .route("/oauth/authorize", get(oauth_routes::authorize))
+        .route("/oauth/device_authorization", post(oauth_routes::device_authorization))
+        .route("/oauth/token", post(oauth_routes::token))
+        .route("/oauth/introspect", post(oauth_routes::introspect))
+        .route("/oauth/revoke", post(oauth_routes::revoke))
+        .route("/password/forgot", post(password_routes::forgot_password))
+        .route("/password/reset", post(password_routes::reset_password))
+        .route("/verify-email", post(verification::verify_email))
+        .route("/verify-email/resend", post(verification::resend_verification))
+        .route("/.well-known/jwks.json", get(well_known::jwks))
+        .route("/.well-known/openid-configuration", get(well_known::openid_configuration))
         .with_state(state.clone());
 
     // Create protected router (auth required)
     let protected_router = Router::new()
         .route("/admin/dashboard", get(protected::admin_dashboard))
         .route("/admin/register", post(protected::register_admin))
+        .route("/admin/oauth/clients", get(protected::list_clients).post(protected::register_client))
+        .route("/admin/oauth/clients/{client_id}", delete(protected::delete_client))
+        .route("/admin/oauth/clients/{client_id}/secret", post(protected::rotate_client_secret))
         .route("/user/profile", get(protected::user_profile))
+        .route("/user/password", post(protected::change_password))
+        .route("/user/mfa", get(mfa::mfa_status))
+        .route("/user/mfa/totp", post(mfa::enroll_totp))
+        .route("/user/mfa/totp/confirm", post(mfa::confirm_totp))
+        .route("/user/mfa/totp/disable", post(mfa::disable_totp))
+        .route("/user/mfa/recovery-codes", post(mfa::regenerate_recovery_codes))
+        .route("/user/passkeys", get(passkeys::list_passkeys).post(passkeys::register_passkey))
+        .route("/user/passkeys/register/options", post(passkeys::registration_options))
+        .route("/user/passkeys/{id}", delete(passkeys::delete_passkey))
+        .route("/oauth/authorize", post(oauth_routes::decide_authorization))
+        .route("/oauth/device", get(oauth_routes::device_request).post(oauth_routes::decide_device))
+        .route("/userinfo", get(oauth_routes::userinfo).post(oauth_routes::userinfo))
+        .route("/logout", post(protected::logout))
+        .route("/admin/users/{id}/revoke-sessions", post(protected::revoke_sessions))
+        .route("/admin/users/{id}/disable", post(protected::disable_user))
+        .route("/admin/users/{id}/enable", post(protected::enable_user))
+        .route("/admin/outbox", get(protected::outbox))
+        .route("/admin/keys", get(key_routes::list_keys).post(key_routes::add_key))
+        .route("/admin/keys/{kid}/promote", post(key_routes::promote_key))
+        .route("/admin/keys/{kid}/retire", post(key_routes::retire_key))
         .layer(axum::middleware::from_fn_with_state(
             state.clone(),
             auth_middleware,
         ))
-        .layer(Extension(state.users.clone()))
         .with_state(state.clone());
 
     // Build the main application
@@ -3,15 +3,21 @@ use axum::{
     extract::State,
     http::{Request, StatusCode},
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Response},
+    Json,
 };
-use jsonwebtoken::{decode, DecodingKey, Validation};
+use jsonwebtoken::decode_header;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use std::sync::Arc;
-use tracing::warn;
+use tracing::{error, warn};
+use uuid::Uuid;
 
 use crate::{
     models::Role,
+    oauth::{client_subject, has_scope, OPENID_SCOPE},
+    tokens::access_token_validation,
+    utils::UnverifiedLogin,
     AppState
 };
 
@@ -22,9 +28,49 @@ pub struct Claims {
     pub first_name: String, // User first name
     pub last_name: String, // User last name
     pub role: Role, // for role-based access control(admin, user, etc.)
+    #[serde(default = "verified_by_default")]
+    pub email_verified: bool, // whether the user confirmed `email`; see UNVERIFIED_LOGIN
+    pub iss: String, // issuer, the deployment that minted the token (JWT_ISSUER)
+    pub aud: String, // audience, the deployment the token is meant for (JWT_AUDIENCE)
     pub exp: usize, // token expiration time as a UNIX timestamp
+    pub nbf: usize, // not valid before this UNIX timestamp
+    pub iat: usize, // issue time as a UNIX timestamp, checked against per-user revocations
+    pub jti: String, // unique token id, used to revoke a single token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>, // OAuth client the user delegated this token to, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>, // space-separated scopes granted to that client
 }
 
+/// Claims of a token an OAuth client got for itself with the client
+/// credentials grant. Without the user fields of `Claims`, `auth_middleware`
+/// rejects these tokens: they are meant for other services, not this API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientClaims {
+    pub sub: String, // `client:<client_id>`; there is no user behind the token
+    pub client_id: String, // the client, as in tokens delegated by a user
+    pub scope: String, // space-separated scopes granted to the client
+    pub iss: String,
+    pub aud: String,
+    pub exp: usize,
+    pub nbf: usize,
+    pub iat: usize,
+    pub jti: String,
+}
+
+// Tokens minted before verification existed belong to accounts that are trusted as they are
+fn verified_by_default() -> bool {
+    true
+}
+
+/// What a token of an unverified user may still reach under `UNVERIFIED_LOGIN=restricted`.
+const UNVERIFIED_PATHS: [&str; 3] = ["/user/profile", "/userinfo", "/logout"];
+
+/// What a token issued to an OAuth client may reach, and the scope it needs
+/// for each; the rest of the API acts on the account itself and stays with
+/// first-party sessions.
+const DELEGATED_PATHS: [(&str, &str); 2] = [("/user/profile", "profile"), ("/userinfo", OPENID_SCOPE)];
+
 pub async fn auth_middleware(
     State(state): State<AppState>,
     mut req: Request<Body>,
@@ -40,15 +86,152 @@ pub async fn auth_middleware(
         .strip_prefix("Bearer ")
         .ok_or(StatusCode::UNAUTHORIZED)?;
 
-    let key = DecodingKey::from_secret(state.config.jwt_secret.as_bytes());
+    // Pick the key by `kid`; retired keys stay in the keyset until their tokens expire
+    let header = decode_header(token).map_err(|e| {
+        warn!("JWT header error: {:?}", e);
+        StatusCode::UNAUTHORIZED
+    })?;
+    let keys = state.keys.current();
+    let key = keys.verifier(header.kid.as_deref()).ok_or_else(|| {
+        warn!("JWT signed with unknown key {:?}", header.kid);
+        StatusCode::UNAUTHORIZED
+    })?;
 
-    let token_data = decode::<Claims>(token, &key, &Validation::default())
+    let token_data = key
+        .decode::<Claims>(token, &access_token_validation(&state.config, key.algorithm))
         .map_err(|e| {
             warn!("JWT decode error: {:?}", e);
             StatusCode::UNAUTHORIZED
         })?;
 
-    req.extensions_mut().insert(Arc::new(token_data.claims));
+    let claims = token_data.claims;
+    let revoked = state
+        .revocations
+        .is_revoked(&claims.jti, &claims.sub, claims.iat as i64)
+        .await
+        .map_err(|e| {
+            error!("Revocation check failed: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    if revoked {
+        warn!("Rejected revoked token {} for subject {}", claims.jti, claims.sub);
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // Deleting or disabling an account cuts off its tokens at once, not when they expire
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| {
+        warn!("Rejected token {} with malformed subject {}", claims.jti, claims.sub);
+        StatusCode::UNAUTHORIZED
+    })?;
+    let user = state.users.find_by_id(user_id).await.map_err(|e| {
+        error!("User lookup failed: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    match user {
+        None => {
+            warn!("Rejected token {} of deleted user {}", claims.jti, claims.sub);
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        Some(user) if user.disabled => {
+            warn!("Rejected token {} of disabled user {}", claims.jti, claims.sub);
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        Some(_) => {}
+    }
+
+    if !claims.email_verified
+        && state.config.unverified_login == UnverifiedLogin::Restricted
+        && !UNVERIFIED_PATHS.contains(&req.uri().path())
+    {
+        return Ok((
+            StatusCode::FORBIDDEN,
+            Json(json!({ "error": "Verify your email address to use this endpoint" })),
+        )
+            .into_response());
+    }
+
+    if let Some(client_id) = claims.client_id.as_deref() {
+        // Deleting a client cuts off the tokens users delegated to it too,
+        // even once a new client is registered under the same id
+        let client = state.oauth.find_client(client_id).await.map_err(|e| {
+            error!("Client lookup failed: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        let client_revoked = state
+            .revocations
+            .is_revoked(&claims.jti, &client_subject(client_id), claims.iat as i64)
+            .await
+            .map_err(|e| {
+                error!("Revocation check failed: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+        if client.is_none() || client_revoked {
+            warn!("Rejected token {} of deleted client {}", claims.jti, client_id);
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+
+        let Some((_, required)) = DELEGATED_PATHS.iter().find(|(path, _)| *path == req.uri().path()) else {
+            return Ok((
+                StatusCode::FORBIDDEN,
+                Json(json!({ "error": "This endpoint is not available to OAuth clients" })),
+            )
+                .into_response());
+        };
+        if !has_scope(claims.scope.as_deref().unwrap_or_default(), required) {
+            return Ok((
+                StatusCode::FORBIDDEN,
+                Json(json!({ "error": format!("This endpoint requires the '{}' scope", required) })),
+            )
+                .into_response());
+        }
+    }
+
+    req.extensions_mut().insert(Arc::new(claims));
 
     Ok(next.run(req).await)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware::from_fn_with_state, routing::get, Router};
+    use tower::ServiceExt;
+
+    use crate::{routes::protected::user_profile, test_support, tokens::mint_access_token};
+
+    async fn profile_status(state: &AppState, token: &str) -> StatusCode {
+        let app = Router::new()
+            .route("/user/profile", get(user_profile))
+            .layer(from_fn_with_state(state.clone(), auth_middleware))
+            .with_state(state.clone());
+        let request = Request::get("/user/profile")
+            .header("Authorization", format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap();
+        app.oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn tokens_of_disabled_users_are_refused() {
+        let state = test_support::memory_state().await;
+        let mut user = test_support::user(&state, "ada@example.com").await;
+        let token = mint_access_token(&state, &user, chrono::Utc::now());
+        assert_eq!(profile_status(&state, &token).await, StatusCode::OK);
+
+        user.disabled = true;
+        state.users.update(&user).await.unwrap();
+        assert_eq!(profile_status(&state, &token).await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn tokens_of_deleted_users_are_refused() {
+        let state = test_support::memory_state().await;
+        let user = test_support::user(&state, "ada@example.com").await;
+        let token = mint_access_token(&state, &user, chrono::Utc::now());
+        assert_eq!(profile_status(&state, &token).await, StatusCode::OK);
+
+        state.users.delete(user.id).await.unwrap();
+        assert_eq!(profile_status(&state, &token).await, StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,56 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A signing key as persisted in the keyset. `private_key` is the PKCS#8 PEM
+/// for asymmetric keys, or the base64url-encoded secret for HS256; in the
+/// store it is encrypted with `SIGNING_KEY_ENCRYPTION_KEY` (see `KeyCipher`).
+///
+/// A key is added pending, starts signing once promoted (the most recently
+/// promoted key wins), and after being retired still verifies tokens until
+/// `verify_until`, when the last token it signed has expired.
+#[derive(Clone, Debug)]
+pub struct SigningKeyRecord {
+    pub kid: String,
+    pub algorithm: String,
+    pub private_key: String,
+    pub created_at: DateTime<Utc>,
+    pub activated_at: Option<DateTime<Utc>>,
+    pub retired_at: Option<DateTime<Utc>>,
+    pub verify_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyStatus {
+    /// Published and accepted for verification, but not signing yet.
+    Pending,
+    /// Signs every new token.
+    Current,
+    /// Was current before another key was promoted; still verifies.
+    Previous,
+    /// Verifies tokens until `verify_until`, then is dropped.
+    Retired,
+}
+
+/// Public view of a keyset entry; never includes key material.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SigningKeyInfo {
+    pub kid: String,
+    pub algorithm: String,
+    pub status: KeyStatus,
+    pub created_at: DateTime<Utc>,
+    pub activated_at: Option<DateTime<Utc>>,
+    pub retired_at: Option<DateTime<Utc>>,
+    pub verify_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AddSigningKeyRequest {
+    /// HS256, RS256, ES256 or EdDSA; defaults to `JWT_ALGORITHM`.
+    pub algorithm: Option<String>,
+    /// Defaults to the RFC 7638 thumbprint of the public key (a random id for HS256).
+    pub kid: Option<String>,
+    /// PKCS#8 PEM private key to import; a fresh key is generated when omitted.
+    pub private_key_pem: Option<String>,
+}
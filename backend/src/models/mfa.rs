@@ -0,0 +1,76 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// A user's TOTP secret. It only guards logins once `enabled_at` is set,
+/// which happens when the user proves their authenticator produces codes.
+#[derive(Clone, Debug)]
+pub struct TotpCredential {
+    pub user_id: Uuid,
+    /// Base32 secret shared with the authenticator app.
+    pub secret: String,
+    pub created_at: DateTime<Utc>,
+    pub enabled_at: Option<DateTime<Utc>>,
+    /// Time step of the last accepted code; codes from it or earlier are replays.
+    pub last_used_step: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TotpEnrollRequest {
+    /// Current password, so a stolen access token can't attach a second factor.
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TotpEnrollment {
+    /// Base32 secret, for apps where it is typed in by hand.
+    pub secret: String,
+    /// `otpauth://` URI to render as a QR code.
+    pub otpauth_uri: String,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MfaCodeRequest {
+    /// Current 6-digit code from the authenticator app, or a recovery code where accepted.
+    pub code: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DisableTotpRequest {
+    pub password: String,
+    /// Current authenticator code or an unused recovery code.
+    pub code: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RecoveryCodesResponse {
+    /// One-time codes that replace an authenticator code at login. They are
+    /// shown only now; the server keeps hashes.
+    pub recovery_codes: Vec<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MfaStatus {
+    pub totp_enabled: bool,
+    pub recovery_codes_remaining: u64,
+}
+
+/// Answer to a correct password when the account has TOTP enabled.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MfaChallengeResponse {
+    pub mfa_required: bool,
+    /// Single-use token to send to `/login/mfa` with the code.
+    pub mfa_token: String,
+    pub expires_in: u64,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MfaLoginRequest {
+    pub mfa_token: String,
+    /// Current authenticator code or an unused recovery code.
+    pub code: String,
+}
@@ -1,2 +1,12 @@
+pub mod key;
+pub mod mfa;
+pub mod oauth;
+pub mod passkey;
+pub mod token;
 pub mod user;
-pub use user::*;
\ No newline at end of file
+pub use key::*;
+pub use mfa::*;
+pub use oauth::*;
+pub use passkey::*;
+pub use token::*;
+pub use user::*;
@@ -0,0 +1,408 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+/// OAuth grants a client may be registered for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GrantType {
+    /// A user approves access in the browser and the client redeems a code (with PKCE).
+    AuthorizationCode,
+    /// The client authenticates with its secret and gets a token for itself.
+    ClientCredentials,
+    /// A device without a browser shows a code the user approves elsewhere,
+    /// and polls for the token (RFC 8628).
+    #[serde(rename = "urn:ietf:params:oauth:grant-type:device_code")]
+    DeviceCode,
+}
+
+impl GrantType {
+    pub const ALL: [GrantType; 3] = [GrantType::AuthorizationCode, GrantType::ClientCredentials, GrantType::DeviceCode];
+
+    /// Name used in token requests and when persisted.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GrantType::AuthorizationCode => "authorization_code",
+            GrantType::ClientCredentials => "client_credentials",
+            GrantType::DeviceCode => "urn:ietf:params:oauth:grant-type:device_code",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "authorization_code" => Some(GrantType::AuthorizationCode),
+            "client_credentials" => Some(GrantType::ClientCredentials),
+            "urn:ietf:params:oauth:grant-type:device_code" => Some(GrantType::DeviceCode),
+            _ => None,
+        }
+    }
+}
+
+/// A registered OAuth client. Confidential clients authenticate with a
+/// secret, of which only the SHA-256 hash is kept; public clients have none
+/// and are limited to the grants that don't need one: the authorization code
+/// grant, where PKCE protects them, and the device grant, where the user
+/// approves the code on another device.
+#[derive(Clone, Debug)]
+pub struct OAuthClient {
+    pub client_id: String,
+    pub name: String,
+    pub secret_hash: Option<String>,
+    pub grant_types: Vec<GrantType>,
+    /// Exact URIs codes may be sent to.
+    pub redirect_uris: Vec<String>,
+    /// Scopes the client may ask for.
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl OAuthClient {
+    pub fn allows(&self, grant: GrantType) -> bool {
+        self.grant_types.contains(&grant)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OAuthClientInfo {
+    pub client_id: String,
+    pub name: String,
+    /// Whether the client authenticates with a secret.
+    pub confidential: bool,
+    pub grant_types: Vec<GrantType>,
+    pub redirect_uris: Vec<String>,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<&OAuthClient> for OAuthClientInfo {
+    fn from(client: &OAuthClient) -> Self {
+        OAuthClientInfo {
+            client_id: client.client_id.clone(),
+            name: client.name.clone(),
+            confidential: client.secret_hash.is_some(),
+            grant_types: client.grant_types.clone(),
+            redirect_uris: client.redirect_uris.clone(),
+            scopes: client.scopes.clone(),
+            created_at: client.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RegisterClientRequest {
+    /// Letters, digits and `-._~`; generated when omitted.
+    pub client_id: Option<String>,
+    pub name: String,
+    /// Issue a secret; required for `client_credentials`.
+    #[serde(default)]
+    pub confidential: bool,
+    pub grant_types: Vec<GrantType>,
+    /// Required for `authorization_code`.
+    #[serde(default)]
+    pub redirect_uris: Vec<String>,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RegisteredClient {
+    #[serde(flatten)]
+    pub client: OAuthClientInfo,
+    /// Shown only once; store it in the client's configuration.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ClientSecretResponse {
+    pub client_id: String,
+    /// Shown only once; the previous secret stops working immediately.
+    pub client_secret: String,
+}
+
+/// Server-side record of an authorization code. Only the SHA-256 hash of the
+/// code is kept, and it can be redeemed once.
+#[derive(Clone, Debug)]
+pub struct AuthorizationCode {
+    pub code_hash: String,
+    pub client_id: String,
+    pub user_id: Uuid,
+    pub redirect_uri: String,
+    /// Space-separated scopes the user granted.
+    pub scope: String,
+    /// BASE64URL(SHA-256(code_verifier)) from the authorization request.
+    pub code_challenge: String,
+    /// OpenID Connect `nonce` to echo back in the ID token.
+    pub nonce: Option<String>,
+    /// `jti` of the access token the code is exchanged for, so it can be
+    /// revoked if the code is replayed.
+    pub token_id: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+/// Server-side record of a device authorization request (RFC 8628). Only
+/// hashes of the device code and the user code are kept.
+#[derive(Clone, Debug)]
+pub struct DeviceAuthorization {
+    pub device_code_hash: String,
+    /// Hash of the user code, normalized to its 8 letters without the dash.
+    pub user_code_hash: String,
+    pub client_id: String,
+    /// Space-separated scopes the client asked for.
+    pub scope: String,
+    /// The user who answered the request; `None` while it is pending.
+    pub user_id: Option<Uuid>,
+    /// The user's answer; `None` while it is pending.
+    pub approved: Option<bool>,
+    /// Seconds the client must wait between polls; each `slow_down` adds 5.
+    pub interval_secs: u32,
+    pub last_polled_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// When the client got its token.
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+/// Authorization request parameters (RFC 6749 section 4.1.1 with RFC 7636).
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuthorizeParams {
+    /// Must be `code`.
+    pub response_type: String,
+    pub client_id: String,
+    /// One of the client's registered redirect URIs.
+    pub redirect_uri: String,
+    /// Space-separated scopes; defaults to `profile`. Include `openid` to get an ID token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    /// Opaque value returned unchanged with the code, to protect the client from CSRF.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+    /// BASE64URL(SHA-256(code_verifier)); required.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code_challenge: Option<String>,
+    /// Must be `S256`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code_challenge_method: Option<String>,
+    /// OpenID Connect: returned in the ID token so the client can tie it to this request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+}
+
+/// The logged-in user's answer to an authorization request.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AuthorizeDecision {
+    #[serde(flatten)]
+    pub request: AuthorizeParams,
+    /// `false` sends the client an `access_denied` error instead of a code.
+    #[serde(default = "approve_by_default")]
+    pub approve: bool,
+}
+
+fn approve_by_default() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AuthorizeRedirect {
+    /// Where to send the browser: the client's redirect URI with either
+    /// `code` or `error`, plus `state`.
+    pub redirect_to: String,
+}
+
+/// Device authorization request, sent form-encoded (RFC 8628 section 3.1).
+/// Confidential clients authenticate as at the token endpoint.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DeviceAuthorizationRequest {
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    /// Space-separated scopes; defaults to `profile`.
+    pub scope: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DeviceAuthorizationResponse {
+    /// Secret the device polls the token endpoint with.
+    pub device_code: String,
+    /// Short code the user enters at `verification_uri`, e.g. `BDWP-HQPK`.
+    pub user_code: String,
+    pub verification_uri: String,
+    /// `verification_uri` with the user code filled in, e.g. for a QR code.
+    pub verification_uri_complete: String,
+    pub expires_in: u64,
+    /// Seconds to wait between polls.
+    pub interval: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UserCodeQuery {
+    /// The code shown on the device; case and dashes don't matter.
+    pub user_code: String,
+}
+
+/// What a pending device authorization asks for, shown to the user before
+/// they answer it.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DeviceRequestInfo {
+    pub client_id: String,
+    pub client_name: String,
+    pub scope: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// The logged-in user's answer to a device authorization request.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DeviceDecision {
+    pub user_code: String,
+    /// `false` makes the device's next poll fail with `access_denied`.
+    #[serde(default = "approve_by_default")]
+    pub approve: bool,
+}
+
+/// Token request parameters, sent form-encoded (RFC 6749 sections 4.1.3 and
+/// 4.4.2, RFC 8628 section 3.4). Confidential clients may send their credentials with HTTP Basic
+/// authentication instead of `client_id` and `client_secret`.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TokenRequest {
+    /// `authorization_code`, `client_credentials` or `urn:ietf:params:oauth:grant-type:device_code`.
+    pub grant_type: String,
+    pub code: Option<String>,
+    pub device_code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    pub code_verifier: Option<String>,
+    /// Space-separated scopes for `client_credentials`; defaults to all the client's scopes.
+    pub scope: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: u64,
+    pub scope: String,
+    /// OpenID Connect ID token, when the `openid` scope was granted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+}
+
+/// Standard OpenID Connect claims about a user (OpenID Connect Core section
+/// 5.1), limited to what the granted scopes allow: `profile` for the names,
+/// `email` for the address.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserInfo {
+    /// The user's id.
+    pub sub: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub given_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub family_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
+}
+
+/// Claims of an ID token: who logged in, for which client, and the user
+/// claims the granted scopes allow.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    /// The client the user logged in to.
+    pub aud: String,
+    pub exp: usize,
+    pub iat: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    #[serde(flatten)]
+    pub user: UserInfo,
+}
+
+/// OpenID Provider metadata (OpenID Connect Discovery section 3).
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OpenIdConfiguration {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub device_authorization_endpoint: String,
+    pub userinfo_endpoint: String,
+    pub jwks_uri: String,
+    pub scopes_supported: Vec<String>,
+    pub response_types_supported: Vec<String>,
+    pub grant_types_supported: Vec<String>,
+    pub subject_types_supported: Vec<String>,
+    pub id_token_signing_alg_values_supported: Vec<String>,
+    pub token_endpoint_auth_methods_supported: Vec<String>,
+    pub code_challenge_methods_supported: Vec<String>,
+    pub claims_supported: Vec<String>,
+}
+
+/// Token introspection request, sent form-encoded by a confidential client
+/// with its credentials (RFC 7662 section 2.1).
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct IntrospectionRequest {
+    pub token: String,
+    /// `access_token` or `refresh_token`; only a hint, every token is looked up the same way.
+    pub token_type_hint: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+/// What the authorization server knows about a token (RFC 7662 section 2.2).
+/// Inactive tokens only get `active: false`.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct IntrospectionResponse {
+    /// Whether the token is valid, unexpired, unrevoked and its user and client still exist.
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    /// The client the token was issued to; absent for first-party sessions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    /// The user's email address, for tokens issued to a user.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iat: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<usize>,
+    /// The user id, or `client:<client_id>` for client credentials tokens.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+}
+
+/// Token revocation request, sent form-encoded by the client the token was
+/// issued to (RFC 7009 section 2.1).
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RevocationRequest {
+    pub token: String,
+    /// `access_token` or `refresh_token`; only a hint, every token is looked up the same way.
+    pub token_type_hint: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+/// Error body of the OAuth endpoints (RFC 6749 section 5.2).
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OAuthErrorResponse {
+    /// Error code such as `invalid_grant`.
+    pub error: String,
+    pub error_description: String,
+}
@@ -0,0 +1,204 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// A WebAuthn credential registered by a user. The private key never leaves
+/// the authenticator; we keep its COSE public key and signature counter.
+#[derive(Clone, Debug)]
+pub struct Passkey {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    /// Credential id chosen by the authenticator, base64url-encoded.
+    pub credential_id: String,
+    /// COSE_Key (RFC 9052) as returned at registration.
+    pub public_key: Vec<u8>,
+    /// Last signature counter seen; authenticators that don't count keep it at 0.
+    pub sign_count: u32,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// Which WebAuthn ceremony a challenge was issued for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ceremony {
+    Registration,
+    Authentication,
+}
+
+impl Ceremony {
+    /// Name stored alongside the challenge.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Ceremony::Registration => "registration",
+            Ceremony::Authentication => "authentication",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "registration" => Some(Ceremony::Registration),
+            "authentication" => Some(Ceremony::Authentication),
+            _ => None,
+        }
+    }
+}
+
+/// A challenge handed to the browser, kept until the signed answer comes back.
+/// Passwordless logins start before we know who is logging in, so `user_id`
+/// is only set when the ceremony is tied to an account.
+#[derive(Clone, Debug)]
+pub struct PasskeyChallenge {
+    pub challenge_hash: String,
+    pub user_id: Option<Uuid>,
+    pub ceremony: Ceremony,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PasskeyInfo {
+    pub id: Uuid,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl From<&Passkey> for PasskeyInfo {
+    fn from(passkey: &Passkey) -> Self {
+        PasskeyInfo {
+            id: passkey.id,
+            name: passkey.name.clone(),
+            created_at: passkey.created_at,
+            last_used_at: passkey.last_used_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RelyingPartyEntity {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UserEntity {
+    /// User handle: the account id, base64url-encoded.
+    pub id: String,
+    pub name: String,
+    pub display_name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CredentialParameters {
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// COSE algorithm identifier, e.g. -7 for ES256.
+    pub alg: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CredentialDescriptor {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticatorSelection {
+    pub resident_key: String,
+    pub user_verification: String,
+}
+
+/// `PublicKeyCredentialCreationOptions` in the JSON form accepted by
+/// `PublicKeyCredential.parseCreationOptionsFromJSON`.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PasskeyCreationOptions {
+    pub challenge: String,
+    pub rp: RelyingPartyEntity,
+    pub user: UserEntity,
+    pub pub_key_cred_params: Vec<CredentialParameters>,
+    /// Milliseconds.
+    pub timeout: u64,
+    pub attestation: String,
+    pub exclude_credentials: Vec<CredentialDescriptor>,
+    pub authenticator_selection: AuthenticatorSelection,
+}
+
+/// `PublicKeyCredentialRequestOptions` in the JSON form accepted by
+/// `PublicKeyCredential.parseRequestOptionsFromJSON`.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PasskeyRequestOptions {
+    pub challenge: String,
+    pub rp_id: String,
+    /// Milliseconds.
+    pub timeout: u64,
+    /// Empty for a passwordless login, where the authenticator offers its passkeys.
+    pub allow_credentials: Vec<CredentialDescriptor>,
+    pub user_verification: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AttestationResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    pub attestation_object: String,
+}
+
+/// What `navigator.credentials.create()` returned, with binary fields base64url-encoded
+/// (`PublicKeyCredential.toJSON()`).
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RegistrationCredential {
+    pub id: String,
+    pub raw_id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub response: AttestationResponse,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AssertionResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    pub authenticator_data: String,
+    pub signature: String,
+    pub user_handle: Option<String>,
+}
+
+/// What `navigator.credentials.get()` returned, with binary fields base64url-encoded
+/// (`PublicKeyCredential.toJSON()`).
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticationCredential {
+    pub id: String,
+    pub raw_id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub response: AssertionResponse,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PasskeyRegistrationRequest {
+    /// Label to tell passkeys apart, e.g. "Work laptop".
+    pub name: Option<String>,
+    pub credential: RegistrationCredential,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct PasskeyLoginOptionsRequest {
+    /// Limits the login to this account's passkeys; omit it for a passwordless
+    /// login where the authenticator picks the account.
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PasskeyLoginRequest {
+    pub credential: AuthenticationCredential,
+}
@@ -0,0 +1,95 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Server-side record of an issued refresh token. Only the SHA-256 hash of
+/// the opaque token is kept. Every token rotated from the same login shares
+/// a `family_id`, so a replayed token can take down the whole chain.
+#[derive(Clone, Debug)]
+pub struct RefreshToken {
+    pub id: Uuid,
+    pub family_id: Uuid,
+    pub user_id: Uuid,
+    pub token_hash: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub revoked: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LogoutRequest {
+    /// Refresh token of the session to end; its whole rotation family is revoked.
+    pub refresh_token: Option<String>,
+}
+
+/// What a single-use token issued to a user lets its holder do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenPurpose {
+    PasswordReset,
+    EmailVerification,
+    /// Completes a login whose password was correct with a second factor.
+    MfaChallenge,
+}
+
+impl TokenPurpose {
+    /// Name stored alongside the token.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenPurpose::PasswordReset => "password_reset",
+            TokenPurpose::EmailVerification => "email_verification",
+            TokenPurpose::MfaChallenge => "mfa_challenge",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "password_reset" => Some(TokenPurpose::PasswordReset),
+            "email_verification" => Some(TokenPurpose::EmailVerification),
+            "mfa_challenge" => Some(TokenPurpose::MfaChallenge),
+            _ => None,
+        }
+    }
+}
+
+/// Server-side record of a single-use token, such as a password reset link
+/// or an MFA challenge. Only the SHA-256 hash is kept, and each token works once.
+#[derive(Clone, Debug)]
+pub struct ActionToken {
+    pub token_hash: String,
+    pub user_id: Uuid,
+    pub purpose: TokenPurpose,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ForgotPasswordRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ResetPasswordRequest {
+    /// Token from the reset link.
+    pub token: String,
+    pub new_password: String,
+    pub confirm_password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct VerifyEmailRequest {
+    /// Token from the verification link.
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ResendVerificationRequest {
+    pub email: String,
+}
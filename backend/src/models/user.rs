@@ -1,23 +1,60 @@
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
+use uuid::Uuid;
 
 #[derive(Serialize, Deserialize, ToSchema, Clone, Debug)]
 pub struct User {
-    pub id: i32,
+    pub id: Uuid,
     pub email: String,
     pub first_name: String,
     pub last_name: String,
     pub password: String, // Hashed in production
     pub role: Role,
+    /// Set once the user followed the link mailed to `email`.
+    pub email_verified: bool,
+    /// Set by an admin to lock the account out: it can't log in, and tokens
+    /// it already holds are refused.
+    pub disabled: bool,
+}
+
+/// A user that has not been persisted yet; it gets its id in `into_user`.
+#[derive(Clone, Debug)]
+pub struct NewUser {
+    pub email: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub password: String,
+    pub role: Role,
+    pub email_verified: bool,
+}
+
+impl NewUser {
+    /// Assigns a UUIDv7 id: unique across instances without coordination,
+    /// time-ordered for index locality, and with enough random bits that ids
+    /// can't be enumerated.
+    pub fn into_user(self) -> User {
+        User {
+            id: Uuid::now_v7(),
+            email: self.email,
+            first_name: self.first_name,
+            last_name: self.last_name,
+            password: self.password,
+            role: self.role,
+            email_verified: self.email_verified,
+            disabled: false,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct UserResponse { // This is the public user info returned after login
-    pub id: i32,
+    pub id: Uuid,
     pub email: String,
     pub first_name: String,
     pub last_name: String,
     pub role: Role,
+    pub email_verified: bool,
+    pub disabled: bool,
 }
 
 #[derive(Serialize, Deserialize, ToSchema, Clone, PartialEq, Debug)]
@@ -26,6 +63,58 @@ pub enum Role {
     User,
 }
 
+impl Role {
+    /// Name used when the role is persisted as text.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Admin => "Admin",
+            Role::User => "User",
+        }
+    }
+}
+
+impl std::str::FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Admin" => Ok(Role::Admin),
+            "User" => Ok(Role::User),
+            other => Err(format!("unknown role: {}", other)),
+        }
+    }
+}
+
+/// A rule of the configured password policy.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PasswordRule {
+    MinLength,
+    MaxLength,
+    Lowercase,
+    Uppercase,
+    Digit,
+    Symbol,
+    PersonalInfo,
+    Breached,
+}
+
+/// One way a proposed password breaks the policy, for display next to the field.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PasswordViolation {
+    pub rule: PasswordRule,
+    pub message: String,
+}
+
+/// 400 response for a password rejected by the policy.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PasswordPolicyError {
+    pub error: String,
+    /// Always `password`, the request field the violations apply to.
+    pub field: String,
+    pub violations: Vec<PasswordViolation>,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct RegisterRequest {
     pub first_name: String,
@@ -35,9 +124,28 @@ pub struct RegisterRequest {
     pub confirm_password: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+    pub confirm_password: String,
+    /// Also sign out every other session of the user. The caller's own
+    /// session is replaced by the tokens in the response.
+    #[serde(default)]
+    pub revoke_other_sessions: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ChangePasswordResponse {
+    pub message: String,
+    /// New tokens for the caller, present when other sessions were revoked.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session: Option<LoginResponse>,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct RegisterResponse {
-    pub id: i32,
+    pub id: Uuid,
     pub first_name: String,
     pub last_name: String,
     pub email: String,
@@ -54,4 +162,6 @@ pub struct LoginResponse {
     pub access_token: String,
     pub message: String,
     pub token_type: String,
+    pub refresh_token: String,
+    pub expires_in: u64, // access token lifetime in seconds
 }
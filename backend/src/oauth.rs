@@ -0,0 +1,164 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+
+use crate::models::{OAuthClient, User, UserInfo};
+use crate::utils::{constant_time_eq, hash_token};
+
+/// Scopes a user can grant through the authorization code grant. `openid`
+/// asks for an ID token (OpenID Connect), `profile` reads the user's names
+/// and profile, `email` their address. Clients acting for themselves may be
+/// registered with any other scopes, which resource servers interpret.
+pub const SUPPORTED_SCOPES: [&str; 3] = ["openid", "profile", "email"];
+/// Granted when an authorization request names no scope.
+pub const DEFAULT_SCOPE: &str = "profile";
+
+/// The scope that makes an authorization request an OpenID Connect one.
+pub const OPENID_SCOPE: &str = "openid";
+
+/// The only code challenge method accepted; `plain` offers no protection
+/// against an intercepted authorization request.
+pub const PKCE_METHOD: &str = "S256";
+
+/// The revocation subject of everything issued to a client: its own client
+/// credentials tokens and the tokens users delegated to it. Prefixed so an
+/// admin-chosen client id can't name a user.
+pub fn client_subject(client_id: &str) -> String {
+    format!("client:{}", client_id)
+}
+
+// RFC 7636 section 4.1
+const VERIFIER_MIN_LENGTH: usize = 43;
+const VERIFIER_MAX_LENGTH: usize = 128;
+
+// RFC 8628 section 6.1: consonants only, so codes don't spell words, in two groups of four
+const USER_CODE_ALPHABET: &[u8] = b"BCDFGHJKLMNPQRSTVWXZ";
+const USER_CODE_LENGTH: usize = 8;
+
+const MAX_CLIENT_ID_LENGTH: usize = 64;
+const MAX_NONCE_LENGTH: usize = 256;
+
+/// Client ids are limited to RFC 3986 unreserved characters, so they need no
+/// encoding in URLs or HTTP Basic credentials.
+pub fn is_valid_client_id(client_id: &str) -> bool {
+    (1..=MAX_CLIENT_ID_LENGTH).contains(&client_id.len())
+        && client_id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~'))
+}
+
+/// Whether `scope` is a single scope token (RFC 6749 section 3.3).
+pub fn is_valid_scope(scope: &str) -> bool {
+    !scope.is_empty() && scope.bytes().all(|b| matches!(b, 0x21 | 0x23..=0x5b | 0x5d..=0x7e))
+}
+
+/// Redirect URIs must be absolute and carry no fragment (RFC 6749 section 3.1.2).
+pub fn is_valid_redirect_uri(uri: &str) -> bool {
+    uri.split_once("://")
+        .is_some_and(|(scheme, rest)| !scheme.is_empty() && !rest.is_empty())
+        && !uri.contains('#')
+        && !uri.chars().any(char::is_whitespace)
+}
+
+/// Checks a presented secret against the client's stored hash. Public
+/// clients have no secret, so nothing matches.
+pub fn verify_client_secret(client: &OAuthClient, secret: &str) -> bool {
+    client
+        .secret_hash
+        .as_deref()
+        .is_some_and(|hash| constant_time_eq(hash_token(secret).as_bytes(), hash.as_bytes()))
+}
+
+/// Checks the requested scopes against `allowed` and returns them
+/// deduplicated, in the order asked for; `default` applies when none are
+/// requested. Unknown scopes are an error rather than silently dropped, so a
+/// client notices it asked for something it won't get.
+pub fn normalize_scope(requested: Option<&str>, default: &str, allowed: &[&str]) -> Result<String, String> {
+    let requested = requested.map(str::trim).filter(|s| !s.is_empty()).unwrap_or(default);
+
+    let mut scopes: Vec<&str> = Vec::new();
+    for scope in requested.split_whitespace() {
+        if !allowed.contains(&scope) {
+            return Err(format!("Scope '{}' is not allowed for this client", scope));
+        }
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+    Ok(scopes.join(" "))
+}
+
+/// Whether `scope` (space-separated) includes `wanted`.
+pub fn has_scope(scope: &str, wanted: &str) -> bool {
+    scope.split_whitespace().any(|s| s == wanted)
+}
+
+/// Nonces are opaque to us, but they are stored with the code and copied into
+/// the ID token, so they are kept short and printable.
+pub fn is_valid_nonce(nonce: &str) -> bool {
+    (1..=MAX_NONCE_LENGTH).contains(&nonce.len()) && nonce.bytes().all(|b| (0x20..=0x7e).contains(&b))
+}
+
+/// The claims about `user` that `scope` allows. First-party tokens carry no
+/// scope and see everything.
+pub fn user_info(user: &User, scope: Option<&str>) -> UserInfo {
+    let granted = |wanted| scope.is_none_or(|scope| has_scope(scope, wanted));
+    let profile = granted("profile");
+    let email = granted("email");
+    UserInfo {
+        sub: user.id.to_string(),
+        name: profile.then(|| format!("{} {}", user.first_name, user.last_name)),
+        given_name: profile.then(|| user.first_name.clone()),
+        family_name: profile.then(|| user.last_name.clone()),
+        email: email.then(|| user.email.clone()),
+        email_verified: email.then_some(user.email_verified),
+    }
+}
+
+/// Whether `challenge` looks like a BASE64URL-encoded SHA-256 digest.
+pub fn is_valid_challenge(challenge: &str) -> bool {
+    challenge.len() == 43 && URL_SAFE_NO_PAD.decode(challenge).is_ok()
+}
+
+/// Checks a code verifier against the S256 challenge of the authorization
+/// request (RFC 7636 section 4.6).
+pub fn verify_pkce(verifier: &str, challenge: &str) -> bool {
+    let well_formed = (VERIFIER_MIN_LENGTH..=VERIFIER_MAX_LENGTH).contains(&verifier.len())
+        && verifier
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~'));
+    if !well_formed {
+        return false;
+    }
+    let computed = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+    constant_time_eq(computed.as_bytes(), challenge.as_bytes())
+}
+
+/// A new user code for the device grant, formatted for display (`BDWP-HQPK`).
+/// 20^8 codes give about 34 bits, plenty for a code that lives minutes and
+/// has to be entered by a logged-in user.
+pub fn generate_user_code() -> String {
+    let mut rng = rand::thread_rng();
+    let letters: String = (0..USER_CODE_LENGTH)
+        .map(|_| USER_CODE_ALPHABET[rng.gen_range(0..USER_CODE_ALPHABET.len())] as char)
+        .collect();
+    format!("{}-{}", &letters[..4], &letters[4..])
+}
+
+/// A user code as typed by the user, uppercased and without dashes or
+/// spaces, or `None` if it can't be one of ours.
+pub fn normalize_user_code(input: &str) -> Option<String> {
+    let code: String = input
+        .chars()
+        .filter(|c| *c != '-' && !c.is_whitespace())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    (code.len() == USER_CODE_LENGTH && code.bytes().all(|b| USER_CODE_ALPHABET.contains(&b))).then_some(code)
+}
+
+/// Appends `params` to the query of `uri`, keeping any query it already has.
+pub fn with_query(uri: &str, params: &[(&str, &str)]) -> String {
+    let query = serde_urlencoded::to_string(params).expect("string pairs always encode");
+    let separator = if uri.contains('?') { '&' } else { '?' };
+    format!("{}{}{}", uri, separator, query)
+}
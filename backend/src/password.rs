@@ -0,0 +1,419 @@
+use argon2::{
+    password_hash::{PasswordHasher, PasswordVerifier},
+    Algorithm, Argon2, Params, PasswordHash, Version,
+};
+use axum::{http::StatusCode, Json};
+use serde_json::{json, Value};
+use std::{fmt, sync::Arc};
+use tokio::sync::Semaphore;
+
+use crate::breach::BreachedPasswords;
+use crate::models::{PasswordPolicyError, PasswordRule, PasswordViolation};
+use crate::utils::{Argon2Settings, PasswordPolicy};
+
+#[derive(Debug)]
+pub struct PasswordError(pub String);
+
+impl fmt::Display for PasswordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "password hashing error: {}", self.0)
+    }
+}
+
+impl std::error::Error for PasswordError {}
+
+/// Outcome of checking a password against a stored hash.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verification {
+    Invalid,
+    Valid,
+    /// The password is right, but the hash uses an older algorithm or
+    /// weaker parameters and should be replaced.
+    ValidNeedsRehash,
+}
+
+fn hasher(settings: &Argon2Settings) -> Result<Argon2<'static>, PasswordError> {
+    let params = Params::new(settings.memory_kib, settings.iterations, settings.parallelism, None)
+        .map_err(|e| PasswordError(e.to_string()))?;
+    Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+}
+
+/// Hashes a password with Argon2id and a fresh random salt, encoded as a PHC string.
+pub fn hash_password(settings: &Argon2Settings, password: &str) -> Result<String, PasswordError> {
+    hasher(settings)?
+        .hash_password(password.as_bytes())
+        .map(|hash| hash.to_string())
+        .map_err(|e| PasswordError(e.to_string()))
+}
+
+/// Checks a password against a stored Argon2 (PHC) or legacy bcrypt hash.
+pub fn verify_password(settings: &Argon2Settings, password: &str, stored: &str) -> Verification {
+    if stored.starts_with("$2") {
+        return match bcrypt::verify(password, stored) {
+            Ok(true) => Verification::ValidNeedsRehash,
+            Ok(false) => Verification::Invalid,
+            Err(e) => {
+                tracing::warn!("Unreadable bcrypt hash: {}", e);
+                Verification::Invalid
+            }
+        };
+    }
+
+    let hash = match PasswordHash::new(stored) {
+        Ok(hash) => hash,
+        Err(e) => {
+            tracing::warn!("Unreadable password hash: {}", e);
+            return Verification::Invalid;
+        }
+    };
+
+    // The algorithm, version and cost are all taken from the stored hash
+    if Argon2::default().verify_password(password.as_bytes(), &hash).is_err() {
+        return Verification::Invalid;
+    }
+
+    if is_current(settings, &hash) {
+        Verification::Valid
+    } else {
+        Verification::ValidNeedsRehash
+    }
+}
+
+/// Whether the hash was made with Argon2id and exactly the configured parameters.
+fn is_current(settings: &Argon2Settings, hash: &PasswordHash) -> bool {
+    let Ok(params) = Params::try_from(hash) else {
+        return false;
+    };
+
+    hash.algorithm == argon2::ARGON2ID_IDENT
+        && hash.version == Some(Version::V0x13.into())
+        && params.m_cost() == settings.memory_kib
+        && params.t_cost() == settings.iterations
+        && params.p_cost() == settings.parallelism
+}
+
+/// Runs hashing and verification on tokio's blocking pool, at most
+/// `max_concurrent` at a time. Slow hashes never stall the async workers, and
+/// a burst of logins queues for a permit instead of allocating Argon2 memory
+/// for every request at once.
+#[derive(Debug)]
+pub struct PasswordPool {
+    settings: Arc<Argon2Settings>,
+    permits: Arc<Semaphore>,
+    dummy_hash: String,
+}
+
+impl PasswordPool {
+    /// Hashes the dummy password up front, so the first login with an unknown
+    /// email doesn't take longer than the ones after it.
+    pub fn new(settings: Argon2Settings, max_concurrent: usize) -> Result<Self, PasswordError> {
+        let dummy_hash = hash_password(&settings, &crate::utils::generate_opaque_token())?;
+        Ok(Self {
+            settings: Arc::new(settings),
+            permits: Arc::new(Semaphore::new(max_concurrent.max(1))),
+            dummy_hash,
+        })
+    }
+
+    /// Hashes a password with the configured Argon2id parameters.
+    pub async fn hash(&self, password: &str) -> Result<String, PasswordError> {
+        let password = password.to_string();
+        self.run(move |settings| hash_password(settings, &password)).await?
+    }
+
+    /// Checks a password against a stored hash; see [`verify_password`].
+    pub async fn verify(&self, password: &str, stored: &str) -> Result<Verification, PasswordError> {
+        let (password, stored) = (password.to_string(), stored.to_string());
+        self.run(move |settings| verify_password(settings, &password, &stored)).await
+    }
+
+    /// Spends as long as verifying a real password would, for logins with an
+    /// unknown email, so response times don't reveal which accounts exist.
+    /// The dummy is an Argon2 hash with the current settings, so accounts
+    /// still on bcrypt or older costs take a different time until they log in
+    /// and get rehashed.
+    pub async fn verify_dummy(&self, password: &str) -> Result<(), PasswordError> {
+        self.verify(password, &self.dummy_hash).await.map(|_| ())
+    }
+
+    /// Takes every free permit, so work queues until the guard is dropped.
+    #[cfg(test)]
+    pub(crate) async fn occupy(&self) -> tokio::sync::OwnedSemaphorePermit {
+        let free = self.permits.available_permits() as u32;
+        self.permits.clone().acquire_many_owned(free).await.expect("the semaphore is never closed")
+    }
+
+    async fn run<T, F>(&self, work: F) -> Result<T, PasswordError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Argon2Settings) -> T + Send + 'static,
+    {
+        let permit = self
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|e| PasswordError(e.to_string()))?;
+        let settings = self.settings.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            work(&settings)
+        })
+        .await
+        .map_err(|e| PasswordError(e.to_string()))
+    }
+}
+
+/// Whose password is being set, for the personal information rule.
+pub struct PasswordOwner<'a> {
+    pub email: &'a str,
+    pub first_name: &'a str,
+    pub last_name: &'a str,
+}
+
+/// Every rule of `policy` that `password` breaks, plus whether it appears in
+/// the breached corpus; empty if it is acceptable.
+pub fn policy_violations(
+    policy: &PasswordPolicy,
+    breached: &BreachedPasswords,
+    password: &str,
+    owner: &PasswordOwner,
+) -> Vec<PasswordViolation> {
+    let mut violations = Vec::new();
+    let mut violate = |rule, message: String| violations.push(PasswordViolation { rule, message });
+
+    let length = password.chars().count();
+    if length < policy.min_length {
+        violate(PasswordRule::MinLength, format!("Must be at least {} characters", policy.min_length));
+    }
+    if length > policy.max_length {
+        violate(PasswordRule::MaxLength, format!("Must be at most {} characters", policy.max_length));
+    }
+    if policy.require_lowercase && !password.chars().any(char::is_lowercase) {
+        violate(PasswordRule::Lowercase, "Must contain a lowercase letter".to_string());
+    }
+    if policy.require_uppercase && !password.chars().any(char::is_uppercase) {
+        violate(PasswordRule::Uppercase, "Must contain an uppercase letter".to_string());
+    }
+    if policy.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+        violate(PasswordRule::Digit, "Must contain a digit".to_string());
+    }
+    if policy.require_symbol && !password.chars().any(|c| !c.is_alphanumeric() && !c.is_whitespace()) {
+        violate(PasswordRule::Symbol, "Must contain a symbol".to_string());
+    }
+    if policy.disallow_personal_info && contains_personal_info(password, owner) {
+        violate(PasswordRule::PersonalInfo, "Must not contain your email or name".to_string());
+    }
+    if breached.contains(password) {
+        violate(
+            PasswordRule::Breached,
+            "Has appeared in a data breach; choose a different password".to_string(),
+        );
+    }
+
+    violations
+}
+
+fn contains_personal_info(password: &str, owner: &PasswordOwner) -> bool {
+    let password = password.to_lowercase();
+    let local_part = owner.email.split('@').next().unwrap_or_default();
+
+    // Very short fragments would reject too many unrelated passwords
+    [owner.email, local_part, owner.first_name, owner.last_name]
+        .iter()
+        .map(|fragment| fragment.trim().to_lowercase())
+        .any(|fragment| fragment.chars().count() >= 3 && password.contains(&fragment))
+}
+
+/// Checks `password` against the policy, producing the 400 response handlers
+/// return when it is rejected.
+pub fn enforce_policy(
+    policy: &PasswordPolicy,
+    breached: &BreachedPasswords,
+    password: &str,
+    owner: &PasswordOwner,
+) -> Result<(), (StatusCode, Json<Value>)> {
+    let violations = policy_violations(policy, breached, password, owner);
+    if violations.is_empty() {
+        return Ok(());
+    }
+
+    let error = violations
+        .iter()
+        .map(|v| v.message.as_str())
+        .collect::<Vec<_>>()
+        .join("; ");
+    let body = PasswordPolicyError {
+        error,
+        field: "password".to_string(),
+        violations,
+    };
+    Err((StatusCode::BAD_REQUEST, Json(json!(body))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{breach::password_key, test_support};
+
+    const OWNER: PasswordOwner<'static> = PasswordOwner {
+        email: "ada.lovelace@example.com",
+        first_name: "Ada",
+        last_name: "Lovelace",
+    };
+
+    fn settings() -> Argon2Settings {
+        test_support::config(crate::utils::StorageBackend::Memory).argon2
+    }
+
+    #[test]
+    fn bcrypt_hashes_verify_but_ask_for_a_rehash() {
+        let stored = bcrypt::hash("hunter22", 4).unwrap();
+        assert_eq!(verify_password(&settings(), "hunter22", &stored), Verification::ValidNeedsRehash);
+        assert_eq!(verify_password(&settings(), "hunter23", &stored), Verification::Invalid);
+        assert_eq!(verify_password(&settings(), "hunter22", "$2b$04$truncated"), Verification::Invalid);
+    }
+
+    #[test]
+    fn argon2_hashes_with_other_parameters_ask_for_a_rehash() {
+        let settings = settings();
+        let stored = hash_password(&settings, "hunter22").unwrap();
+        assert_eq!(verify_password(&settings, "hunter22", &stored), Verification::Valid);
+        assert_eq!(verify_password(&settings, "hunter23", &stored), Verification::Invalid);
+
+        let stronger = Argon2Settings { iterations: settings.iterations + 1, ..settings.clone() };
+        assert_eq!(verify_password(&stronger, "hunter22", &stored), Verification::ValidNeedsRehash);
+        let roomier = Argon2Settings { memory_kib: settings.memory_kib * 2, ..settings.clone() };
+        assert_eq!(verify_password(&roomier, "hunter22", &stored), Verification::ValidNeedsRehash);
+
+        // Argon2i with the configured cost is still another algorithm
+        let params = Params::new(settings.memory_kib, settings.iterations, settings.parallelism, None).unwrap();
+        let argon2i = Argon2::new(Algorithm::Argon2i, Version::V0x13, params)
+            .hash_password(b"hunter22")
+            .unwrap()
+            .to_string();
+        assert_eq!(verify_password(&settings, "hunter22", &argon2i), Verification::ValidNeedsRehash);
+    }
+
+    #[tokio::test]
+    async fn pool_runs_at_most_max_concurrent_hashes() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let pool = Arc::new(PasswordPool::new(settings(), 2).unwrap());
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        let tasks: Vec<_> = (0..8)
+            .map(|_| {
+                let (pool, running, peak) = (pool.clone(), running.clone(), peak.clone());
+                tokio::spawn(async move {
+                    pool.run(move |_| {
+                        let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                        peak.fetch_max(now, Ordering::SeqCst);
+                        std::thread::sleep(std::time::Duration::from_millis(20));
+                        running.fetch_sub(1, Ordering::SeqCst);
+                    })
+                    .await
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn hashing_waits_for_a_permit() {
+        let pool = PasswordPool::new(settings(), 1).unwrap();
+        let held = pool.occupy().await;
+
+        let wait = std::time::Duration::from_millis(100);
+        assert!(tokio::time::timeout(wait, pool.hash("hunter22")).await.is_err());
+        drop(held);
+        assert!(tokio::time::timeout(wait * 20, pool.hash("hunter22")).await.unwrap().is_ok());
+    }
+
+    fn policy() -> PasswordPolicy {
+        test_support::config(crate::utils::StorageBackend::Memory).password_policy
+    }
+
+    fn rules(
+        policy: &PasswordPolicy,
+        breached: &BreachedPasswords,
+        password: &str,
+        owner: &PasswordOwner,
+    ) -> Vec<PasswordRule> {
+        policy_violations(policy, breached, password, owner).into_iter().map(|v| v.rule).collect()
+    }
+
+    fn check(policy: &PasswordPolicy, password: &str) -> Vec<PasswordRule> {
+        rules(policy, &BreachedPasswords::Disabled, password, &OWNER)
+    }
+
+    #[test]
+    fn counts_length_in_characters() {
+        let policy = policy();
+        assert_eq!(check(&policy, "seven77"), [PasswordRule::MinLength]);
+        assert!(check(&policy, "eight888").is_empty());
+        // Eight characters, sixteen bytes
+        assert!(check(&policy, "ééééééé8").is_empty());
+        assert!(check(&policy, &"x".repeat(128)).is_empty());
+        assert_eq!(check(&policy, &"x".repeat(129)), [PasswordRule::MaxLength]);
+    }
+
+    #[test]
+    fn requires_the_configured_character_classes() {
+        let mut policy = policy();
+        assert!(check(&policy, "alllowercase").is_empty());
+
+        policy.require_lowercase = true;
+        policy.require_uppercase = true;
+        policy.require_digit = true;
+        policy.require_symbol = true;
+        assert_eq!(
+            check(&policy, "        "),
+            [PasswordRule::Lowercase, PasswordRule::Uppercase, PasswordRule::Digit, PasswordRule::Symbol]
+        );
+        assert_eq!(check(&policy, "ÉCOLE-42"), [PasswordRule::Lowercase]);
+        assert_eq!(check(&policy, "Passw0rd"), [PasswordRule::Symbol]);
+        assert!(check(&policy, "Passw0rd!").is_empty());
+    }
+
+    #[test]
+    fn rejects_the_owners_email_and_name() {
+        let mut policy = policy();
+        assert_eq!(check(&policy, "xxADA.LOVELACExx"), [PasswordRule::PersonalInfo]);
+        assert_eq!(check(&policy, "lovelace1815"), [PasswordRule::PersonalInfo]);
+        assert_eq!(check(&policy, "i-am-ada-now"), [PasswordRule::PersonalInfo]);
+
+        // Fragments under three characters are ignored
+        let owner = PasswordOwner { email: "al@example.com", first_name: "Al", last_name: "Wu" };
+        let breached = BreachedPasswords::Disabled;
+        assert!(rules(&policy, &breached, "always-wupper", &owner).is_empty());
+        assert_eq!(rules(&policy, &breached, "mail-al@example.com", &owner), [PasswordRule::PersonalInfo]);
+
+        policy.disallow_personal_info = false;
+        assert!(check(&policy, "lovelace1815").is_empty());
+    }
+
+    #[test]
+    fn rejects_breached_passwords() {
+        let breached = BreachedPasswords::Hashes(vec![password_key("password1")]);
+        let violations = policy_violations(&policy(), &breached, "password1", &OWNER);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].rule, PasswordRule::Breached);
+        assert!(rules(&policy(), &breached, "password2", &OWNER).is_empty());
+    }
+
+    #[test]
+    fn lists_every_violation_in_the_response() {
+        let (status, Json(body)) =
+            enforce_policy(&policy(), &BreachedPasswords::Disabled, "ada", &OWNER).unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["field"], "password");
+        assert_eq!(body["violations"].as_array().unwrap().len(), 2);
+        assert_eq!(body["error"], "Must be at least 8 characters; Must not contain your email or name");
+    }
+}
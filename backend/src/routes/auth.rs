@@ -1,25 +1,32 @@
 use axum::extract::State;
 use axum::{http::StatusCode, response::IntoResponse, Json};
-use jsonwebtoken::{encode, EncodingKey, Header};
+use chrono::Utc;
 use serde_json::json;
+use tracing::warn;
 use utoipa::{OpenApi};
 
-use crate::middleware::auth::Claims;
 use crate::models::user::*;
+use crate::mail::Email;
+use crate::models::RefreshRequest;
+use crate::password::{enforce_policy, PasswordOwner, Verification};
+use crate::store::StoreError;
+use crate::routes::mfa::mfa_challenge;
+use crate::routes::verification::send_verification_link;
+use crate::tokens::issue_tokens;
 use crate::AppState;
-use crate::utils::is_valid_email;
+use crate::email::normalize_email;
+use crate::utils::hash_token;
 
 #[derive(OpenApi)]
-#[openapi(paths(login, register), components(schemas(LoginRequest, LoginResponse, RegisterRequest)))]
+#[openapi(paths(login, register, refresh), components(schemas(LoginRequest, LoginResponse, RegisterRequest, RefreshRequest)))]
 pub struct AuthApi;
 
-
 #[utoipa::path(
     post,
     path = "/login",
     request_body = LoginRequest,
     responses(
-        (status = 200, description = "Login successful", body = LoginResponse),
+        (status = 200, description = "Login successful, or an MFA challenge (`mfa_required`) to complete at /login/mfa when the account has TOTP enabled", body = LoginResponse),
         (status = 401, description = "Invalid credentials")
     )
 )]
@@ -27,46 +34,81 @@ pub async fn login(
     State(state): State<AppState>,
     Json(payload): Json<LoginRequest>,
 ) -> impl IntoResponse {
-    let users = state.users.lock().unwrap();
+    // Look the user up by their normalized email; a malformed address can't
+    // belong to anyone, but still pays for a password verification below
+    let found = match normalize_email(&payload.email, state.config.email_local_part_folding) {
+        Ok(email) => match state.users.find_by_email(&email).await {
+            Ok(found) => found,
+            Err(e) => return e.into_response(),
+        },
+        Err(_) => None,
+    };
+    // The store call above has returned, so no lock is held while hashing
+    let verification = match &found {
+        Some(user) => match state.passwords.verify(&payload.password, &user.password).await {
+            Ok(verification) => verification,
+            Err(e) => {
+                tracing::error!("{}", e);
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(json!({"error": "Internal server error"})),
+                )
+                    .into_response();
+            }
+        },
+        None => {
+            // Unknown emails pay for a verification too, so timing doesn't reveal them
+            if let Err(e) = state.passwords.verify_dummy(&payload.password).await {
+                tracing::error!("{}", e);
+            }
+            Verification::Invalid
+        }
+    };
 
-    //Check if the user exist and the password matches
-    let user = users.iter().find(|u| u.email == payload.email);
+    let mut user = match (found, verification) {
+        (Some(user), Verification::Valid | Verification::ValidNeedsRehash) => user,
+        _ => {
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(json!({"error": "Invalid credentials"})),
+            )
+                .into_response();
+        }
+    };
 
-    if user.is_none()
-        || bcrypt::verify(payload.password.as_bytes(), &user.unwrap().password).ok() != Some(true)
-    {
+    // Only someone who knows the password learns that the account is disabled
+    if user.disabled {
         return (
-            StatusCode::UNAUTHORIZED,
-            Json(json!({"error": "Invalid credentials"})),
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "This account is disabled"})),
         )
             .into_response();
     }
 
-    let user = user.unwrap();
-    let claims = Claims {
-        sub: user.id.to_string(),
-        email: user.email.clone(),
-        first_name: user.first_name.clone(),
-        last_name: user.last_name.clone(),
-        role: user.role.clone(),
-        exp: (chrono::Utc::now() + chrono::Duration::hours(24)).timestamp() as usize,
-    };
-
-    let config = state.config.clone();
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(config.jwt_secret.as_ref()),
-    )
-    .unwrap();
+    // Upgrade bcrypt and outdated Argon2 hashes while we have the plaintext
+    if verification == Verification::ValidNeedsRehash {
+        match state.passwords.hash(&payload.password).await {
+            Ok(hash) => {
+                user.password = hash;
+                if let Err(e) = state.users.update(&user).await {
+                    warn!("Failed to store rehashed password for {}: {}", user.id, e);
+                }
+            }
+            Err(e) => warn!("Failed to rehash password for {}: {}", user.id, e),
+        }
+    }
 
-    let response = LoginResponse {
-        access_token: token,
-        token_type: "Bearer".to_string(),
-        message: "Login successful".to_string(),       
-    };
+    // With TOTP enabled the password alone only earns a challenge
+    match state.mfa.find_totp(user.id).await {
+        Ok(Some(totp)) if totp.enabled_at.is_some() => return mfa_challenge(&state, &user).await,
+        Ok(_) => {}
+        Err(e) => return e.into_response(),
+    }
 
-    return (StatusCode::OK, Json(response)).into_response();
+    match issue_tokens(&state, &user, None, "Login successful").await {
+        Ok(response) => (StatusCode::OK, Json(response)).into_response(),
+        Err(e) => e.into_response(),
+    }
 }
 
 #[utoipa::path(
@@ -75,7 +117,8 @@ pub async fn login(
     request_body = RegisterRequest,
     responses(
         (status = 201, description = "User registered successfully", body = LoginResponse),
-        (status = 400, description = "Bad request")
+        (status = 202, description = "Registration received (CONCEAL_EXISTING_ACCOUNTS mode, also for taken emails)"),
+        (status = 400, description = "Bad request, with per-rule `violations` when the password breaks the policy", body = PasswordPolicyError)
     )
 )]
 pub async fn register(
@@ -103,66 +146,271 @@ pub async fn register(
         .into_response();
     }
 
-    if !is_valid_email(&payload.email) {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(json!({"error": "Invalid email format"})),
-        ).into_response();
-    }
-
-    let config = state.config.clone();
-    
-    // Here you would typically hash the password and save the user to a database
-    let hashed_password = bcrypt::hash_with_salt(payload.password, bcrypt::DEFAULT_COST, config.jwt_salt).unwrap();
-
-    let mut users = state.users.lock().unwrap();
+    // Accounts are stored and looked up under the canonical form of the address
+    let email = match normalize_email(&payload.email, state.config.email_local_part_folding) {
+        Ok(email) => email,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": format!("Invalid email format: {}", e.0), "field": "email"})),
+            )
+                .into_response();
+        }
+    };
 
-    if users.iter().any(|u| u.email == payload.email) {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(json!({"error": "User already exists"})),
-        )
-        .into_response();
+    let owner = PasswordOwner {
+        email: &email,
+        first_name: &payload.first_name,
+        last_name: &payload.last_name,
+    };
+    if let Err(rejection) = enforce_policy(&state.config.password_policy, &state.breached_passwords, &payload.password, &owner) {
+        return rejection.into_response();
     }
 
+    let hashed_password = match state.passwords.hash(&payload.password).await {
+        Ok(hash) => hash,
+        Err(e) => {
+            tracing::error!("{}", e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": "Hash failure"})),
+            )
+                .into_response();
+        }
+    };
+
     let first_name = payload.first_name.clone();
     let last_name = payload.last_name.clone();
 
-    let new_user = User {
-        id: users.len() as i32 + 1,
-        email: payload.email.clone(),
+    let new_user = NewUser {
+        email: email.clone(),
         first_name: first_name.clone(),
         last_name: last_name.clone(),
-        password: hashed_password.to_string(),
+        password: hashed_password,
         role: Role::User,
+        email_verified: false,
     };
 
-    users.push(new_user.clone());
-
-    // Generate JWT token for the new user
-    let claims = Claims {
-        sub: new_user.id.to_string(),
-        email: new_user.email.clone(),
-        first_name: new_user.first_name.clone(),
-        last_name: new_user.last_name.clone(),
-        role: Role::User,
-        exp: (chrono::Utc::now() + chrono::Duration::seconds(config.jwt_expiration_secs as i64)).timestamp() as usize,
-    };
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(config.jwt_secret.as_ref()),
-    ).unwrap();
-
-    let response = LoginResponse {
-        access_token: token.clone(),
-        message: "User registered successfully".to_string(),
-        token_type: "Bearer".to_string()
+    let new_user = match state.users.insert(new_user).await {
+        Ok(user) => user,
+        Err(StoreError::Conflict) if state.config.conceal_existing_accounts => {
+            // Tell the owner instead of the caller; don't wait on delivery so timing matches
+            let mailer = state.mailer.clone();
+            let notice = existing_account_notice(&email);
+            tokio::spawn(async move {
+                if let Err(e) = mailer.send(notice).await {
+                    warn!("Failed to send existing account notice: {}", e);
+                }
+            });
+            return registration_received();
+        }
+        Err(StoreError::Conflict) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "User already exists"})),
+            )
+            .into_response();
+        }
+        Err(e) => return e.into_response(),
     };
 
+    tokio::spawn(send_verification_link(state.clone(), new_user.clone()));
+
+    if state.config.conceal_existing_accounts {
+        return registration_received();
+    }
+
+    // Log the new user in straight away; until verified the token may be restricted
+    let message = "User registered successfully. Check your email to verify your address.";
+    match issue_tokens(&state, &new_user, None, message).await {
+        Ok(response) => (StatusCode::CREATED, Json(response)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// The answer to every registration when `CONCEAL_EXISTING_ACCOUNTS` is on,
+/// whether or not the email was already taken.
+fn registration_received() -> axum::response::Response {
     (
-        StatusCode::CREATED,
-        Json(response),
+        StatusCode::ACCEPTED,
+        Json(json!({"message": "Registration received. Check your email, then log in."})),
     )
-    .into_response()
+        .into_response()
+}
+
+fn existing_account_notice(email: &str) -> Email {
+    Email {
+        to: email.to_string(),
+        subject: "Someone tried to register with your email".to_string(),
+        body: "Somebody, perhaps you, tried to create an account with this email address, \
+               but you already have one. If it was you, just log in. If not, you can ignore this message."
+            .to_string(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/token/refresh",
+    request_body = RefreshRequest,
+    responses(
+        (status = 200, description = "New access and refresh tokens", body = LoginResponse),
+        (status = 401, description = "Invalid, expired, revoked or reused refresh token")
+    )
+)]
+pub async fn refresh(
+    State(state): State<AppState>,
+    Json(payload): Json<RefreshRequest>,
+) -> impl IntoResponse {
+    let invalid = || {
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(json!({"error": "Invalid refresh token"})),
+        )
+            .into_response()
+    };
+
+    let token_hash = hash_token(&payload.refresh_token);
+    let now = Utc::now();
+
+    let token = match state.refresh_tokens.find_by_hash(&token_hash).await {
+        Ok(Some(token)) => token,
+        Ok(None) => return invalid(),
+        Err(e) => return e.into_response(),
+    };
+
+    if token.revoked || token.expires_at <= now {
+        return invalid();
+    }
+
+    // A refresh token can be exchanged only once. Seeing it again means it
+    // leaked, so the whole family is revoked and its holder must log in again.
+    match state.refresh_tokens.mark_used(&token_hash, now).await {
+        Ok(true) => {}
+        Ok(false) => {
+            warn!(
+                "Refresh token reuse detected for user {}, revoking family {}",
+                token.user_id, token.family_id
+            );
+            if let Err(e) = state.refresh_tokens.revoke_family(token.family_id).await {
+                return e.into_response();
+            }
+            return invalid();
+        }
+        Err(e) => return e.into_response(),
+    }
+
+    let user = match state.users.find_by_id(token.user_id).await {
+        Ok(Some(user)) if !user.disabled => user,
+        Ok(_) => return invalid(),
+        Err(e) => return e.into_response(),
+    };
+
+    match issue_tokens(&state, &user, Some(token.family_id), "Token refreshed").await {
+        Ok(response) => (StatusCode::OK, Json(response)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    async fn exchange(state: &AppState, refresh_token: &str) -> Result<LoginResponse, StatusCode> {
+        let payload = RefreshRequest { refresh_token: refresh_token.to_string() };
+        let response = refresh(State(state.clone()), Json(payload)).await.into_response();
+        match response.status() {
+            StatusCode::OK => Ok(serde_json::from_value(test_support::json_body(response).await).unwrap()),
+            status => Err(status),
+        }
+    }
+
+    async fn states() -> Vec<AppState> {
+        let mut states = vec![test_support::memory_state().await, test_support::sqlite_state().await];
+        states.extend(test_support::postgres_state().await);
+        states
+    }
+
+    #[tokio::test]
+    async fn rotated_token_cannot_be_used_again() {
+        for state in states().await {
+            let user = test_support::user(&state, "ada@example.com").await;
+            let first = issue_tokens(&state, &user, None, "Login successful").await.unwrap();
+
+            let second = exchange(&state, &first.refresh_token).await.unwrap();
+            assert_ne!(second.refresh_token, first.refresh_token);
+            assert_eq!(exchange(&state, &first.refresh_token).await.unwrap_err(), StatusCode::UNAUTHORIZED);
+        }
+    }
+
+    #[tokio::test]
+    async fn replaying_an_old_token_revokes_the_family() {
+        for state in states().await {
+            let user = test_support::user(&state, "ada@example.com").await;
+            let first = issue_tokens(&state, &user, None, "Login successful").await.unwrap();
+            let other_session = issue_tokens(&state, &user, None, "Login successful").await.unwrap();
+            let second = exchange(&state, &first.refresh_token).await.unwrap();
+            let third = exchange(&state, &second.refresh_token).await.unwrap();
+
+            // A thief replaying the first token cuts off the legitimate holder too
+            assert_eq!(exchange(&state, &first.refresh_token).await.unwrap_err(), StatusCode::UNAUTHORIZED);
+            assert_eq!(exchange(&state, &third.refresh_token).await.unwrap_err(), StatusCode::UNAUTHORIZED);
+
+            // Other logins of the same user are separate families
+            assert!(exchange(&state, &other_session.refresh_token).await.is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn unknown_emails_pay_for_a_verification() {
+        let state = test_support::memory_state().await;
+        let wait = std::time::Duration::from_millis(100);
+
+        for email in ["nobody@example.com", "not an address"] {
+            // With the pool busy, a login that verifies anything has to wait
+            let held = state.passwords.occupy().await;
+            let payload = LoginRequest { email: email.to_string(), password: test_support::PASSWORD.to_string() };
+            let attempt = login(State(state.clone()), Json(payload));
+            tokio::pin!(attempt);
+            assert!(tokio::time::timeout(wait, &mut attempt).await.is_err(), "{} answered at once", email);
+
+            drop(held);
+            let response = attempt.await.into_response();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+    }
+
+    #[tokio::test]
+    async fn login_upgrades_bcrypt_hashes() {
+        for state in states().await {
+            let mut user = test_support::user(&state, "ada@example.com").await;
+            user.password = bcrypt::hash(test_support::PASSWORD, 4).unwrap();
+            state.users.update(&user).await.unwrap();
+
+            for _ in 0..2 {
+                let payload = LoginRequest { email: user.email.clone(), password: test_support::PASSWORD.to_string() };
+                let response = login(State(state.clone()), Json(payload)).await.into_response();
+                assert_eq!(response.status(), StatusCode::OK);
+
+                let stored = state.users.find_by_id(user.id).await.unwrap().unwrap().password;
+                assert!(stored.starts_with("$argon2id$"), "{}", stored);
+                assert_eq!(state.passwords.verify(test_support::PASSWORD, &stored).await.unwrap(), Verification::Valid);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn disabled_user_can_neither_log_in_nor_refresh() {
+        for state in states().await {
+            let mut user = test_support::user(&state, "ada@example.com").await;
+            let session = issue_tokens(&state, &user, None, "Login successful").await.unwrap();
+            user.disabled = true;
+            state.users.update(&user).await.unwrap();
+
+            let payload = LoginRequest { email: user.email.clone(), password: test_support::PASSWORD.to_string() };
+            let response = login(State(state.clone()), Json(payload)).await.into_response();
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+            assert_eq!(exchange(&state, &session.refresh_token).await.unwrap_err(), StatusCode::UNAUTHORIZED);
+        }
+    }
 }
@@ -0,0 +1,373 @@
+use axum::{
+    extract::{Extension, Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{Duration, Utc};
+use jsonwebtoken::Algorithm;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use utoipa::OpenApi;
+use uuid::Uuid;
+
+use crate::{
+    keys::{self, SigningKey},
+    middleware::auth::Claims,
+    models::{AddSigningKeyRequest, KeyStatus, Role, SigningKeyInfo, SigningKeyRecord},
+    store::StoreError,
+    AppState,
+};
+
+/// Admin endpoints to rotate the access token signing keys. A rotation is
+/// add (pending, published in the JWKS), promote (starts signing) and retire
+/// (stops verifying once the last token it signed has expired). Instances
+/// sharing the store pick changes up within a minute, so a key can only be
+/// promoted once it is that old.
+#[derive(OpenApi)]
+#[openapi(
+    paths(list_keys, add_key, promote_key, retire_key),
+    components(schemas(KeyStatus, SigningKeyInfo, AddSigningKeyRequest)),
+)]
+pub struct KeysApi;
+
+type ApiError = (StatusCode, Json<Value>);
+
+fn require_admin(claims: &Claims) -> Result<(), ApiError> {
+    if claims.role != Role::Admin {
+        return Err((StatusCode::FORBIDDEN, Json(json!({ "error": "Admin access required" }))));
+    }
+    Ok(())
+}
+
+fn key_not_found() -> ApiError {
+    (StatusCode::NOT_FOUND, Json(json!({ "error": "Signing key not found" })))
+}
+
+/// Rebuilds the keyset after a change and returns the stored entry for `kid`.
+async fn reload_and_describe(state: &AppState, kid: &str) -> Result<SigningKeyInfo, ApiError> {
+    if let Err(e) = state.keys.reload().await {
+        tracing::error!("Failed to reload signing keys: {}", e);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "Internal server error" }))));
+    }
+
+    let records = state.keys.store().list().await?;
+    let record = records.iter().find(|r| r.kid == kid).ok_or_else(key_not_found)?;
+    Ok(state.keys.describe(record))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/keys",
+    tag = "keys",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Every key in the keyset", body = [SigningKeyInfo]),
+        (status = 401, description = "Unauthorized - Invalid or missing token"),
+        (status = 403, description = "Forbidden - Admin access required")
+    )
+)]
+/// GET /admin/keys
+/// Lists the signing keys and their rotation status — only accessible by Admins.
+pub async fn list_keys(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Arc<Claims>>,
+) -> Result<impl IntoResponse, ApiError> {
+    require_admin(&claims)?;
+
+    let keys: Vec<SigningKeyInfo> = state
+        .keys
+        .store()
+        .list()
+        .await?
+        .iter()
+        .map(|record| state.keys.describe(record))
+        .collect();
+
+    Ok((StatusCode::OK, Json(keys)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/keys",
+    tag = "keys",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = AddSigningKeyRequest,
+    responses(
+        (status = 201, description = "Key added as pending", body = SigningKeyInfo),
+        (status = 400, description = "Bad request - Unsupported algorithm or invalid key"),
+        (status = 401, description = "Unauthorized - Invalid or missing token"),
+        (status = 403, description = "Forbidden - Admin access required"),
+        (status = 409, description = "Conflict - Key id already in use")
+    )
+)]
+/// POST /admin/keys
+/// Imports or generates a key. It is published and verifies tokens right away,
+/// but only signs once promoted — only accessible by Admins.
+pub async fn add_key(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Arc<Claims>>,
+    Json(payload): Json<AddSigningKeyRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    require_admin(&claims)?;
+
+    let bad_request = |e: keys::KeyError| (StatusCode::BAD_REQUEST, Json(json!({ "error": e.to_string() })));
+
+    let algorithm = match payload.algorithm.as_deref() {
+        Some(name) => keys::parse_algorithm(name).map_err(bad_request)?,
+        None => state.config.jwt_algorithm,
+    };
+    if payload.kid.as_deref().is_some_and(|kid| kid.trim().is_empty()) {
+        return Err((StatusCode::BAD_REQUEST, Json(json!({ "error": "kid must not be empty" }))));
+    }
+
+    let private_key = match (algorithm, payload.private_key_pem) {
+        (Algorithm::HS256, Some(_)) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": "HS256 keys are always generated" })),
+            ));
+        }
+        (_, Some(pem)) => pem,
+        // RSA key generation takes a while; keep it off the async workers
+        (_, None) => tokio::task::spawn_blocking(move || keys::generate_key_material(algorithm))
+            .await
+            .map_err(|e| keys::KeyError(e.to_string()))
+            .and_then(|material| material)
+            .map_err(|e| {
+                tracing::error!("Failed to generate signing key: {}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "Internal server error" })))
+            })?,
+    };
+
+    let kid = match algorithm {
+        Algorithm::HS256 => payload.kid.unwrap_or_else(|| Uuid::now_v7().to_string()),
+        _ => SigningKey::from_pem(algorithm, private_key.as_bytes(), payload.kid)
+            .map_err(bad_request)?
+            .kid,
+    };
+
+    let inserted = state
+        .keys
+        .insert(SigningKeyRecord {
+            kid: kid.clone(),
+            algorithm: format!("{:?}", algorithm),
+            private_key,
+            created_at: Utc::now(),
+            activated_at: None,
+            retired_at: None,
+            verify_until: None,
+        })
+        .await;
+
+    match inserted {
+        Ok(()) => {}
+        Err(StoreError::Conflict) => {
+            return Err((StatusCode::CONFLICT, Json(json!({ "error": "Key id already in use" }))));
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    tracing::info!("Added {:?} signing key {}", algorithm, kid);
+    Ok((StatusCode::CREATED, Json(reload_and_describe(&state, &kid).await?)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/keys/{kid}/promote",
+    tag = "keys",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        ("kid" = String, Path, description = "Id of the key that should sign new tokens")
+    ),
+    responses(
+        (status = 200, description = "Key now signs new tokens", body = SigningKeyInfo),
+        (status = 401, description = "Unauthorized - Invalid or missing token"),
+        (status = 403, description = "Forbidden - Admin access required"),
+        (status = 404, description = "Not Found - Key not found"),
+        (status = 409, description = "Conflict - Key is retired, or was added less than a minute ago")
+    )
+)]
+/// POST /admin/keys/{kid}/promote
+/// Makes the key the one that signs new tokens. The previous signing key keeps
+/// verifying until it is retired — only accessible by Admins.
+pub async fn promote_key(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Arc<Claims>>,
+    Path(kid): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    require_admin(&claims)?;
+
+    let records = state.keys.store().list().await?;
+    let record = records.iter().find(|r| r.kid == kid).ok_or_else(key_not_found)?;
+    if record.retired_at.is_some() {
+        return Err((StatusCode::CONFLICT, Json(json!({ "error": "Retired keys cannot be promoted" }))));
+    }
+    // Until every instance has reloaded, some would reject the tokens this key signs
+    let now = Utc::now();
+    let trusted_from = record.created_at + Duration::seconds(keys::RELOAD_INTERVAL_SECS as i64);
+    if record.activated_at.is_none() && now < trusted_from {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(json!({
+                "error": format!(
+                    "Other instances may not know this key yet; promote it in {} seconds",
+                    (trusted_from - now).num_seconds() + 1
+                )
+            })),
+        ));
+    }
+
+    match state.keys.store().promote(&kid, now).await {
+        Ok(()) => {}
+        Err(StoreError::NotFound) => return Err(key_not_found()),
+        Err(e) => return Err(e.into()),
+    }
+
+    tracing::info!("Promoted signing key {}", kid);
+    Ok((StatusCode::OK, Json(reload_and_describe(&state, &kid).await?)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/keys/{kid}/retire",
+    tag = "keys",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        ("kid" = String, Path, description = "Id of the key to retire")
+    ),
+    responses(
+        (status = 200, description = "Key retired; it verifies until verify_until", body = SigningKeyInfo),
+        (status = 401, description = "Unauthorized - Invalid or missing token"),
+        (status = 403, description = "Forbidden - Admin access required"),
+        (status = 404, description = "Not Found - Key not found"),
+        (status = 409, description = "Conflict - Key is the current signing key")
+    )
+)]
+/// POST /admin/keys/{kid}/retire
+/// Stops the key from signing. It still verifies tokens for one reload
+/// interval plus one access token lifetime, then is dropped from the keyset
+/// — only accessible by Admins.
+pub async fn retire_key(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Arc<Claims>>,
+    Path(kid): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    require_admin(&claims)?;
+
+    let records = state.keys.store().list().await?;
+    let record = records.iter().find(|r| r.kid == kid).ok_or_else(key_not_found)?;
+    if record.retired_at.is_some() {
+        return Ok((StatusCode::OK, Json(state.keys.describe(record))));
+    }
+    // Checked against the store rather than our cached keyset, which may lag behind another instance
+    let signer = records
+        .iter()
+        .filter(|r| r.retired_at.is_none())
+        .filter_map(|r| r.activated_at.map(|at| (at, &r.kid)))
+        .max();
+    if signer.is_some_and(|(_, signer)| *signer == kid) {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(json!({ "error": "Promote another key before retiring the current signing key" })),
+        ));
+    }
+
+    // Instances that haven't reloaded yet keep signing with this key for up to
+    // a reload interval, and their last token lives one lifetime more
+    let now = Utc::now();
+    let verify_until =
+        now + Duration::seconds((keys::RELOAD_INTERVAL_SECS + state.config.jwt_expiration_secs as u64) as i64);
+    match state.keys.store().retire(&kid, now, verify_until).await {
+        Ok(()) => {}
+        Err(StoreError::NotFound) => return Err(key_not_found()),
+        Err(e) => return Err(e.into()),
+    }
+
+    tracing::info!("Retired signing key {}, verifying until {}", kid, verify_until);
+    Ok((StatusCode::OK, Json(reload_and_describe(&state, &kid).await?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    async fn admin_claims(state: &AppState) -> Arc<Claims> {
+        let mut admin = test_support::user(state, "admin@example.com").await;
+        admin.role = Role::Admin;
+        test_support::claims(state, &admin)
+    }
+
+    async fn add(state: &AppState, claims: &Arc<Claims>) -> String {
+        let request = AddSigningKeyRequest { algorithm: Some("ES256".to_string()), kid: None, private_key_pem: None };
+        let response = add_key(State(state.clone()), Extension(claims.clone()), Json(request))
+            .await
+            .unwrap()
+            .into_response();
+        test_support::json_body(response).await["kid"].as_str().unwrap().to_string()
+    }
+
+    async fn promote(state: &AppState, claims: &Arc<Claims>, kid: &str) -> StatusCode {
+        match promote_key(State(state.clone()), Extension(claims.clone()), Path(kid.to_string())).await {
+            Ok(response) => response.into_response().status(),
+            Err((status, _)) => status,
+        }
+    }
+
+    #[tokio::test]
+    async fn new_keys_wait_for_every_instance_to_reload() {
+        let state = test_support::memory_state().await;
+        let claims = admin_claims(&state).await;
+        let kid = add(&state, &claims).await;
+
+        assert_eq!(promote(&state, &claims, &kid).await, StatusCode::CONFLICT);
+        assert_eq!(state.keys.current().signer().kid, "default");
+    }
+
+    #[tokio::test]
+    async fn keys_older_than_the_reload_interval_can_be_promoted() {
+        let state = test_support::memory_state().await;
+        let claims = admin_claims(&state).await;
+        let pem = keys::generate_key_material(Algorithm::ES256).unwrap();
+        state
+            .keys
+            .insert(SigningKeyRecord {
+                kid: "next".to_string(),
+                algorithm: "ES256".to_string(),
+                private_key: pem,
+                created_at: Utc::now() - Duration::seconds(keys::RELOAD_INTERVAL_SECS as i64 + 1),
+                activated_at: None,
+                retired_at: None,
+                verify_until: None,
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(promote(&state, &claims, "next").await, StatusCode::OK);
+        assert_eq!(state.keys.current().signer().kid, "next");
+        // The previous key is known everywhere already
+        assert_eq!(promote(&state, &claims, "default").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn retired_keys_verify_tokens_signed_before_every_instance_reloads() {
+        let state = test_support::memory_state().await;
+        let claims = admin_claims(&state).await;
+        let kid = add(&state, &claims).await;
+
+        let response = retire_key(State(state.clone()), Extension(claims), Path(kid.clone())).await.unwrap();
+        let retired = test_support::json_body(response.into_response()).await;
+        let verify_until: chrono::DateTime<Utc> = serde_json::from_value(retired["verify_until"].clone()).unwrap();
+        let lifetime = keys::RELOAD_INTERVAL_SECS as i64 + state.config.jwt_expiration_secs as i64;
+        assert!(verify_until >= Utc::now() + Duration::seconds(lifetime - 5));
+    }
+}
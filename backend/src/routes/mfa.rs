@@ -0,0 +1,551 @@
+use axum::{
+    extract::{Extension, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use utoipa::OpenApi;
+use uuid::Uuid;
+
+use crate::{
+    keys::KeyRing,
+    middleware::auth::Claims,
+    models::{
+        DisableTotpRequest, LoginResponse, MfaChallengeResponse, MfaCodeRequest, MfaLoginRequest, MfaStatus,
+        RecoveryCodesResponse, TokenPurpose, TotpCredential, TotpEnrollRequest, TotpEnrollment, User,
+    },
+    password::Verification,
+    store::{MfaStore, StoreError},
+    tokens::{issue_action_token, issue_tokens},
+    totp,
+    utils::hash_token,
+    AppState,
+};
+
+/// TOTP two-factor authentication: enrollment, one-time recovery codes and
+/// the second step of a login.
+#[derive(OpenApi)]
+#[openapi(
+    paths(mfa_status, enroll_totp, confirm_totp, disable_totp, regenerate_recovery_codes, login_mfa),
+    components(schemas(
+        TotpEnrollRequest,
+        TotpEnrollment,
+        MfaCodeRequest,
+        DisableTotpRequest,
+        RecoveryCodesResponse,
+        MfaStatus,
+        MfaChallengeResponse,
+        MfaLoginRequest
+    )),
+)]
+pub struct MfaApi;
+
+type Rejection = (StatusCode, Json<Value>);
+
+/// Answers a correct password with a challenge instead of tokens; the login
+/// completes at `/login/mfa`.
+pub async fn mfa_challenge(state: &AppState, user: &User) -> Response {
+    let ttl = state.config.mfa_challenge_ttl_secs;
+    match issue_action_token(state, user, TokenPurpose::MfaChallenge, ttl).await {
+        Ok(mfa_token) => (
+            StatusCode::OK,
+            Json(MfaChallengeResponse {
+                mfa_required: true,
+                mfa_token,
+                expires_in: ttl as u64,
+                message: "Enter the code from your authenticator app".to_string(),
+            }),
+        )
+            .into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// The credential's secret, decrypted if it is stored encrypted.
+fn open_secret(state: &AppState, credential: &TotpCredential) -> Result<String, StoreError> {
+    state
+        .keys
+        .open_secret(&totp::sealing_label(credential.user_id), &credential.secret)
+        .map_err(|e| StoreError::Backend(e.0))
+}
+
+/// Encrypts TOTP secrets that earlier versions stored in the clear.
+pub async fn seal_stored_secrets(mfa: &dyn MfaStore, keys: &KeyRing) -> Result<(), StoreError> {
+    for credential in mfa.list_totp().await? {
+        if !keys.needs_sealing(&credential.secret) {
+            continue;
+        }
+        let sealed = keys.seal_secret(&totp::sealing_label(credential.user_id), &credential.secret);
+        // A user re-enrolling meanwhile stored a sealed secret already
+        if mfa.replace_totp_secret(credential.user_id, &credential.secret, &sealed).await? {
+            tracing::info!("Encrypted the TOTP secret of user {} at rest", credential.user_id);
+        }
+    }
+    Ok(())
+}
+
+/// Which second factor a code was accepted as.
+enum SecondFactor {
+    Totp,
+    RecoveryCode,
+}
+
+/// Accepts a current authenticator code, once, or an unused recovery code.
+async fn check_second_factor(
+    state: &AppState,
+    credential: &TotpCredential,
+    code: &str,
+    now: DateTime<Utc>,
+) -> Result<Option<SecondFactor>, StoreError> {
+    if totp::is_totp_code(code) {
+        let Some(step) = totp::matching_step(&open_secret(state, credential)?, code, now) else {
+            return Ok(None);
+        };
+        let fresh = state.mfa.use_totp_step(credential.user_id, step).await?;
+        return Ok(fresh.then_some(SecondFactor::Totp));
+    }
+
+    let code_hash = hash_token(&totp::normalize_recovery_code(code));
+    let unused = state.mfa.use_recovery_code(credential.user_id, &code_hash, now).await?;
+    Ok(unused.then_some(SecondFactor::RecoveryCode))
+}
+
+async fn current_user(state: &AppState, claims: &Claims) -> Result<User, Rejection> {
+    let Ok(user_id) = Uuid::parse_str(&claims.sub) else {
+        return Err((StatusCode::BAD_REQUEST, Json(json!({ "error": "Invalid user ID" }))));
+    };
+    state
+        .users
+        .find_by_id(user_id)
+        .await?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, Json(json!({ "error": "User not found" }))))
+}
+
+async fn check_password(state: &AppState, user: &User, password: &str) -> Result<(), Rejection> {
+    let verification = state.passwords.verify(password, &user.password).await.map_err(|e| {
+        tracing::error!("{}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "Hash failure" })))
+    })?;
+    if verification == Verification::Invalid {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "Password is incorrect", "field": "password" })),
+        ));
+    }
+    Ok(())
+}
+
+async fn enabled_totp(state: &AppState, user_id: Uuid) -> Result<TotpCredential, Rejection> {
+    state
+        .mfa
+        .find_totp(user_id)
+        .await?
+        .filter(|t| t.enabled_at.is_some())
+        .ok_or_else(|| {
+            (
+                StatusCode::CONFLICT,
+                Json(json!({ "error": "Two-factor authentication is not enabled" })),
+            )
+        })
+}
+
+/// Stores a fresh set of recovery codes, replacing the old ones, and returns them in plain text.
+async fn issue_recovery_codes(state: &AppState, user_id: Uuid) -> Result<Vec<String>, StoreError> {
+    let codes = totp::generate_recovery_codes();
+    let hashes: Vec<String> = codes.iter().map(|c| hash_token(&totp::normalize_recovery_code(c))).collect();
+    state.mfa.replace_recovery_codes(user_id, &hashes, Utc::now()).await?;
+    Ok(codes)
+}
+
+fn invalid_code() -> Rejection {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(json!({ "error": "Invalid or already used code", "field": "code" })),
+    )
+}
+
+#[utoipa::path(
+    get,
+    path = "/user/mfa",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Second factors of the account", body = MfaStatus),
+        (status = 401, description = "Unauthorized - Invalid or missing token")
+    )
+)]
+/// GET /user/mfa
+/// Tells whether TOTP is enabled and how many recovery codes are left.
+pub async fn mfa_status(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Arc<Claims>>,
+) -> Result<impl IntoResponse, Rejection> {
+    let user = current_user(&state, &claims).await?;
+    let totp_enabled = state.mfa.find_totp(user.id).await?.is_some_and(|t| t.enabled_at.is_some());
+    let recovery_codes_remaining = state.mfa.remaining_recovery_codes(user.id).await?;
+
+    Ok((StatusCode::OK, Json(MfaStatus { totp_enabled, recovery_codes_remaining })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/user/mfa/totp",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = TotpEnrollRequest,
+    responses(
+        (status = 200, description = "New secret to add to an authenticator app, pending confirmation", body = TotpEnrollment),
+        (status = 400, description = "Bad request - Wrong password"),
+        (status = 401, description = "Unauthorized - Invalid or missing token"),
+        (status = 409, description = "Conflict - TOTP is already enabled")
+    )
+)]
+/// POST /user/mfa/totp
+/// Starts TOTP enrollment with a new secret. Logins don't ask for codes until
+/// the secret is confirmed at `/user/mfa/totp/confirm`.
+pub async fn enroll_totp(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Arc<Claims>>,
+    Json(payload): Json<TotpEnrollRequest>,
+) -> Result<impl IntoResponse, Rejection> {
+    let user = current_user(&state, &claims).await?;
+    check_password(&state, &user, &payload.password).await?;
+
+    let secret = totp::generate_secret();
+    let credential = TotpCredential {
+        user_id: user.id,
+        secret: state.keys.seal_secret(&totp::sealing_label(user.id), &secret),
+        created_at: Utc::now(),
+        enabled_at: None,
+        last_used_step: None,
+    };
+    match state.mfa.begin_totp(credential).await {
+        Ok(()) => {}
+        Err(StoreError::Conflict) => {
+            return Err((
+                StatusCode::CONFLICT,
+                Json(json!({ "error": "Two-factor authentication is already enabled" })),
+            ));
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(TotpEnrollment {
+            otpauth_uri: totp::provisioning_uri(&state.config.mfa_issuer, &user.email, &secret),
+            secret,
+            message: "Add the secret to your authenticator app, then confirm it with a code".to_string(),
+        }),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/user/mfa/totp/confirm",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = MfaCodeRequest,
+    responses(
+        (status = 200, description = "TOTP enabled; the recovery codes are shown only this once", body = RecoveryCodesResponse),
+        (status = 400, description = "Bad request - Wrong code or no enrollment in progress"),
+        (status = 401, description = "Unauthorized - Invalid or missing token")
+    )
+)]
+/// POST /user/mfa/totp/confirm
+/// Enables TOTP once the authenticator produces a valid code, and issues the
+/// account's recovery codes.
+pub async fn confirm_totp(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Arc<Claims>>,
+    Json(payload): Json<MfaCodeRequest>,
+) -> Result<impl IntoResponse, Rejection> {
+    let user = current_user(&state, &claims).await?;
+    let Some(credential) = state.mfa.find_totp(user.id).await?.filter(|t| t.enabled_at.is_none()) else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "No two-factor enrollment in progress" })),
+        ));
+    };
+
+    let now = Utc::now();
+    let secret = open_secret(&state, &credential)?;
+    let step = totp::matching_step(&secret, &payload.code, now).ok_or_else(invalid_code)?;
+    if !state.mfa.use_totp_step(user.id, step).await? || !state.mfa.enable_totp(user.id, now).await? {
+        return Err(invalid_code());
+    }
+    let recovery_codes = issue_recovery_codes(&state, user.id).await?;
+    tracing::info!("User {} enabled two-factor authentication", user.id);
+
+    Ok((
+        StatusCode::OK,
+        Json(RecoveryCodesResponse {
+            recovery_codes,
+            message: "Two-factor authentication enabled. Store these recovery codes somewhere safe.".to_string(),
+        }),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/user/mfa/totp/disable",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = DisableTotpRequest,
+    responses(
+        (status = 200, description = "TOTP disabled and recovery codes deleted"),
+        (status = 400, description = "Bad request - Wrong password or code"),
+        (status = 401, description = "Unauthorized - Invalid or missing token"),
+        (status = 409, description = "Conflict - TOTP is not enabled")
+    )
+)]
+/// POST /user/mfa/totp/disable
+/// Turns two-factor authentication off, given the password and a current
+/// code or recovery code.
+pub async fn disable_totp(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Arc<Claims>>,
+    Json(payload): Json<DisableTotpRequest>,
+) -> Result<impl IntoResponse, Rejection> {
+    let user = current_user(&state, &claims).await?;
+    let credential = enabled_totp(&state, user.id).await?;
+    check_password(&state, &user, &payload.password).await?;
+    if check_second_factor(&state, &credential, &payload.code, Utc::now()).await?.is_none() {
+        return Err(invalid_code());
+    }
+
+    state.mfa.disable_totp(user.id).await?;
+    tracing::info!("User {} disabled two-factor authentication", user.id);
+
+    Ok((StatusCode::OK, Json(json!({ "message": "Two-factor authentication disabled" }))))
+}
+
+#[utoipa::path(
+    post,
+    path = "/user/mfa/recovery-codes",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = MfaCodeRequest,
+    responses(
+        (status = 200, description = "New recovery codes; the previous ones stop working", body = RecoveryCodesResponse),
+        (status = 400, description = "Bad request - Wrong code"),
+        (status = 401, description = "Unauthorized - Invalid or missing token"),
+        (status = 409, description = "Conflict - TOTP is not enabled")
+    )
+)]
+/// POST /user/mfa/recovery-codes
+/// Replaces every recovery code with a new set, given a current code or recovery code.
+pub async fn regenerate_recovery_codes(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Arc<Claims>>,
+    Json(payload): Json<MfaCodeRequest>,
+) -> Result<impl IntoResponse, Rejection> {
+    let user = current_user(&state, &claims).await?;
+    let credential = enabled_totp(&state, user.id).await?;
+    if check_second_factor(&state, &credential, &payload.code, Utc::now()).await?.is_none() {
+        return Err(invalid_code());
+    }
+
+    let recovery_codes = issue_recovery_codes(&state, user.id).await?;
+    tracing::info!("User {} regenerated their recovery codes", user.id);
+
+    Ok((
+        StatusCode::OK,
+        Json(RecoveryCodesResponse {
+            recovery_codes,
+            message: "New recovery codes issued; the previous ones no longer work.".to_string(),
+        }),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/login/mfa",
+    tag = "auth",
+    request_body = MfaLoginRequest,
+    responses(
+        (status = 200, description = "Login successful", body = LoginResponse),
+        (status = 401, description = "Invalid, used or expired challenge, or wrong code; log in again")
+    )
+)]
+/// POST /login/mfa
+/// Completes a login that answered with `mfa_required`. Each challenge allows
+/// one attempt, so guessing codes costs a password verification every time.
+pub async fn login_mfa(
+    State(state): State<AppState>,
+    Json(payload): Json<MfaLoginRequest>,
+) -> Result<impl IntoResponse, Rejection> {
+    let rejected = || {
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(json!({ "error": "Invalid code or expired login; log in again" })),
+        )
+    };
+
+    let token_hash = hash_token(&payload.mfa_token);
+    let now = Utc::now();
+    let challenge = state
+        .action_tokens
+        .find_by_hash(&token_hash)
+        .await?
+        .filter(|t| t.purpose == TokenPurpose::MfaChallenge && t.used_at.is_none() && t.expires_at > now)
+        .ok_or_else(rejected)?;
+    if !state.action_tokens.mark_used(&token_hash, now).await? {
+        return Err(rejected());
+    }
+
+    let Some(user) = state.users.find_by_id(challenge.user_id).await?.filter(|u| !u.disabled) else {
+        return Err(rejected());
+    };
+    let Some(credential) = state.mfa.find_totp(user.id).await?.filter(|t| t.enabled_at.is_some()) else {
+        return Err(rejected());
+    };
+
+    let message = match check_second_factor(&state, &credential, &payload.code, now).await? {
+        Some(SecondFactor::Totp) => "Login successful".to_string(),
+        Some(SecondFactor::RecoveryCode) => {
+            let remaining = state.mfa.remaining_recovery_codes(user.id).await?;
+            tracing::info!("User {} logged in with a recovery code, {} left", user.id, remaining);
+            format!("Login successful with a recovery code; {} left", remaining)
+        }
+        None => {
+            tracing::warn!("Failed second factor for user {}", user.id);
+            return Err(rejected());
+        }
+    };
+
+    let response: LoginResponse = issue_tokens(&state, &user, None, &message).await?;
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data_encoding::BASE32_NOPAD;
+
+    use crate::{models::LoginRequest, routes::auth::login, test_support};
+
+    /// Enrolls and confirms TOTP for `user`; returns the raw key and the recovery codes.
+    async fn enable(state: &AppState, user: &User) -> (Vec<u8>, Vec<String>) {
+        let claims = test_support::claims(state, user);
+        let request = TotpEnrollRequest { password: test_support::PASSWORD.to_string() };
+        let response = enroll_totp(State(state.clone()), Extension(claims.clone()), Json(request))
+            .await
+            .unwrap()
+            .into_response();
+        let secret = test_support::json_body(response).await["secret"].as_str().unwrap().to_string();
+        let key = BASE32_NOPAD.decode(secret.as_bytes()).unwrap();
+
+        let code = totp::code_at(&key, totp::step_at(Utc::now()));
+        let response = confirm_totp(State(state.clone()), Extension(claims), Json(MfaCodeRequest { code }))
+            .await
+            .unwrap()
+            .into_response();
+        let body = test_support::json_body(response).await;
+        let codes = body["recovery_codes"].as_array().unwrap().iter().map(|c| c.as_str().unwrap().to_string());
+        (key, codes.collect())
+    }
+
+    async fn check(state: &AppState, user: &User, code: &str) -> Option<SecondFactor> {
+        let credential = state.mfa.find_totp(user.id).await.unwrap().unwrap();
+        check_second_factor(state, &credential, code, Utc::now()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn codes_are_accepted_once_per_step() {
+        let state = test_support::memory_state().await;
+        let user = test_support::user(&state, "ada@example.com").await;
+        let (key, _) = enable(&state, &user).await;
+
+        // Confirming used up the current step
+        let current = totp::step_at(Utc::now());
+        assert!(check(&state, &user, &totp::code_at(&key, current)).await.is_none());
+
+        let next = totp::code_at(&key, current + 1);
+        assert!(matches!(check(&state, &user, &next).await, Some(SecondFactor::Totp)));
+        assert!(check(&state, &user, &next).await.is_none());
+        // Nor does an earlier step come back into play
+        assert!(check(&state, &user, &totp::code_at(&key, current - 1)).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn recovery_codes_work_once() {
+        let state = test_support::memory_state().await;
+        let user = test_support::user(&state, "ada@example.com").await;
+        let (_, codes) = enable(&state, &user).await;
+        assert_eq!(codes.len(), totp::RECOVERY_CODE_COUNT);
+
+        let code = codes[0].to_uppercase();
+        assert!(matches!(check(&state, &user, &code).await, Some(SecondFactor::RecoveryCode)));
+        assert!(check(&state, &user, &code).await.is_none());
+        assert!(check(&state, &user, "aaaaa-aaaaa").await.is_none());
+        assert_eq!(
+            state.mfa.remaining_recovery_codes(user.id).await.unwrap(),
+            totp::RECOVERY_CODE_COUNT as u64 - 1
+        );
+    }
+
+    #[tokio::test]
+    async fn login_completes_with_a_second_factor() {
+        let state = test_support::memory_state().await;
+        let user = test_support::user(&state, "ada@example.com").await;
+        let (key, _) = enable(&state, &user).await;
+
+        let credentials =
+            LoginRequest { email: "ada@example.com".to_string(), password: test_support::PASSWORD.to_string() };
+        let response = login(State(state.clone()), Json(credentials)).await.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = test_support::json_body(response).await;
+        assert_eq!(body["mfa_required"], true);
+        assert!(body["access_token"].is_null());
+        let mfa_token = body["mfa_token"].as_str().unwrap().to_string();
+
+        let code = totp::code_at(&key, totp::step_at(Utc::now()) + 1);
+        let request = MfaLoginRequest { mfa_token: mfa_token.clone(), code: code.clone() };
+        let response = login_mfa(State(state.clone()), Json(request)).await.unwrap().into_response();
+        assert!(test_support::json_body(response).await["access_token"].is_string());
+
+        // The challenge was spent
+        let request = MfaLoginRequest { mfa_token, code };
+        let (status, _) = login_mfa(State(state.clone()), Json(request)).await.err().unwrap();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn secrets_are_encrypted_at_rest() {
+        let state = test_support::sqlite_state().await;
+        let user = test_support::user(&state, "ada@example.com").await;
+        let (key, _) = enable(&state, &user).await;
+        let stored = state.mfa.find_totp(user.id).await.unwrap().unwrap();
+        assert!(!state.keys.needs_sealing(&stored.secret));
+        assert_ne!(stored.secret, BASE32_NOPAD.encode(&key));
+
+        // As stored by a version that kept secrets in the clear
+        let other = test_support::user(&state, "grace@example.com").await;
+        let secret = totp::generate_secret();
+        let credential = TotpCredential {
+            user_id: other.id,
+            secret: secret.clone(),
+            created_at: Utc::now(),
+            enabled_at: None,
+            last_used_step: None,
+        };
+        state.mfa.begin_totp(credential).await.unwrap();
+
+        seal_stored_secrets(state.mfa.as_ref(), &state.keys).await.unwrap();
+        let sealed = state.mfa.find_totp(other.id).await.unwrap().unwrap();
+        assert!(!state.keys.needs_sealing(&sealed.secret));
+        assert_eq!(open_secret(&state, &sealed).unwrap(), secret);
+        // Sealed under another user's label, a secret doesn't open
+        let moved = TotpCredential { user_id: user.id, ..sealed };
+        assert!(open_secret(&state, &moved).is_err());
+    }
+}
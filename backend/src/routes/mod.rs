@@ -1,4 +1,11 @@
 pub mod auth;
+pub mod keys;
+pub mod mfa;
+pub mod oauth;
+pub mod passkeys;
+pub mod password;
 pub mod protected;
+pub mod verification;
+pub mod well_known;
 // pub mod register;
 // pub mod user_route;
\ No newline at end of file
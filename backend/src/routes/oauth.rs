@@ -0,0 +1,1311 @@
+use axum::{
+    extract::{rejection::FormRejection, Extension, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    Form, Json,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Duration, Utc};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing::{info, warn};
+use utoipa::OpenApi;
+use uuid::Uuid;
+
+use crate::{
+    middleware::auth::{Claims, ClientClaims},
+    models::{
+        AuthorizationCode, AuthorizeDecision, AuthorizeParams, AuthorizeRedirect, DeviceAuthorization,
+        DeviceAuthorizationRequest, DeviceAuthorizationResponse, DeviceDecision, DeviceRequestInfo, GrantType,
+        IntrospectionRequest, IntrospectionResponse, OAuthClient, OAuthErrorResponse, RevocationRequest, TokenRequest,
+        TokenResponse, User, UserCodeQuery, UserInfo,
+    },
+    oauth::{
+        client_subject, generate_user_code, has_scope, is_valid_challenge, is_valid_nonce, normalize_scope, normalize_user_code,
+        user_info, verify_client_secret, verify_pkce, with_query, DEFAULT_SCOPE, OPENID_SCOPE, PKCE_METHOD,
+        SUPPORTED_SCOPES,
+    },
+    store::StoreError,
+    tokens::{access_claims, client_claims, decode_access_token, id_token_claims, sign_id_token, sign_token},
+    utils::{generate_opaque_token, hash_token},
+    AppState,
+};
+
+/// OAuth 2.0 authorization server (RFC 6749) for the registered clients: the
+/// authorization code grant with PKCE (RFC 7636) and the client credentials
+/// grant, the device authorization grant (RFC 8628), plus the OpenID Connect
+/// ID token and UserInfo endpoint on top, and token introspection (RFC 7662)
+/// and revocation (RFC 7009).
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        authorize, decide_authorization, device_authorization, device_request, decide_device, token, userinfo,
+        introspect, revoke
+    ),
+    components(schemas(
+        AuthorizeParams, AuthorizeDecision, AuthorizeRedirect, DeviceAuthorizationRequest, DeviceAuthorizationResponse,
+        DeviceRequestInfo, DeviceDecision, TokenRequest, TokenResponse, OAuthErrorResponse, UserInfo,
+        IntrospectionRequest, IntrospectionResponse, RevocationRequest
+    )),
+)]
+pub struct OAuthApi;
+
+type Rejection = (StatusCode, Json<Value>);
+
+/// Token responses carry credentials and must not be cached (RFC 6749 section 5.1).
+const NO_STORE: [(header::HeaderName, &str); 2] = [(header::CACHE_CONTROL, "no-store"), (header::PRAGMA, "no-cache")];
+
+/// Added to a device's polling interval each time it polls too fast (RFC 8628 section 3.5).
+const SLOW_DOWN_SECS: u32 = 5;
+
+/// Attempts at drawing a user code that no pending request holds.
+const USER_CODE_ATTEMPTS: usize = 3;
+
+/// An authorization request that passed every check.
+struct ValidRequest {
+    client: OAuthClient,
+    scope: String,
+    code_challenge: String,
+}
+
+/// Why an authorization request was refused.
+enum AuthorizeError {
+    /// The client or redirect URI can't be trusted, so the user is told
+    /// instead of being sent anywhere (RFC 6749 section 4.1.2.1).
+    Untrusted(Rejection),
+    /// Reported back to the client through its redirect URI.
+    Redirect { error: &'static str, description: String },
+}
+
+impl From<StoreError> for AuthorizeError {
+    fn from(err: StoreError) -> Self {
+        AuthorizeError::Untrusted(err.into())
+    }
+}
+
+async fn check_request(state: &AppState, params: &AuthorizeParams) -> Result<ValidRequest, AuthorizeError> {
+    let untrusted = |reason: &str| AuthorizeError::Untrusted((StatusCode::BAD_REQUEST, Json(json!({ "error": reason }))));
+    let Some(client) = state.oauth.find_client(&params.client_id).await? else {
+        return Err(untrusted("Unknown client_id"));
+    };
+    // Exact match only: prefix or pattern matching has let codes leak to attacker-controlled paths
+    if !client.redirect_uris.contains(&params.redirect_uri) {
+        return Err(untrusted("redirect_uri is not registered for this client"));
+    }
+
+    let redirect = |error, description: &str| AuthorizeError::Redirect { error, description: description.to_string() };
+    if params.response_type != "code" {
+        return Err(redirect("unsupported_response_type", "Only response_type=code is supported"));
+    }
+    if !client.allows(GrantType::AuthorizationCode) {
+        return Err(redirect("unauthorized_client", "The client may not use the authorization code grant"));
+    }
+    let Some(code_challenge) = params.code_challenge.as_deref() else {
+        return Err(redirect("invalid_request", "code_challenge is required"));
+    };
+    if params.code_challenge_method.as_deref() != Some(PKCE_METHOD) {
+        return Err(redirect("invalid_request", "code_challenge_method must be S256"));
+    }
+    if !is_valid_challenge(code_challenge) {
+        return Err(redirect("invalid_request", "code_challenge must be a BASE64URL-encoded SHA-256 digest"));
+    }
+    if params.nonce.as_deref().is_some_and(|nonce| !is_valid_nonce(nonce)) {
+        return Err(redirect("invalid_request", "nonce must be at most 256 printable ASCII characters"));
+    }
+    let scope = delegated_scope(state, &client, params.scope.as_deref()).map_err(|e| redirect("invalid_scope", &e))?;
+
+    Ok(ValidRequest { client, scope, code_challenge: code_challenge.to_string() })
+}
+
+/// The scopes a user can grant `client`: those it is registered for that we support.
+fn delegable_scopes(client: &OAuthClient) -> Vec<&str> {
+    client
+        .scopes
+        .iter()
+        .map(String::as_str)
+        .filter(|s| SUPPORTED_SCOPES.contains(s))
+        .collect()
+}
+
+/// Checks the scopes requested for `client` on a user's behalf. `openid`
+/// also needs a signing key that clients can verify ID tokens with.
+fn delegated_scope(state: &AppState, client: &OAuthClient, requested: Option<&str>) -> Result<String, String> {
+    let scope = normalize_scope(requested, DEFAULT_SCOPE, &delegable_scopes(client))?;
+    if has_scope(&scope, OPENID_SCOPE) && state.keys.current().id_token_signer().is_none() {
+        return Err("The openid scope needs an RS256, ES256 or EdDSA signing key".to_string());
+    }
+    Ok(scope)
+}
+
+/// The client's redirect URI carrying `error`, plus `state` when it sent one.
+fn error_redirect(params: &AuthorizeParams, error: &str, description: &str) -> String {
+    let mut query = vec![("error", error), ("error_description", description)];
+    if let Some(state) = params.state.as_deref() {
+        query.push(("state", state));
+    }
+    with_query(&params.redirect_uri, &query)
+}
+
+fn token_error(status: StatusCode, error: &str, description: &str) -> Response {
+    let body = OAuthErrorResponse {
+        error: error.to_string(),
+        error_description: description.to_string(),
+    };
+    (status, NO_STORE, Json(body)).into_response()
+}
+
+fn invalid_client(used_basic: bool, description: &str) -> Response {
+    let mut response = token_error(StatusCode::UNAUTHORIZED, "invalid_client", description);
+    // Required when the client tried HTTP authentication (RFC 6749 section 5.2)
+    if used_basic {
+        response
+            .headers_mut()
+            .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Basic realm=\"oauth\""));
+    }
+    response
+}
+
+/// Identifies the client behind a request to the token endpoint, from HTTP
+/// Basic credentials (`client_secret_basic`) or form fields
+/// (`client_secret_post`). Public clients only send their `client_id`.
+pub(crate) async fn authenticate_client(
+    state: &AppState,
+    headers: &HeaderMap,
+    client_id: Option<&str>,
+    client_secret: Option<&str>,
+) -> Result<OAuthClient, Response> {
+    let basic = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Basic "));
+
+    let (id, secret) = match basic {
+        Some(encoded) => {
+            let credentials = STANDARD
+                .decode(encoded.trim())
+                .ok()
+                .and_then(|bytes| String::from_utf8(bytes).ok())
+                .and_then(|text| text.split_once(':').map(|(id, secret)| (id.to_string(), secret.to_string())));
+            let Some((id, secret)) = credentials else {
+                return Err(invalid_client(true, "Malformed Basic credentials"));
+            };
+            if client_id.is_some_and(|form_id| form_id != id) {
+                return Err(invalid_client(true, "client_id does not match the Basic credentials"));
+            }
+            (id, Some(secret))
+        }
+        None => {
+            let Some(id) = client_id else {
+                return Err(invalid_client(false, "Client authentication is required"));
+            };
+            (id.to_string(), client_secret.map(str::to_string))
+        }
+    };
+
+    let client = state.oauth.find_client(&id).await.map_err(IntoResponse::into_response)?;
+    let authenticated = match (&client, secret) {
+        // Public clients prove themselves with PKCE instead
+        (Some(client), None) => client.secret_hash.is_none(),
+        (Some(client), Some(secret)) => verify_client_secret(client, &secret),
+        (None, _) => false,
+    };
+    match client {
+        Some(client) if authenticated => Ok(client),
+        _ => Err(invalid_client(basic.is_some(), "Unknown client or invalid client credentials")),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/oauth/authorize",
+    tag = "oauth",
+    params(AuthorizeParams),
+    responses(
+        (status = 303, description = "To the frontend's consent page, or back to the client with an error"),
+        (status = 400, description = "Bad request - Unknown client or unregistered redirect_uri")
+    )
+)]
+/// GET /oauth/authorize
+/// Where clients send the browser to ask for an authorization code. Valid
+/// requests continue on the frontend, which logs the user in, asks for
+/// consent and answers with POST /oauth/authorize.
+pub async fn authorize(
+    State(state): State<AppState>,
+    Query(params): Query<AuthorizeParams>,
+) -> Result<Redirect, Rejection> {
+    match check_request(&state, &params).await {
+        Ok(_) => {
+            let query = serde_urlencoded::to_string(&params).expect("authorization requests always encode");
+            Ok(Redirect::to(&format!("{}/oauth/authorize?{}", state.config.app_url, query)))
+        }
+        Err(AuthorizeError::Untrusted(rejection)) => Err(rejection),
+        Err(AuthorizeError::Redirect { error, description }) => {
+            Ok(Redirect::to(&error_redirect(&params, error, &description)))
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/oauth/authorize",
+    tag = "oauth",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = AuthorizeDecision,
+    responses(
+        (status = 200, description = "Where to send the browser, with a code or an error for the client", body = AuthorizeRedirect),
+        (status = 400, description = "Bad request - Unknown client or unregistered redirect_uri"),
+        (status = 401, description = "Unauthorized - Invalid or missing token")
+    )
+)]
+/// POST /oauth/authorize
+/// Records the authenticated user's decision on an authorization request.
+/// Approving issues a single-use code bound to the client, redirect URI and
+/// PKCE challenge.
+pub async fn decide_authorization(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Arc<Claims>>,
+    Json(payload): Json<AuthorizeDecision>,
+) -> Result<impl IntoResponse, Rejection> {
+    let params = &payload.request;
+    let redirect_to = match check_request(&state, params).await {
+        Err(AuthorizeError::Untrusted(rejection)) => return Err(rejection),
+        Err(AuthorizeError::Redirect { error, description }) => error_redirect(params, error, &description),
+        Ok(_) if !payload.approve => error_redirect(params, "access_denied", "The user denied the request"),
+        Ok(request) => {
+            let Ok(user_id) = Uuid::parse_str(&claims.sub) else {
+                return Err((StatusCode::BAD_REQUEST, Json(json!({ "error": "Invalid user ID" }))));
+            };
+            let code = generate_opaque_token();
+            let now = Utc::now();
+            state
+                .oauth
+                .insert_code(AuthorizationCode {
+                    code_hash: hash_token(&code),
+                    client_id: request.client.client_id,
+                    user_id,
+                    redirect_uri: params.redirect_uri.clone(),
+                    scope: request.scope,
+                    code_challenge: request.code_challenge,
+                    nonce: params.nonce.clone(),
+                    token_id: Uuid::new_v4().to_string(),
+                    created_at: now,
+                    expires_at: now + Duration::seconds(state.config.oauth_code_ttl_secs as i64),
+                    used_at: None,
+                })
+                .await?;
+
+            let mut query = vec![("code", code.as_str())];
+            if let Some(state) = params.state.as_deref() {
+                query.push(("state", state));
+            }
+            with_query(&params.redirect_uri, &query)
+        }
+    };
+
+    Ok((StatusCode::OK, Json(AuthorizeRedirect { redirect_to })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/oauth/device_authorization",
+    tag = "oauth",
+    request_body(content = DeviceAuthorizationRequest, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Codes for the device to show and poll with", body = DeviceAuthorizationResponse),
+        (status = 400, description = "invalid_request, invalid_scope or unauthorized_client", body = OAuthErrorResponse),
+        (status = 401, description = "invalid_client - Unknown client or wrong secret", body = OAuthErrorResponse)
+    )
+)]
+/// POST /oauth/device_authorization
+/// Starts the device authorization grant for a client without a browser,
+/// such as a CLI (RFC 8628). The device shows the user code and polls
+/// POST /oauth/token while the user approves it at `APP_URL/device`.
+pub async fn device_authorization(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    payload: Result<Form<DeviceAuthorizationRequest>, FormRejection>,
+) -> Result<impl IntoResponse, Response> {
+    let Ok(Form(payload)) = payload else {
+        return Err(token_error(StatusCode::BAD_REQUEST, "invalid_request", "Expected a form-encoded request"));
+    };
+    let client =
+        authenticate_client(&state, &headers, payload.client_id.as_deref(), payload.client_secret.as_deref()).await?;
+    if !client.allows(GrantType::DeviceCode) {
+        return Err(token_error(
+            StatusCode::BAD_REQUEST,
+            "unauthorized_client",
+            "The client may not use the device authorization grant",
+        ));
+    }
+    let scope = delegated_scope(&state, &client, payload.scope.as_deref())
+        .map_err(|e| token_error(StatusCode::BAD_REQUEST, "invalid_scope", &e))?;
+
+    let config = &state.config;
+    let device_code = generate_opaque_token();
+    let now = Utc::now();
+    let mut attempts = 0;
+    let user_code = loop {
+        let user_code = generate_user_code();
+        let inserted = state
+            .oauth
+            .insert_device_authorization(DeviceAuthorization {
+                device_code_hash: hash_token(&device_code),
+                user_code_hash: hash_token(&normalize_user_code(&user_code).expect("generated codes are valid")),
+                client_id: client.client_id.clone(),
+                scope: scope.clone(),
+                user_id: None,
+                approved: None,
+                interval_secs: config.oauth_device_poll_interval_secs,
+                last_polled_at: None,
+                created_at: now,
+                expires_at: now + Duration::seconds(config.oauth_device_code_ttl_secs as i64),
+                used_at: None,
+            })
+            .await;
+        attempts += 1;
+        match inserted {
+            Ok(()) => break user_code,
+            Err(StoreError::Conflict) if attempts < USER_CODE_ATTEMPTS => continue,
+            Err(e) => return Err(e.into_response()),
+        }
+    };
+
+    let verification_uri = format!("{}/device", config.app_url);
+    Ok((
+        StatusCode::OK,
+        NO_STORE,
+        Json(DeviceAuthorizationResponse {
+            verification_uri_complete: with_query(&verification_uri, &[("user_code", &user_code)]),
+            verification_uri,
+            device_code,
+            user_code,
+            expires_in: config.oauth_device_code_ttl_secs as u64,
+            interval: config.oauth_device_poll_interval_secs as u64,
+        }),
+    ))
+}
+
+fn invalid_user_code() -> Rejection {
+    (StatusCode::BAD_REQUEST, Json(json!({ "error": "Invalid or expired user code", "field": "user_code" })))
+}
+
+/// The pending, unexpired request behind a user code the user typed in.
+async fn pending_device_request(state: &AppState, user_code: &str) -> Result<(DeviceAuthorization, String), Rejection> {
+    let Some(user_code_hash) = normalize_user_code(user_code).map(|code| hash_token(&code)) else {
+        return Err(invalid_user_code());
+    };
+    match state.oauth.find_device_authorization_by_user_code(&user_code_hash).await? {
+        Some(request) if request.approved.is_none() && request.expires_at > Utc::now() => Ok((request, user_code_hash)),
+        _ => Err(invalid_user_code()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/oauth/device",
+    tag = "oauth",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(UserCodeQuery),
+    responses(
+        (status = 200, description = "The client and scopes the device asks for", body = DeviceRequestInfo),
+        (status = 400, description = "Bad request - Unknown, answered or expired user code"),
+        (status = 401, description = "Unauthorized - Invalid or missing token")
+    )
+)]
+/// GET /oauth/device
+/// Looks up a pending device authorization by its user code, so the
+/// frontend can show the user what they are about to approve.
+pub async fn device_request(
+    State(state): State<AppState>,
+    Query(query): Query<UserCodeQuery>,
+) -> Result<impl IntoResponse, Rejection> {
+    let (request, _) = pending_device_request(&state, &query.user_code).await?;
+    // The client may have been deleted since the device asked
+    let Some(client) = state.oauth.find_client(&request.client_id).await? else {
+        return Err(invalid_user_code());
+    };
+
+    Ok((
+        StatusCode::OK,
+        Json(DeviceRequestInfo {
+            client_id: client.client_id,
+            client_name: client.name,
+            scope: request.scope,
+            expires_at: request.expires_at,
+        }),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/oauth/device",
+    tag = "oauth",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = DeviceDecision,
+    responses(
+        (status = 200, description = "Answer recorded; the device gets its token or access_denied at its next poll"),
+        (status = 400, description = "Bad request - Unknown, answered or expired user code"),
+        (status = 401, description = "Unauthorized - Invalid or missing token")
+    )
+)]
+/// POST /oauth/device
+/// Records the authenticated user's answer to the device authorization
+/// request behind a user code. Each request can be answered once.
+pub async fn decide_device(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Arc<Claims>>,
+    Json(payload): Json<DeviceDecision>,
+) -> Result<impl IntoResponse, Rejection> {
+    let Ok(user_id) = Uuid::parse_str(&claims.sub) else {
+        return Err((StatusCode::BAD_REQUEST, Json(json!({ "error": "Invalid user ID" }))));
+    };
+    let (request, user_code_hash) = pending_device_request(&state, &payload.user_code).await?;
+
+    // Another tab may have answered it in the meantime
+    if !state
+        .oauth
+        .decide_device_authorization(&user_code_hash, user_id, payload.approve, Utc::now())
+        .await?
+    {
+        return Err(invalid_user_code());
+    }
+
+    let message = if payload.approve {
+        info!("User {} approved a device for client {}", user_id, request.client_id);
+        "Device approved"
+    } else {
+        "Device denied"
+    };
+    Ok((StatusCode::OK, Json(json!({ "message": message }))))
+}
+
+#[utoipa::path(
+    post,
+    path = "/oauth/token",
+    tag = "oauth",
+    request_body(content = TokenRequest, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Access token, and ID token for OpenID Connect requests", body = TokenResponse),
+        (status = 400, description = "invalid_request, invalid_grant, invalid_scope, unauthorized_client or unsupported_grant_type", body = OAuthErrorResponse),
+        (status = 401, description = "invalid_client - Unknown client or wrong secret", body = OAuthErrorResponse)
+    )
+)]
+/// POST /oauth/token
+/// Issues access tokens: for the user who approved an authorization code
+/// (with its PKCE verifier), or for a confidential client itself with the
+/// client credentials grant. Codes granted the `openid` scope also come with
+/// an ID token.
+pub async fn token(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    payload: Result<Form<TokenRequest>, FormRejection>,
+) -> Result<impl IntoResponse, Response> {
+    let Ok(Form(payload)) = payload else {
+        return Err(token_error(StatusCode::BAD_REQUEST, "invalid_request", "Expected a form-encoded token request"));
+    };
+    let client =
+        authenticate_client(&state, &headers, payload.client_id.as_deref(), payload.client_secret.as_deref()).await?;
+
+    let Some(grant) = GrantType::parse(&payload.grant_type) else {
+        return Err(token_error(
+            StatusCode::BAD_REQUEST,
+            "unsupported_grant_type",
+            "Supported grants are authorization_code, client_credentials and urn:ietf:params:oauth:grant-type:device_code",
+        ));
+    };
+    if !client.allows(grant) {
+        return Err(token_error(
+            StatusCode::BAD_REQUEST,
+            "unauthorized_client",
+            &format!("The client may not use the {} grant", grant.as_str()),
+        ));
+    }
+
+    let (access_token, scope, id_token) = match grant {
+        GrantType::AuthorizationCode => redeem_code(&state, &client, &payload).await?,
+        GrantType::ClientCredentials => {
+            let allowed: Vec<&str> = client.scopes.iter().map(String::as_str).collect();
+            let scope = normalize_scope(payload.scope.as_deref(), &allowed.join(" "), &allowed)
+                .map_err(|e| token_error(StatusCode::BAD_REQUEST, "invalid_scope", &e))?;
+            let claims = client_claims(&state.config, &client, &scope, Utc::now());
+            (sign_token(&state, &claims), scope, None)
+        }
+        GrantType::DeviceCode => redeem_device_code(&state, &client, &payload).await?,
+    };
+
+    Ok((
+        StatusCode::OK,
+        NO_STORE,
+        Json(TokenResponse {
+            access_token,
+            token_type: "Bearer".to_string(),
+            expires_in: state.config.jwt_expiration_secs as u64,
+            scope,
+            id_token,
+        }),
+    ))
+}
+
+/// Exchanges an authorization code for an access token on behalf of the user
+/// who approved it, and an ID token if `openid` was granted. Codes are
+/// single-use: presenting one again revokes the token it was exchanged for,
+/// since someone else must have got hold of it.
+async fn redeem_code(
+    state: &AppState,
+    client: &OAuthClient,
+    payload: &TokenRequest,
+) -> Result<(String, String, Option<String>), Response> {
+    let (Some(code), Some(redirect_uri), Some(code_verifier)) = (
+        payload.code.as_deref(),
+        payload.redirect_uri.as_deref(),
+        payload.code_verifier.as_deref(),
+    ) else {
+        return Err(token_error(
+            StatusCode::BAD_REQUEST,
+            "invalid_request",
+            "code, redirect_uri and code_verifier are required",
+        ));
+    };
+
+    let invalid_grant = || token_error(StatusCode::BAD_REQUEST, "invalid_grant", "Invalid, expired or used authorization code");
+    let code_hash = hash_token(code);
+    let now = Utc::now();
+    let Some(grant) = state
+        .oauth
+        .find_code(&code_hash)
+        .await
+        .map_err(IntoResponse::into_response)?
+        .filter(|c| c.client_id == client.client_id)
+    else {
+        return Err(invalid_grant());
+    };
+
+    // Only the party that requested the code may spend it (or, replaying it,
+    // revoke its token); otherwise anyone who saw it could burn it first
+    if grant.expires_at <= now || grant.redirect_uri != redirect_uri || !verify_pkce(code_verifier, &grant.code_challenge) {
+        return Err(invalid_grant());
+    }
+
+    let redeemed = grant.used_at.is_none()
+        && state.oauth.mark_code_used(&code_hash, now).await.map_err(IntoResponse::into_response)?;
+    if !redeemed {
+        warn!("Authorization code for client {} replayed; revoking its access token", client.client_id);
+        let token_expiry = now + Duration::seconds(state.config.jwt_expiration_secs as i64);
+        state
+            .revocations
+            .revoke_token(&grant.token_id, token_expiry)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        return Err(invalid_grant());
+    }
+
+    let Some(user) =
+        state.users.find_by_id(grant.user_id).await.map_err(IntoResponse::into_response)?.filter(|u| !u.disabled)
+    else {
+        return Err(invalid_grant());
+    };
+
+    // The id reserved with the code, so a replayed code can revoke this token
+    let (access_token, id_token) =
+        delegated_tokens(state, &user, &grant.client_id, &grant.scope, grant.nonce, grant.token_id, now);
+    Ok((access_token, grant.scope, id_token))
+}
+
+/// Signs the access token a user delegated to `client_id` with `scope`,
+/// and an ID token when `scope` includes `openid`.
+fn delegated_tokens(
+    state: &AppState,
+    user: &User,
+    client_id: &str,
+    scope: &str,
+    nonce: Option<String>,
+    token_id: String,
+    now: DateTime<Utc>,
+) -> (String, Option<String>) {
+    // Keys rotated to HS256 since the grant can't sign one; the client gets the access token alone
+    let id_token = has_scope(scope, OPENID_SCOPE)
+        .then(|| sign_id_token(state, &id_token_claims(&state.config, user, client_id, scope, nonce, now)))
+        .flatten();
+
+    let mut claims = access_claims(&state.config, user, now);
+    claims.jti = token_id;
+    claims.client_id = Some(client_id.to_string());
+    claims.scope = Some(scope.to_string());
+
+    (sign_token(state, &claims), id_token)
+}
+
+/// Answers a device polling for its token: `authorization_pending` until the
+/// user approves, `slow_down` if it polls faster than its interval, then the
+/// tokens, once.
+async fn redeem_device_code(
+    state: &AppState,
+    client: &OAuthClient,
+    payload: &TokenRequest,
+) -> Result<(String, String, Option<String>), Response> {
+    let Some(device_code) = payload.device_code.as_deref() else {
+        return Err(token_error(StatusCode::BAD_REQUEST, "invalid_request", "device_code is required"));
+    };
+
+    let invalid_grant = || token_error(StatusCode::BAD_REQUEST, "invalid_grant", "Invalid or used device code");
+    let device_code_hash = hash_token(device_code);
+    let now = Utc::now();
+    let Some(grant) = state
+        .oauth
+        .find_device_authorization(&device_code_hash)
+        .await
+        .map_err(IntoResponse::into_response)?
+        .filter(|a| a.client_id == client.client_id)
+    else {
+        return Err(invalid_grant());
+    };
+    if grant.used_at.is_some() {
+        return Err(invalid_grant());
+    }
+    if grant.expires_at <= now {
+        return Err(token_error(StatusCode::BAD_REQUEST, "expired_token", "The device code has expired"));
+    }
+
+    let user_id = match (grant.approved, grant.user_id) {
+        (Some(true), Some(user_id)) => user_id,
+        (Some(_), _) => return Err(token_error(StatusCode::BAD_REQUEST, "access_denied", "The user denied the request")),
+        (None, _) => {
+            let too_fast = grant
+                .last_polled_at
+                .is_some_and(|last| now < last + Duration::seconds(grant.interval_secs as i64));
+            let interval = if too_fast { grant.interval_secs + SLOW_DOWN_SECS } else { grant.interval_secs };
+            state
+                .oauth
+                .record_device_poll(&device_code_hash, now, interval)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            return Err(if too_fast {
+                token_error(
+                    StatusCode::BAD_REQUEST,
+                    "slow_down",
+                    &format!("Wait {} seconds between requests", interval),
+                )
+            } else {
+                token_error(StatusCode::BAD_REQUEST, "authorization_pending", "The user has not answered the request yet")
+            });
+        }
+    };
+
+    let redeemed = state
+        .oauth
+        .mark_device_authorization_used(&device_code_hash, now)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    if !redeemed {
+        return Err(invalid_grant());
+    }
+    let Some(user) =
+        state.users.find_by_id(user_id).await.map_err(IntoResponse::into_response)?.filter(|u| !u.disabled)
+    else {
+        return Err(invalid_grant());
+    };
+
+    let (access_token, id_token) =
+        delegated_tokens(state, &user, &grant.client_id, &grant.scope, None, Uuid::new_v4().to_string(), now);
+    Ok((access_token, grant.scope, id_token))
+}
+
+#[utoipa::path(
+    method(get, post),
+    path = "/userinfo",
+    tag = "oauth",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Claims about the user, limited to the token's scopes", body = UserInfo),
+        (status = 400, description = "Bad request - Invalid user ID"),
+        (status = 401, description = "Unauthorized - Invalid or missing token"),
+        (status = 403, description = "Forbidden - The OAuth token lacks the openid scope"),
+        (status = 404, description = "Not Found - User not found")
+    )
+)]
+/// GET|POST /userinfo
+/// OpenID Connect UserInfo endpoint: the profile of GET /user/profile under
+/// the standard claim names. Tokens issued to a client need the `openid`
+/// scope and only see the claims their other scopes allow; first-party
+/// tokens see them all.
+pub async fn userinfo(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Arc<Claims>>,
+) -> Result<impl IntoResponse, Rejection> {
+    let Ok(user_id) = Uuid::parse_str(&claims.sub) else {
+        return Err((StatusCode::BAD_REQUEST, Json(json!({ "error": "Invalid user ID" }))));
+    };
+    let Some(user) = state.users.find_by_id(user_id).await? else {
+        return Err((StatusCode::NOT_FOUND, Json(json!({ "error": "User not found" }))));
+    };
+
+    Ok((StatusCode::OK, Json(user_info(&user, claims.scope.as_deref()))))
+}
+
+/// The token's claims if it is an access token this server issued and would
+/// still accept. On top of the checks of `auth_middleware`, the user and the
+/// client it was issued to must still exist.
+async fn introspect_token(state: &AppState, token: &str) -> Result<IntrospectionResponse, StoreError> {
+    let inactive = IntrospectionResponse::default();
+
+    // User tokens first: delegated ones also carry the `client_id` and `scope` of client tokens
+    if let Some(claims) = decode_access_token::<Claims>(state, token) {
+        if state.revocations.is_revoked(&claims.jti, &claims.sub, claims.iat as i64).await? {
+            return Ok(inactive);
+        }
+        let Ok(user_id) = Uuid::parse_str(&claims.sub) else {
+            return Ok(inactive);
+        };
+        let Some(user) = state.users.find_by_id(user_id).await?.filter(|u| !u.disabled) else {
+            return Ok(inactive);
+        };
+        if let Some(client_id) = claims.client_id.as_deref() {
+            if state.oauth.find_client(client_id).await?.is_none()
+                || state.revocations.is_revoked(&claims.jti, &client_subject(client_id), claims.iat as i64).await?
+            {
+                return Ok(inactive);
+            }
+        }
+        return Ok(IntrospectionResponse {
+            active: true,
+            scope: claims.scope,
+            client_id: claims.client_id,
+            username: Some(user.email),
+            token_type: Some("Bearer".to_string()),
+            exp: Some(claims.exp),
+            iat: Some(claims.iat),
+            nbf: Some(claims.nbf),
+            sub: Some(claims.sub),
+            aud: Some(claims.aud),
+            iss: Some(claims.iss),
+            jti: Some(claims.jti),
+        });
+    }
+
+    if let Some(claims) = decode_access_token::<ClientClaims>(state, token) {
+        if state.revocations.is_revoked(&claims.jti, &client_subject(&claims.client_id), claims.iat as i64).await?
+            || state.oauth.find_client(&claims.client_id).await?.is_none()
+        {
+            return Ok(inactive);
+        }
+        return Ok(IntrospectionResponse {
+            active: true,
+            scope: Some(claims.scope),
+            client_id: Some(claims.client_id),
+            username: None,
+            token_type: Some("Bearer".to_string()),
+            exp: Some(claims.exp),
+            iat: Some(claims.iat),
+            nbf: Some(claims.nbf),
+            sub: Some(claims.sub),
+            aud: Some(claims.aud),
+            iss: Some(claims.iss),
+            jti: Some(claims.jti),
+        });
+    }
+
+    // Refresh tokens, ID tokens and anything else are never active access tokens
+    Ok(inactive)
+}
+
+#[utoipa::path(
+    post,
+    path = "/oauth/introspect",
+    tag = "oauth",
+    request_body(content = IntrospectionRequest, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Whether the token is active, and its claims if so", body = IntrospectionResponse),
+        (status = 400, description = "invalid_request - No token", body = OAuthErrorResponse),
+        (status = 401, description = "invalid_client - Unknown, public or wrongly authenticated client", body = OAuthErrorResponse)
+    )
+)]
+/// POST /oauth/introspect
+/// Lets a confidential client, typically a resource server, ask whether an
+/// access token is still good (RFC 7662). Tokens that were revoked, or whose
+/// user or client was deleted, are reported inactive.
+pub async fn introspect(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    payload: Result<Form<IntrospectionRequest>, FormRejection>,
+) -> Result<impl IntoResponse, Response> {
+    let Ok(Form(payload)) = payload else {
+        return Err(token_error(StatusCode::BAD_REQUEST, "invalid_request", "Expected a form-encoded request with a token"));
+    };
+    let client =
+        authenticate_client(&state, &headers, payload.client_id.as_deref(), payload.client_secret.as_deref()).await?;
+    // Anyone can pose as a public client, and introspection would let them test stolen tokens
+    if client.secret_hash.is_none() {
+        return Err(invalid_client(false, "Public clients cannot introspect tokens"));
+    }
+
+    let response = introspect_token(&state, &payload.token).await.map_err(IntoResponse::into_response)?;
+    Ok((StatusCode::OK, NO_STORE, Json(response)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/oauth/revoke",
+    tag = "oauth",
+    request_body(content = RevocationRequest, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "The token is revoked, or was not valid to begin with"),
+        (status = 400, description = "invalid_request or unauthorized_client - The token was issued to another client", body = OAuthErrorResponse),
+        (status = 401, description = "invalid_client - Unknown client or wrong secret", body = OAuthErrorResponse)
+    )
+)]
+/// POST /oauth/revoke
+/// Revokes an access token on behalf of the client it was issued to
+/// (RFC 7009). Invalid or expired tokens are not an error: the client's goal
+/// of the token being unusable is met either way.
+pub async fn revoke(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    payload: Result<Form<RevocationRequest>, FormRejection>,
+) -> Result<impl IntoResponse, Response> {
+    let Ok(Form(payload)) = payload else {
+        return Err(token_error(StatusCode::BAD_REQUEST, "invalid_request", "Expected a form-encoded request with a token"));
+    };
+    let client =
+        authenticate_client(&state, &headers, payload.client_id.as_deref(), payload.client_secret.as_deref()).await?;
+
+    // User tokens first: delegated ones also carry the `client_id` of client tokens
+    let issued_to = match decode_access_token::<Claims>(&state, &payload.token) {
+        Some(claims) => Some((claims.client_id, claims.jti, claims.exp)),
+        None => decode_access_token::<ClientClaims>(&state, &payload.token)
+            .map(|claims| (Some(claims.client_id), claims.jti, claims.exp)),
+    };
+
+    if let Some((client_id, jti, exp)) = issued_to {
+        if client_id.as_deref() != Some(client.client_id.as_str()) {
+            return Err(token_error(
+                StatusCode::BAD_REQUEST,
+                "unauthorized_client",
+                "The token was not issued to this client",
+            ));
+        }
+        let expires_at = DateTime::from_timestamp(exp as i64, 0).unwrap_or_else(Utc::now);
+        state.revocations.revoke_token(&jti, expires_at).await.map_err(IntoResponse::into_response)?;
+        info!("Client {} revoked token {}", client.client_id, jti);
+    }
+
+    Ok((StatusCode::OK, NO_STORE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use sha2::{Digest, Sha256};
+
+    use crate::{models::Role, routes::well_known::openid_configuration, test_support};
+
+    const REDIRECT_URI: &str = "http://localhost:8080/callback";
+
+    async fn public_client(state: &AppState) -> OAuthClient {
+        let client = OAuthClient {
+            client_id: "cli".to_string(),
+            name: "CLI".to_string(),
+            secret_hash: None,
+            grant_types: vec![GrantType::AuthorizationCode],
+            redirect_uris: vec![REDIRECT_URI.to_string()],
+            scopes: SUPPORTED_SCOPES.iter().map(|s| s.to_string()).collect(),
+            created_at: Utc::now(),
+        };
+        state.oauth.insert_client(client.clone()).await.unwrap();
+        client
+    }
+
+    /// Approves an authorization request for `client` and returns where the
+    /// user is sent back to.
+    async fn approve_redirect(state: &AppState, client: &OAuthClient, user: &User, verifier: &str) -> String {
+        let request = AuthorizeParams {
+            response_type: "code".to_string(),
+            client_id: client.client_id.clone(),
+            redirect_uri: REDIRECT_URI.to_string(),
+            scope: Some("openid".to_string()),
+            state: None,
+            code_challenge: Some(URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))),
+            code_challenge_method: Some(PKCE_METHOD.to_string()),
+            nonce: None,
+        };
+        let response = decide_authorization(
+            State(state.clone()),
+            Extension(test_support::claims(state, user)),
+            Json(AuthorizeDecision { request, approve: true }),
+        )
+        .await
+        .unwrap()
+        .into_response();
+        test_support::json_body(response).await["redirect_to"].as_str().unwrap().to_string()
+    }
+
+    /// Approves an authorization request for `client` and returns the code.
+    async fn approve(state: &AppState, client: &OAuthClient, user: &User, verifier: &str) -> String {
+        let redirect = approve_redirect(state, client, user, verifier).await;
+        redirect.split_once("code=").unwrap().1.to_string()
+    }
+
+    async fn exchange(state: &AppState, code: &str, verifier: &str) -> Response {
+        let request = TokenRequest {
+            grant_type: GrantType::AuthorizationCode.as_str().to_string(),
+            code: Some(code.to_string()),
+            device_code: None,
+            redirect_uri: Some(REDIRECT_URI.to_string()),
+            client_id: Some("cli".to_string()),
+            client_secret: None,
+            code_verifier: Some(verifier.to_string()),
+            scope: None,
+        };
+        match token(State(state.clone()), HeaderMap::new(), Ok(Form(request))).await {
+            Ok(response) => response.into_response(),
+            Err(response) => response,
+        }
+    }
+
+    /// Registers a confidential client whose secret is `<client_id>-secret`.
+    async fn confidential_client(state: &AppState, client_id: &str) -> OAuthClient {
+        let client = OAuthClient {
+            client_id: client_id.to_string(),
+            name: client_id.to_string(),
+            secret_hash: Some(hash_token(&format!("{}-secret", client_id))),
+            grant_types: vec![GrantType::AuthorizationCode, GrantType::ClientCredentials],
+            redirect_uris: vec![REDIRECT_URI.to_string()],
+            scopes: vec![DEFAULT_SCOPE.to_string()],
+            created_at: Utc::now(),
+        };
+        state.oauth.insert_client(client.clone()).await.unwrap();
+        client
+    }
+
+    /// An access token `user` delegated to `client`.
+    fn delegated_token(state: &AppState, client: &OAuthClient, user: &User) -> String {
+        delegated_tokens(state, user, &client.client_id, DEFAULT_SCOPE, None, Uuid::new_v4().to_string(), Utc::now()).0
+    }
+
+    async fn is_active(state: &AppState, client_id: &str, token: &str) -> bool {
+        let request = IntrospectionRequest {
+            token: token.to_string(),
+            token_type_hint: None,
+            client_id: Some(client_id.to_string()),
+            client_secret: Some(format!("{}-secret", client_id)),
+        };
+        let response = introspect(State(state.clone()), HeaderMap::new(), Ok(Form(request))).await.unwrap().into_response();
+        test_support::json_body(response).await["active"].as_bool().unwrap()
+    }
+
+    async fn revoke_as(state: &AppState, client_id: &str, token: &str) -> Response {
+        let request = RevocationRequest {
+            token: token.to_string(),
+            token_type_hint: None,
+            client_id: Some(client_id.to_string()),
+            client_secret: Some(format!("{}-secret", client_id)),
+        };
+        match revoke(State(state.clone()), HeaderMap::new(), Ok(Form(request))).await {
+            Ok(response) => response.into_response(),
+            Err(response) => response,
+        }
+    }
+
+    async fn client_credentials(state: &AppState, client_id: &str, secret: Option<&str>, scope: Option<&str>) -> Response {
+        let request = TokenRequest {
+            grant_type: GrantType::ClientCredentials.as_str().to_string(),
+            code: None,
+            device_code: None,
+            redirect_uri: None,
+            client_id: Some(client_id.to_string()),
+            client_secret: secret.map(str::to_string),
+            code_verifier: None,
+            scope: scope.map(str::to_string),
+        };
+        match token(State(state.clone()), HeaderMap::new(), Ok(Form(request))).await {
+            Ok(response) => response.into_response(),
+            Err(response) => response,
+        }
+    }
+
+    /// Starts a device authorization for a public client that may use the
+    /// device grant, returning the device and user codes.
+    async fn start_device(state: &AppState) -> (String, String) {
+        let client = OAuthClient {
+            client_id: "tv".to_string(),
+            name: "TV".to_string(),
+            secret_hash: None,
+            grant_types: vec![GrantType::DeviceCode],
+            redirect_uris: Vec::new(),
+            scopes: vec![DEFAULT_SCOPE.to_string()],
+            created_at: Utc::now(),
+        };
+        state.oauth.insert_client(client).await.unwrap();
+
+        let request = DeviceAuthorizationRequest { client_id: Some("tv".to_string()), client_secret: None, scope: None };
+        let response =
+            device_authorization(State(state.clone()), HeaderMap::new(), Ok(Form(request))).await.unwrap().into_response();
+        let body = test_support::json_body(response).await;
+        (body["device_code"].as_str().unwrap().to_string(), body["user_code"].as_str().unwrap().to_string())
+    }
+
+    async fn poll(state: &AppState, device_code: &str) -> Response {
+        let request = TokenRequest {
+            grant_type: GrantType::DeviceCode.as_str().to_string(),
+            code: None,
+            device_code: Some(device_code.to_string()),
+            redirect_uri: None,
+            client_id: Some("tv".to_string()),
+            client_secret: None,
+            code_verifier: None,
+            scope: None,
+        };
+        match token(State(state.clone()), HeaderMap::new(), Ok(Form(request))).await {
+            Ok(response) => response.into_response(),
+            Err(response) => response,
+        }
+    }
+
+    #[tokio::test]
+    async fn device_polls_until_approved() {
+        let state = test_support::memory_state().await;
+        let user = test_support::user(&state, "ada@example.com").await;
+        let (device_code, user_code) = start_device(&state).await;
+
+        let response = poll(&state, &device_code).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(test_support::json_body(response).await["error"], "authorization_pending");
+
+        // Polling again within the 5 second interval widens it
+        let body = test_support::json_body(poll(&state, &device_code).await).await;
+        assert_eq!(body["error"], "slow_down");
+        assert_eq!(body["error_description"], "Wait 10 seconds between requests");
+
+        decide_device(
+            State(state.clone()),
+            Extension(test_support::claims(&state, &user)),
+            Json(DeviceDecision { user_code, approve: true }),
+        )
+        .await
+        .unwrap();
+        let response = poll(&state, &device_code).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(test_support::json_body(response).await["access_token"].is_string());
+
+        let response = poll(&state, &device_code).await;
+        assert_eq!(test_support::json_body(response).await["error"], "invalid_grant");
+    }
+
+    #[tokio::test]
+    async fn expired_device_codes_are_refused() {
+        let mut config = test_support::config(crate::utils::StorageBackend::Memory);
+        config.oauth_device_code_ttl_secs = 0;
+        let state = test_support::state(config).await;
+        let (device_code, _) = start_device(&state).await;
+
+        let response = poll(&state, &device_code).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(test_support::json_body(response).await["error"], "expired_token");
+    }
+
+    #[tokio::test]
+    async fn wrong_verifier_leaves_code_redeemable() {
+        let state = test_support::es256_state().await;
+        let client = public_client(&state).await;
+        let user = test_support::user(&state, "ada@example.com").await;
+        let verifier = generate_opaque_token();
+        let code = approve(&state, &client, &user, &verifier).await;
+
+        let response = exchange(&state, &code, &generate_opaque_token()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(test_support::json_body(response).await["error"], "invalid_grant");
+
+        let response = exchange(&state, &code, &verifier).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = test_support::json_body(response).await;
+        assert!(body["access_token"].is_string());
+        assert!(body["id_token"].is_string());
+    }
+
+    #[tokio::test]
+    async fn replayed_code_is_refused() {
+        let state = test_support::es256_state().await;
+        let client = public_client(&state).await;
+        let user = test_support::user(&state, "ada@example.com").await;
+        let verifier = generate_opaque_token();
+        let code = approve(&state, &client, &user, &verifier).await;
+
+        assert_eq!(exchange(&state, &code, &verifier).await.status(), StatusCode::OK);
+        let response = exchange(&state, &code, &verifier).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(test_support::json_body(response).await["error"], "invalid_grant");
+    }
+
+    #[tokio::test]
+    async fn id_tokens_are_signed_with_the_asymmetric_key() {
+        let state = test_support::es256_state().await;
+        let client = public_client(&state).await;
+        let user = test_support::user(&state, "ada@example.com").await;
+        let verifier = generate_opaque_token();
+        let code = approve(&state, &client, &user, &verifier).await;
+
+        let body = test_support::json_body(exchange(&state, &code, &verifier).await).await;
+        let header = jsonwebtoken::decode_header(body["id_token"].as_str().unwrap()).unwrap();
+        assert_eq!(header.alg, jsonwebtoken::Algorithm::ES256);
+
+        let response = openid_configuration(State(state.clone())).await.unwrap().into_response();
+        let discovery = test_support::json_body(response).await;
+        assert_eq!(discovery["id_token_signing_alg_values_supported"], json!(["ES256"]));
+    }
+
+    #[tokio::test]
+    async fn openid_needs_an_asymmetric_key() {
+        let state = test_support::memory_state().await;
+        let client = public_client(&state).await;
+        let user = test_support::user(&state, "ada@example.com").await;
+
+        let redirect = approve_redirect(&state, &client, &user, &generate_opaque_token()).await;
+        assert!(redirect.contains("error=invalid_scope"));
+        assert!(!redirect.contains("code="));
+
+        let (status, _) = openid_configuration(State(state.clone())).await.err().unwrap();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn revoked_tokens_are_inactive() {
+        let state = test_support::memory_state().await;
+        let app = confidential_client(&state, "app").await;
+        let user = test_support::user(&state, "ada@example.com").await;
+        let token = delegated_token(&state, &app, &user);
+        assert!(is_active(&state, "app", &token).await);
+
+        assert_eq!(revoke_as(&state, "app", &token).await.status(), StatusCode::OK);
+        assert!(!is_active(&state, "app", &token).await);
+        // Revoking again, or revoking garbage, is not an error
+        assert_eq!(revoke_as(&state, "app", &token).await.status(), StatusCode::OK);
+        assert_eq!(revoke_as(&state, "app", "not-a-token").await.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn clients_cannot_revoke_each_others_tokens() {
+        let state = test_support::memory_state().await;
+        let app = confidential_client(&state, "app").await;
+        confidential_client(&state, "other").await;
+        let user = test_support::user(&state, "ada@example.com").await;
+        let token = delegated_token(&state, &app, &user);
+
+        let response = revoke_as(&state, "other", &token).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(test_support::json_body(response).await["error"], "unauthorized_client");
+        assert!(is_active(&state, "other", &token).await);
+
+        // Nor the user's own first-party tokens
+        let first_party = crate::tokens::mint_access_token(&state, &user, Utc::now());
+        assert_eq!(revoke_as(&state, "app", &first_party).await.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn deleted_clients_tokens_are_inactive() {
+        let state = test_support::memory_state().await;
+        let app = confidential_client(&state, "app").await;
+        confidential_client(&state, "rs").await;
+        let user = test_support::user(&state, "ada@example.com").await;
+        let token = delegated_token(&state, &app, &user);
+        assert!(is_active(&state, "rs", &token).await);
+
+        state.oauth.delete_client("app").await.unwrap();
+        assert!(!is_active(&state, "rs", &token).await);
+    }
+
+    #[tokio::test]
+    async fn client_credentials_tokens_name_the_client() {
+        let state = test_support::memory_state().await;
+        confidential_client(&state, "app").await;
+
+        let response = client_credentials(&state, "app", Some("app-secret"), None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = test_support::json_body(response).await;
+        assert_eq!(body["scope"], DEFAULT_SCOPE);
+        assert!(body.get("id_token").is_none());
+        let access_token = body["access_token"].as_str().unwrap();
+        let introspection = introspect_token(&state, access_token).await.unwrap();
+        assert!(introspection.active);
+        assert_eq!(introspection.sub.as_deref(), Some("client:app"));
+        assert_eq!(introspection.client_id.as_deref(), Some("app"));
+    }
+
+    #[tokio::test]
+    async fn client_credentials_are_checked() {
+        let state = test_support::memory_state().await;
+        confidential_client(&state, "app").await;
+        public_client(&state).await;
+
+        let response = client_credentials(&state, "app", Some("app-secret"), Some("openid")).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(test_support::json_body(response).await["error"], "invalid_scope");
+
+        let response = client_credentials(&state, "app", Some("wrong-secret"), None).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(test_support::json_body(response).await["error"], "invalid_client");
+
+        let response = client_credentials(&state, "cli", None, None).await;
+        assert_eq!(test_support::json_body(response).await["error"], "unauthorized_client");
+    }
+
+    #[tokio::test]
+    async fn recreating_a_client_does_not_revive_its_tokens() {
+        let state = test_support::memory_state().await;
+        let app = confidential_client(&state, "app").await;
+        confidential_client(&state, "rs").await;
+        let mut admin = test_support::user(&state, "admin@example.com").await;
+        admin.role = Role::Admin;
+        let user = test_support::user(&state, "ada@example.com").await;
+        let delegated = delegated_token(&state, &app, &user);
+        let response = client_credentials(&state, "app", Some("app-secret"), None).await;
+        let own = test_support::json_body(response).await["access_token"].as_str().unwrap().to_string();
+
+        let response = crate::routes::protected::delete_client(
+            State(state.clone()),
+            Extension(test_support::claims(&state, &admin)),
+            axum::extract::Path("app".to_string()),
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        confidential_client(&state, "app").await;
+        assert!(!is_active(&state, "rs", &delegated).await);
+        assert!(!is_active(&state, "rs", &own).await);
+    }
+
+    #[tokio::test]
+    async fn deleted_users_tokens_are_inactive() {
+        let state = test_support::memory_state().await;
+        let app = confidential_client(&state, "app").await;
+        let user = test_support::user(&state, "ada@example.com").await;
+        let token = delegated_token(&state, &app, &user);
+
+        state.users.delete(user.id).await.unwrap();
+        assert!(!is_active(&state, "app", &token).await);
+    }
+
+    #[tokio::test]
+    async fn disabled_users_tokens_are_inactive() {
+        let state = test_support::es256_state().await;
+        let client = public_client(&state).await;
+        let mut user = test_support::user(&state, "ada@example.com").await;
+        let token = crate::tokens::mint_access_token(&state, &user, Utc::now());
+        let verifier = generate_opaque_token();
+        let code = approve(&state, &client, &user, &verifier).await;
+        assert!(introspect_token(&state, &token).await.unwrap().active);
+
+        user.disabled = true;
+        state.users.update(&user).await.unwrap();
+        assert!(!introspect_token(&state, &token).await.unwrap().active);
+        let response = exchange(&state, &code, &verifier).await;
+        assert_eq!(test_support::json_body(response).await["error"], "invalid_grant");
+    }
+}
@@ -0,0 +1,517 @@
+use axum::{
+    extract::{Extension, Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{Duration, Utc};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing::warn;
+use utoipa::OpenApi;
+use uuid::Uuid;
+
+use crate::{
+    email::normalize_email,
+    middleware::auth::Claims,
+    models::{
+        AuthenticatorSelection, Ceremony, CredentialDescriptor, CredentialParameters, LoginResponse, Passkey,
+        PasskeyChallenge, PasskeyCreationOptions, PasskeyInfo, PasskeyLoginOptionsRequest, PasskeyLoginRequest,
+        PasskeyRegistrationRequest, PasskeyRequestOptions, RelyingPartyEntity, User, UserEntity,
+    },
+    routes::mfa::mfa_challenge,
+    store::StoreError,
+    tokens::issue_tokens,
+    utils::{generate_opaque_token, hash_token},
+    webauthn::{self, RelyingParty, SUPPORTED_ALGORITHMS},
+    AppState,
+};
+
+/// Passkeys (WebAuthn): registering credentials on an account, managing them,
+/// and logging in with one instead of a password.
+#[derive(OpenApi)]
+#[openapi(
+    paths(registration_options, register_passkey, list_passkeys, delete_passkey, login_options, login_passkey),
+    components(schemas(
+        PasskeyCreationOptions,
+        PasskeyRequestOptions,
+        PasskeyRegistrationRequest,
+        PasskeyLoginOptionsRequest,
+        PasskeyLoginRequest,
+        PasskeyInfo
+    )),
+)]
+pub struct PasskeyApi;
+
+type Rejection = (StatusCode, Json<Value>);
+
+const MAX_NAME_CHARS: usize = 64;
+
+fn relying_party(state: &AppState) -> RelyingParty<'_> {
+    RelyingParty {
+        id: &state.config.webauthn_rp_id,
+        origins: &state.config.webauthn_origins,
+    }
+}
+
+/// The WebAuthn user handle: the account id, which reveals nothing personal.
+fn user_handle(user_id: Uuid) -> String {
+    URL_SAFE_NO_PAD.encode(user_id.as_bytes())
+}
+
+fn credential_descriptor(passkey: &Passkey) -> CredentialDescriptor {
+    CredentialDescriptor {
+        kind: "public-key".to_string(),
+        id: passkey.credential_id.clone(),
+    }
+}
+
+/// Stores a new challenge for `ceremony` and returns it for the browser.
+async fn issue_challenge(state: &AppState, user_id: Option<Uuid>, ceremony: Ceremony) -> Result<String, StoreError> {
+    let challenge = generate_opaque_token();
+    state
+        .passkeys
+        .insert_challenge(PasskeyChallenge {
+            challenge_hash: hash_token(&challenge),
+            user_id,
+            ceremony,
+            expires_at: Utc::now() + Duration::seconds(state.config.webauthn_challenge_ttl_secs as i64),
+        })
+        .await?;
+    Ok(challenge)
+}
+
+/// Spends the challenge a response answers. `None` unless it was issued for
+/// `ceremony` and hasn't expired or been answered before.
+async fn take_challenge(
+    state: &AppState,
+    challenge: &str,
+    ceremony: Ceremony,
+) -> Result<Option<PasskeyChallenge>, StoreError> {
+    Ok(state
+        .passkeys
+        .take_challenge(&hash_token(challenge))
+        .await?
+        .filter(|c| c.ceremony == ceremony && c.expires_at > Utc::now()))
+}
+
+async fn current_user(state: &AppState, claims: &Claims) -> Result<User, Rejection> {
+    let Ok(user_id) = Uuid::parse_str(&claims.sub) else {
+        return Err((StatusCode::BAD_REQUEST, Json(json!({ "error": "Invalid user ID" }))));
+    };
+    state
+        .users
+        .find_by_id(user_id)
+        .await?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, Json(json!({ "error": "User not found" }))))
+}
+
+#[utoipa::path(
+    post,
+    path = "/user/passkeys/register/options",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Options to pass to navigator.credentials.create()", body = PasskeyCreationOptions),
+        (status = 401, description = "Unauthorized - Invalid or missing token")
+    )
+)]
+/// POST /user/passkeys/register/options
+/// Starts registering a passkey on the authenticated account.
+pub async fn registration_options(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Arc<Claims>>,
+) -> Result<impl IntoResponse, Rejection> {
+    let user = current_user(&state, &claims).await?;
+    let existing = state.passkeys.list_for_user(user.id).await?;
+    let challenge = issue_challenge(&state, Some(user.id), Ceremony::Registration).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(PasskeyCreationOptions {
+            challenge,
+            rp: RelyingPartyEntity {
+                id: state.config.webauthn_rp_id.clone(),
+                name: state.config.webauthn_rp_name.clone(),
+            },
+            user: UserEntity {
+                id: user_handle(user.id),
+                name: user.email.clone(),
+                display_name: format!("{} {}", user.first_name, user.last_name),
+            },
+            pub_key_cred_params: SUPPORTED_ALGORITHMS
+                .iter()
+                .map(|alg| CredentialParameters { kind: "public-key".to_string(), alg: *alg })
+                .collect(),
+            timeout: state.config.webauthn_challenge_ttl_secs as u64 * 1000,
+            attestation: "none".to_string(),
+            // Stops the same authenticator from being registered twice
+            exclude_credentials: existing.iter().map(credential_descriptor).collect(),
+            authenticator_selection: AuthenticatorSelection {
+                resident_key: "preferred".to_string(),
+                user_verification: "preferred".to_string(),
+            },
+        }),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/user/passkeys",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = PasskeyRegistrationRequest,
+    responses(
+        (status = 201, description = "Passkey registered", body = PasskeyInfo),
+        (status = 400, description = "Bad request - Invalid response or unknown, expired or used challenge"),
+        (status = 401, description = "Unauthorized - Invalid or missing token"),
+        (status = 409, description = "Conflict - The credential is already registered")
+    )
+)]
+/// POST /user/passkeys
+/// Finishes a registration with the response of navigator.credentials.create().
+pub async fn register_passkey(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Arc<Claims>>,
+    Json(payload): Json<PasskeyRegistrationRequest>,
+) -> Result<impl IntoResponse, Rejection> {
+    let user = current_user(&state, &claims).await?;
+    let invalid = |reason: String| (StatusCode::BAD_REQUEST, Json(json!({ "error": reason })));
+
+    let registration = webauthn::verify_registration(&relying_party(&state), &payload.credential)
+        .map_err(|e| invalid(e.to_string()))?;
+    let challenge = take_challenge(&state, &registration.challenge, Ceremony::Registration).await?;
+    if challenge.is_none_or(|c| c.user_id != Some(user.id)) {
+        return Err(invalid("Unknown or expired challenge; start the registration again".to_string()));
+    }
+
+    let name = payload.name.as_deref().map(str::trim).filter(|n| !n.is_empty()).unwrap_or("Passkey");
+    let passkey = Passkey {
+        id: Uuid::now_v7(),
+        user_id: user.id,
+        credential_id: registration.credential_id,
+        public_key: registration.public_key,
+        sign_count: registration.sign_count,
+        name: name.chars().take(MAX_NAME_CHARS).collect(),
+        created_at: Utc::now(),
+        last_used_at: None,
+    };
+    match state.passkeys.insert(passkey.clone()).await {
+        Ok(()) => {}
+        Err(StoreError::Conflict) => {
+            return Err((StatusCode::CONFLICT, Json(json!({ "error": "Passkey already registered" }))));
+        }
+        Err(e) => return Err(e.into()),
+    }
+    tracing::info!("User {} registered passkey {}", user.id, passkey.id);
+
+    Ok((StatusCode::CREATED, Json(PasskeyInfo::from(&passkey))))
+}
+
+#[utoipa::path(
+    get,
+    path = "/user/passkeys",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "The account's passkeys, oldest first", body = [PasskeyInfo]),
+        (status = 401, description = "Unauthorized - Invalid or missing token")
+    )
+)]
+/// GET /user/passkeys
+/// Lists the authenticated user's passkeys.
+pub async fn list_passkeys(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Arc<Claims>>,
+) -> Result<impl IntoResponse, Rejection> {
+    let user = current_user(&state, &claims).await?;
+    let passkeys = state.passkeys.list_for_user(user.id).await?;
+
+    Ok((StatusCode::OK, Json(passkeys.iter().map(PasskeyInfo::from).collect::<Vec<_>>())))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/user/passkeys/{id}",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        ("id" = Uuid, Path, description = "Id of the passkey to remove")
+    ),
+    responses(
+        (status = 200, description = "Passkey removed; it can no longer log in"),
+        (status = 401, description = "Unauthorized - Invalid or missing token"),
+        (status = 404, description = "Not Found - No such passkey on this account")
+    )
+)]
+/// DELETE /user/passkeys/{id}
+/// Removes one of the authenticated user's passkeys.
+pub async fn delete_passkey(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Arc<Claims>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, Rejection> {
+    let user = current_user(&state, &claims).await?;
+    match state.passkeys.delete(user.id, id).await {
+        Ok(()) => {}
+        Err(StoreError::NotFound) => {
+            return Err((StatusCode::NOT_FOUND, Json(json!({ "error": "Passkey not found" }))));
+        }
+        Err(e) => return Err(e.into()),
+    }
+    tracing::info!("User {} removed passkey {}", user.id, id);
+
+    Ok((StatusCode::OK, Json(json!({ "message": "Passkey removed" }))))
+}
+
+#[utoipa::path(
+    post,
+    path = "/login/passkey/options",
+    tag = "auth",
+    request_body = PasskeyLoginOptionsRequest,
+    responses(
+        (status = 200, description = "Options to pass to navigator.credentials.get()", body = PasskeyRequestOptions)
+    )
+)]
+/// POST /login/passkey/options
+/// Starts a passkey login. Without an email the authenticator offers the
+/// passkeys it holds for this site; unknown emails get an empty list, like
+/// accounts without passkeys.
+pub async fn login_options(
+    State(state): State<AppState>,
+    payload: Option<Json<PasskeyLoginOptionsRequest>>,
+) -> Result<impl IntoResponse, Rejection> {
+    let Json(payload) = payload.unwrap_or_default();
+    let user = match payload.email.map(|e| normalize_email(&e, state.config.email_local_part_folding)) {
+        Some(Ok(email)) => state.users.find_by_email(&email).await?,
+        _ => None,
+    };
+    let allow_credentials = match &user {
+        Some(user) => state.passkeys.list_for_user(user.id).await?.iter().map(credential_descriptor).collect(),
+        None => Vec::new(),
+    };
+    let challenge = issue_challenge(&state, user.map(|u| u.id), Ceremony::Authentication).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(PasskeyRequestOptions {
+            challenge,
+            rp_id: state.config.webauthn_rp_id.clone(),
+            timeout: state.config.webauthn_challenge_ttl_secs as u64 * 1000,
+            allow_credentials,
+            user_verification: "preferred".to_string(),
+        }),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/login/passkey",
+    tag = "auth",
+    request_body = PasskeyLoginRequest,
+    responses(
+        (status = 200, description = "Login successful, or an MFA challenge when the authenticator didn't verify the user and the account has TOTP enabled", body = LoginResponse),
+        (status = 401, description = "Unknown passkey, invalid signature, or unknown, expired or used challenge")
+    )
+)]
+/// POST /login/passkey
+/// Logs in with the response of navigator.credentials.get().
+pub async fn login_passkey(
+    State(state): State<AppState>,
+    Json(payload): Json<PasskeyLoginRequest>,
+) -> Result<axum::response::Response, Rejection> {
+    let failed = || (StatusCode::UNAUTHORIZED, Json(json!({ "error": "Passkey login failed" })));
+    let credential = &payload.credential;
+
+    let Some(passkey) = state.passkeys.find_by_credential_id(credential.raw_id.trim_end_matches('=')).await? else {
+        return Err(failed());
+    };
+    let assertion = webauthn::verify_assertion(&relying_party(&state), credential, &passkey.public_key).map_err(|e| {
+        warn!("Rejected assertion for passkey {}: {}", passkey.id, e);
+        failed()
+    })?;
+
+    let challenge = take_challenge(&state, &assertion.challenge, Ceremony::Authentication).await?;
+    if challenge.is_none_or(|c| c.user_id.is_some_and(|id| id != passkey.user_id)) {
+        return Err(failed());
+    }
+    if credential.response.user_handle.as_deref().is_some_and(|h| h.trim_end_matches('=') != user_handle(passkey.user_id)) {
+        return Err(failed());
+    }
+
+    // A counter that doesn't move forward means two copies of the key exist
+    if (assertion.sign_count != 0 || passkey.sign_count != 0) && assertion.sign_count <= passkey.sign_count {
+        warn!(
+            "Passkey {} presented counter {} after {}; the authenticator may be cloned",
+            passkey.id, assertion.sign_count, passkey.sign_count
+        );
+        return Err(failed());
+    }
+    if !state.passkeys.record_use(passkey.id, passkey.sign_count, assertion.sign_count, Utc::now()).await? {
+        return Err(failed());
+    }
+
+    let Some(user) = state.users.find_by_id(passkey.user_id).await?.filter(|u| !u.disabled) else {
+        return Err(failed());
+    };
+
+    // Without user verification the passkey only proves possession, like a password
+    if !assertion.user_verified && state.mfa.find_totp(user.id).await?.is_some_and(|t| t.enabled_at.is_some()) {
+        return Ok(mfa_challenge(&state, &user).await);
+    }
+
+    let response: LoginResponse = issue_tokens(&state, &user, None, "Login successful").await?;
+    Ok((StatusCode::OK, Json(response)).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::{PasskeyRequestOptions, TotpCredential},
+        soft_authenticator::{self, StoredCredential},
+        test_support,
+    };
+
+    const ORIGIN: &str = "http://localhost:5173";
+
+    async fn creation_options(state: &AppState, user: &User) -> PasskeyCreationOptions {
+        let response = registration_options(State(state.clone()), Extension(test_support::claims(state, user)))
+            .await
+            .unwrap()
+            .into_response();
+        serde_json::from_value(test_support::json_body(response).await).unwrap()
+    }
+
+    async fn register(
+        state: &AppState,
+        user: &User,
+        authenticator: &mut Vec<StoredCredential>,
+        options: &PasskeyCreationOptions,
+        origin: &str,
+    ) -> Result<(), StatusCode> {
+        let credential = soft_authenticator::create(authenticator, options, origin, true).unwrap();
+        let request = PasskeyRegistrationRequest { name: None, credential };
+        register_passkey(State(state.clone()), Extension(test_support::claims(state, user)), Json(request))
+            .await
+            .map(|_| ())
+            .map_err(|(status, _)| status)
+    }
+
+    async fn request_options(state: &AppState) -> PasskeyRequestOptions {
+        let response = login_options(State(state.clone()), None).await.unwrap().into_response();
+        serde_json::from_value(test_support::json_body(response).await).unwrap()
+    }
+
+    async fn login(
+        state: &AppState,
+        authenticator: &mut [StoredCredential],
+        options: &PasskeyRequestOptions,
+        user_verified: bool,
+    ) -> Result<Value, StatusCode> {
+        let credential = soft_authenticator::get(authenticator, options, ORIGIN, user_verified).unwrap();
+        match login_passkey(State(state.clone()), Json(PasskeyLoginRequest { credential })).await {
+            Ok(response) => Ok(test_support::json_body(response).await),
+            Err((status, _)) => Err(status),
+        }
+    }
+
+    /// A user with a passkey in the returned authenticator.
+    async fn registered_user(state: &AppState) -> (User, Vec<StoredCredential>) {
+        let user = test_support::user(state, "ada@example.com").await;
+        let mut authenticator = Vec::new();
+        let options = creation_options(state, &user).await;
+        register(state, &user, &mut authenticator, &options, ORIGIN).await.unwrap();
+        (user, authenticator)
+    }
+
+    #[tokio::test]
+    async fn registers_and_logs_in() {
+        let state = test_support::memory_state().await;
+        let (_, mut authenticator) = registered_user(&state).await;
+
+        let body = login(&state, &mut authenticator, &request_options(&state).await, true).await.unwrap();
+        assert!(body["access_token"].is_string());
+    }
+
+    #[tokio::test]
+    async fn rejects_foreign_origin_or_rp_id() {
+        let state = test_support::memory_state().await;
+        let user = test_support::user(&state, "ada@example.com").await;
+        let mut authenticator = Vec::new();
+
+        let options = creation_options(&state, &user).await;
+        let result = register(&state, &user, &mut authenticator, &options, "https://evil.example").await;
+        assert_eq!(result, Err(StatusCode::BAD_REQUEST));
+
+        let mut options = creation_options(&state, &user).await;
+        options.rp.id = "evil.example".to_string();
+        let result = register(&state, &user, &mut authenticator, &options, ORIGIN).await;
+        assert_eq!(result, Err(StatusCode::BAD_REQUEST));
+
+        assert!(state.passkeys.list_for_user(user.id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn rejects_replayed_or_foreign_challenges() {
+        let state = test_support::memory_state().await;
+        let (_, mut authenticator) = registered_user(&state).await;
+
+        let options = request_options(&state).await;
+        login(&state, &mut authenticator, &options, true).await.unwrap();
+        assert_eq!(login(&state, &mut authenticator, &options, true).await, Err(StatusCode::UNAUTHORIZED));
+
+        let mut options = request_options(&state).await;
+        options.challenge = generate_opaque_token();
+        assert_eq!(login(&state, &mut authenticator, &options, true).await, Err(StatusCode::UNAUTHORIZED));
+
+        // A registration challenge can't answer a login
+        let other = test_support::user(&state, "grace@example.com").await;
+        let mut options = request_options(&state).await;
+        options.challenge = creation_options(&state, &other).await.challenge;
+        assert_eq!(login(&state, &mut authenticator, &options, true).await, Err(StatusCode::UNAUTHORIZED));
+    }
+
+    #[tokio::test]
+    async fn rejects_counter_that_does_not_advance() {
+        let state = test_support::memory_state().await;
+        let (_, mut authenticator) = registered_user(&state).await;
+        let mut clone = authenticator.clone();
+
+        login(&state, &mut authenticator, &request_options(&state).await, true).await.unwrap();
+        // The copy signs with the same counter the original just used
+        let result = login(&state, &mut clone, &request_options(&state).await, true).await;
+        assert_eq!(result, Err(StatusCode::UNAUTHORIZED));
+    }
+
+    #[tokio::test]
+    async fn unverified_login_falls_back_to_totp() {
+        let state = test_support::memory_state().await;
+        let (user, mut authenticator) = registered_user(&state).await;
+        let now = Utc::now();
+        state
+            .mfa
+            .begin_totp(TotpCredential {
+                user_id: user.id,
+                secret: "JBSWY3DPEHPK3PXP".to_string(),
+                created_at: now,
+                enabled_at: None,
+                last_used_step: None,
+            })
+            .await
+            .unwrap();
+        state.mfa.enable_totp(user.id, now).await.unwrap();
+
+        let body = login(&state, &mut authenticator, &request_options(&state).await, false).await.unwrap();
+        assert_eq!(body["mfa_required"], true);
+        assert!(body.get("access_token").is_none());
+
+        let body = login(&state, &mut authenticator, &request_options(&state).await, true).await.unwrap();
+        assert!(body["access_token"].is_string());
+    }
+}
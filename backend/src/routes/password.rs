@@ -0,0 +1,268 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use chrono::{Duration, Utc};
+use serde_json::{json, Value};
+use tracing::warn;
+use utoipa::OpenApi;
+
+use crate::{
+    email::normalize_email,
+    mail::Email,
+    models::{ForgotPasswordRequest, PasswordPolicyError, ResetPasswordRequest, TokenPurpose},
+    password::{enforce_policy, PasswordOwner},
+    tokens::issue_action_token,
+    utils::{hash_token, Config},
+    AppState,
+};
+
+/// Forgotten password recovery: a reset link is mailed to the account's
+/// address and can be used once, within `PASSWORD_RESET_TTL_SECS`.
+#[derive(OpenApi)]
+#[openapi(
+    paths(forgot_password, reset_password),
+    components(schemas(ForgotPasswordRequest, ResetPasswordRequest)),
+)]
+pub struct PasswordApi;
+
+#[utoipa::path(
+    post,
+    path = "/password/forgot",
+    tag = "auth",
+    request_body = ForgotPasswordRequest,
+    responses(
+        (status = 202, description = "Reset link mailed if the account exists")
+    )
+)]
+/// POST /password/forgot
+/// Mails a password reset link. Answers the same way, and just as fast,
+/// whether or not the email belongs to an account.
+pub async fn forgot_password(
+    State(state): State<AppState>,
+    Json(payload): Json<ForgotPasswordRequest>,
+) -> impl IntoResponse {
+    // The lookup and delivery run after we answer, so timing reveals nothing either
+    tokio::spawn(send_reset_link(state, payload.email));
+
+    (
+        StatusCode::ACCEPTED,
+        Json(json!({ "message": "If an account exists for that email, a reset link is on its way" })),
+    )
+}
+
+async fn send_reset_link(state: AppState, email: String) {
+    let Ok(email) = normalize_email(&email, state.config.email_local_part_folding) else {
+        return;
+    };
+    let user = match state.users.find_by_email(&email).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return,
+        Err(e) => {
+            warn!("Failed to look up account for password reset: {}", e);
+            return;
+        }
+    };
+
+    let ttl = state.config.password_reset_ttl_secs;
+    let token = match issue_action_token(&state, &user, TokenPurpose::PasswordReset, ttl).await {
+        Ok(token) => token,
+        Err(e) => {
+            warn!("Failed to store password reset token for {}: {}", user.id, e);
+            return;
+        }
+    };
+
+    if let Err(e) = state.mailer.send(password_reset_email(&state.config, &user.email, &token)).await {
+        warn!("Failed to send password reset email to {}: {}", user.id, e);
+    }
+}
+
+fn password_reset_email(config: &Config, email: &str, token: &str) -> Email {
+    Email {
+        to: email.to_string(),
+        subject: "Reset your password".to_string(),
+        body: format!(
+            "Somebody, perhaps you, asked to reset the password of your account. \
+             Open this link within {} minutes to choose a new one:\n\n{}/reset-password?token={}\n\n\
+             If it wasn't you, ignore this message; your password has not changed.",
+            config.password_reset_ttl_secs / 60,
+            config.app_url,
+            token
+        ),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/password/reset",
+    tag = "auth",
+    request_body = ResetPasswordRequest,
+    responses(
+        (status = 200, description = "Password reset; every session of the account is signed out"),
+        (status = 400, description = "Bad request - Invalid, used or expired token, mismatch or password policy violations", body = PasswordPolicyError)
+    )
+)]
+/// POST /password/reset
+/// Sets a new password with the token from a reset link. The token works
+/// once, and every existing session of the account is revoked.
+pub async fn reset_password(
+    State(state): State<AppState>,
+    Json(payload): Json<ResetPasswordRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+    let invalid = || {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "Invalid or expired reset token", "field": "token" })),
+        )
+    };
+
+    if payload.new_password != payload.confirm_password {
+        return Err((StatusCode::BAD_REQUEST, Json(json!({ "error": "Passwords do not match" }))));
+    }
+
+    let token_hash = hash_token(&payload.token);
+    let now = Utc::now();
+    let token = state
+        .action_tokens
+        .find_by_hash(&token_hash)
+        .await?
+        .filter(|t| t.purpose == TokenPurpose::PasswordReset && t.used_at.is_none() && t.expires_at > now)
+        .ok_or_else(invalid)?;
+    let Some(mut user) = state.users.find_by_id(token.user_id).await? else {
+        return Err(invalid());
+    };
+
+    // Checked before the token is spent, so a rejected password doesn't burn the link
+    let owner = PasswordOwner {
+        email: &user.email,
+        first_name: &user.first_name,
+        last_name: &user.last_name,
+    };
+    enforce_policy(&state.config.password_policy, &state.breached_passwords, &payload.new_password, &owner)?;
+
+    let hashed = state.passwords.hash(&payload.new_password).await.map_err(|e| {
+        tracing::error!("{}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "Hash failure" })))
+    })?;
+
+    // Only one of two concurrent requests with the same token gets past this
+    if !state.action_tokens.mark_used(&token_hash, now).await? {
+        return Err(invalid());
+    }
+
+    user.password = hashed;
+    // Following the link proved the user reads this inbox
+    user.email_verified = true;
+    state.users.update(&user).await?;
+    state.action_tokens.revoke_user(user.id, TokenPurpose::PasswordReset).await?;
+
+    // Whoever knew the old password may still hold a session
+    let expires_at = now + Duration::seconds(state.config.jwt_expiration_secs as i64);
+    state.revocations.revoke_subject(&user.id.to_string(), now, expires_at, None).await?;
+    state.refresh_tokens.revoke_user(user.id).await?;
+    tracing::info!("User {} reset their password", user.id);
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({ "message": "Password has been reset. Log in with your new password." })),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::User,
+        password::Verification,
+        test_support,
+        tokens::{access_claims, issue_tokens},
+    };
+
+    const NEW_PASSWORD: &str = "a brand new passphrase";
+
+    async fn forgot(state: &AppState, email: &str) -> StatusCode {
+        let payload = ForgotPasswordRequest { email: email.to_string() };
+        forgot_password(State(state.clone()), Json(payload)).await.into_response().status()
+    }
+
+    /// Waits for the reset link sent in the background and returns its token.
+    async fn mailed_token(state: &AppState) -> String {
+        for _ in 0..200 {
+            if let Some(email) = state.mailer.outbox().unwrap().pop() {
+                return email.body.split("token=").nth(1).unwrap().split_whitespace().next().unwrap().to_string();
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("no reset link was mailed");
+    }
+
+    async fn reset(state: &AppState, token: &str) -> StatusCode {
+        let payload = ResetPasswordRequest {
+            token: token.to_string(),
+            new_password: NEW_PASSWORD.to_string(),
+            confirm_password: NEW_PASSWORD.to_string(),
+        };
+        match reset_password(State(state.clone()), Json(payload)).await {
+            Ok(response) => response.into_response().status(),
+            Err((status, _)) => status,
+        }
+    }
+
+    async fn stored_user(state: &AppState, user: &User) -> User {
+        state.users.find_by_id(user.id).await.unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn answers_alike_for_unknown_emails() {
+        let state = test_support::memory_state().await;
+        test_support::user(&state, "ada@example.com").await;
+
+        assert_eq!(forgot(&state, "nobody@example.com").await, StatusCode::ACCEPTED);
+        assert_eq!(forgot(&state, "ada@example.com").await, StatusCode::ACCEPTED);
+        mailed_token(&state).await;
+        assert_eq!(state.mailer.outbox().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn reset_token_is_stored_hashed_and_works_once() {
+        let state = test_support::memory_state().await;
+        let user = test_support::user(&state, "ada@example.com").await;
+        forgot(&state, "ada@example.com").await;
+        let token = mailed_token(&state).await;
+
+        assert!(state.action_tokens.find_by_hash(&token).await.unwrap().is_none());
+        assert!(state.action_tokens.find_by_hash(&hash_token(&token)).await.unwrap().is_some());
+
+        assert_eq!(reset(&state, &token).await, StatusCode::OK);
+        let updated = stored_user(&state, &user).await;
+        let verification = state.passwords.verify(NEW_PASSWORD, &updated.password).await.unwrap();
+        assert!(matches!(verification, Verification::Valid));
+
+        assert_eq!(reset(&state, &token).await, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn expired_token_is_refused() {
+        let state = test_support::memory_state().await;
+        let user = test_support::user(&state, "ada@example.com").await;
+        let token = issue_action_token(&state, &user, TokenPurpose::PasswordReset, 0).await.unwrap();
+
+        assert_eq!(reset(&state, &token).await, StatusCode::BAD_REQUEST);
+        assert_eq!(stored_user(&state, &user).await.password, user.password);
+    }
+
+    #[tokio::test]
+    async fn reset_signs_out_earlier_sessions() {
+        let state = test_support::memory_state().await;
+        let user = test_support::user(&state, "ada@example.com").await;
+        let session = issue_tokens(&state, &user, None, "Login successful").await.unwrap();
+        // Issued within the second the reset happens in
+        let earlier = access_claims(&state.config, &user, Utc::now());
+
+        forgot(&state, "ada@example.com").await;
+        assert_eq!(reset(&state, &mailed_token(&state).await).await, StatusCode::OK);
+
+        let revoked = state.revocations.is_revoked(&earlier.jti, &earlier.sub, earlier.iat as i64).await.unwrap();
+        assert!(revoked);
+        let refresh = state.refresh_tokens.find_by_hash(&hash_token(&session.refresh_token)).await.unwrap();
+        assert!(refresh.unwrap().revoked);
+    }
+}
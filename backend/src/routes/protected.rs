@@ -1,20 +1,59 @@
 use axum::{
-    extract::Extension,
+    extract::{Extension, Path, State},
     http::StatusCode,
     response::IntoResponse,
     Json,
 };
-use serde_json::json;
-use std::sync::{Arc, Mutex};
+use chrono::{DateTime, Duration, Utc};
+use serde_json::{json, Value};
+use std::sync::Arc;
 use utoipa::OpenApi;
+use uuid::Uuid;
 
-use crate::{middleware::auth::Claims, models::UserResponse};
-use crate::models::{RegisterRequest, Role, User};
-/// Aggregates all protected routes: admin dashboard, admin-only registration, user profile view.
+use crate::{email::normalize_email, mail::Email, middleware::auth::Claims, models::UserResponse, tokens::issue_tokens_with_jti, utils::{generate_opaque_token, hash_token}, AppState};
+use crate::oauth::{client_subject, is_valid_client_id, is_valid_redirect_uri, is_valid_scope};
+use crate::store::StoreError;
+use crate::password::{enforce_policy, PasswordOwner, Verification};
+use crate::routes::verification::send_verification_link;
+use crate::models::{
+    ChangePasswordRequest, ChangePasswordResponse, ClientSecretResponse, GrantType, LogoutRequest, NewUser,
+    OAuthClient, OAuthClientInfo, PasswordPolicyError, RegisterClientRequest, RegisterRequest, RegisteredClient, Role,
+    User,
+};
+/// Aggregates all protected routes: admin dashboard, admin-only registration, OAuth client
+/// management, user profile view, password change, logout, admin session revocation and the
+/// local mail outbox.
 #[derive(OpenApi)]
 #[openapi(
-    paths(admin_dashboard, register_admin, user_profile),
-    components(schemas(User, RegisterRequest, Role, UserResponse)),
+    paths(
+        admin_dashboard,
+        register_admin,
+        list_clients,
+        register_client,
+        rotate_client_secret,
+        delete_client,
+        user_profile,
+        change_password,
+        logout,
+        revoke_sessions,
+        disable_user,
+        enable_user,
+        outbox
+    ),
+    components(schemas(
+        User,
+        RegisterRequest,
+        Role,
+        UserResponse,
+        OAuthClientInfo,
+        RegisterClientRequest,
+        RegisteredClient,
+        ClientSecretResponse,
+        ChangePasswordRequest,
+        ChangePasswordResponse,
+        LogoutRequest,
+        Email
+    )),
 )]
 pub struct ProtectedApi;
 
@@ -34,15 +73,15 @@ pub struct ProtectedApi;
 /// GET /admin/dashboard
 /// Returns system stats and list of users — only accessible by Admins.
 pub async fn admin_dashboard(
+    State(state): State<AppState>,
     Extension(claims): Extension<Arc<Claims>>,
-    Extension(users): Extension<Arc<Mutex<Vec<User>>>>,
 ) -> impl IntoResponse {
     if claims.role != Role::Admin {
         return Err((StatusCode::FORBIDDEN, Json(json!({"error": "Admin access required"}))));
     }
 
-    let users_guard = users.lock().unwrap();
-    let list: Vec<UserResponse> = users_guard
+    let users = state.users.list().await?;
+    let list: Vec<UserResponse> = users
         .iter()
         .map(|u| UserResponse {
             id: u.id,
@@ -50,11 +89,13 @@ pub async fn admin_dashboard(
             first_name: u.first_name.clone(),
             last_name: u.last_name.clone(),
             role: u.role.clone(),
+            email_verified: u.email_verified,
+            disabled: u.disabled,
         })
         .collect();
 
     let payload = json!({
-        "user_count": users_guard.len(),
+        "user_count": users.len(),
         "users": list,
     });
 
@@ -70,7 +111,7 @@ pub async fn admin_dashboard(
     request_body = RegisterRequest,
     responses(
         (status = 201, description = "Admin user created", body = UserResponse),
-        (status = 400, description = "Bad request - Validation error"),
+        (status = 400, description = "Bad request - Validation error or password policy violations", body = PasswordPolicyError),
         (status = 401, description = "Unauthorized - Invalid or missing token"),
         (status = 403, description = "Forbidden - Admin access required"),
         (status = 409, description = "Conflict - Email already registered"),
@@ -80,8 +121,8 @@ pub async fn admin_dashboard(
 /// POST /admin/register
 /// Allows Admin to create a new Admin user.
 pub async fn register_admin(
+    State(state): State<AppState>,
     Extension(claims): Extension<Arc<Claims>>,
-    Extension(users): Extension<Arc<Mutex<Vec<User>>>>,
     Json(payload): Json<RegisterRequest>,
 ) -> impl IntoResponse {
     if claims.role != Role::Admin {
@@ -97,27 +138,42 @@ pub async fn register_admin(
         return Err((StatusCode::BAD_REQUEST, Json(json!({ "error": "Passwords do not match" }))));
     }
 
-    if payload.password.len() < 6 {
-        return Err((StatusCode::BAD_REQUEST, Json(json!({ "error": "Password must be at least 6 characters" }))));
-    }
+    let email = normalize_email(&payload.email, state.config.email_local_part_folding).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": format!("Invalid email format: {}", e.0), "field": "email" })),
+        )
+    })?;
 
-    let mut users_guard = users.lock().unwrap();
-    if users_guard.iter().any(|u| u.email == payload.email) {
-        return Err((StatusCode::CONFLICT, Json(json!({ "error": "Email already registered" }))));
-    }
+    let owner = PasswordOwner {
+        email: &email,
+        first_name: &payload.first_name,
+        last_name: &payload.last_name,
+    };
+    enforce_policy(&state.config.password_policy, &state.breached_passwords, &payload.password, &owner)?;
 
-    let hashed = bcrypt::hash(&payload.password, bcrypt::DEFAULT_COST)
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "Hash failure" }))))?;
+    let hashed = state.passwords.hash(&payload.password).await.map_err(|e| {
+        tracing::error!("{}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "Hash failure" })))
+    })?;
 
-    let new_admin = User {
-        id: (users_guard.len() + 1) as i32,
-        email: payload.email.clone(),
+    let new_admin = NewUser {
+        email,
         first_name: payload.first_name.clone(),
         last_name: payload.last_name.clone(),
         password: hashed,
         role: Role::Admin,
+        email_verified: false,
+    };
+    // The store rejects duplicate emails atomically
+    let new_admin = match state.users.insert(new_admin).await {
+        Ok(user) => user,
+        Err(StoreError::Conflict) => {
+            return Err((StatusCode::CONFLICT, Json(json!({ "error": "Email already registered" }))));
+        }
+        Err(e) => return Err(e.into()),
     };
-    users_guard.push(new_admin.clone());
+    tokio::spawn(send_verification_link(state.clone(), new_admin.clone()));
 
     let response = UserResponse {
         id: new_admin.id,
@@ -125,11 +181,231 @@ pub async fn register_admin(
         first_name: new_admin.first_name,
         last_name: new_admin.last_name,
         role: new_admin.role,
+        email_verified: new_admin.email_verified,
+        disabled: new_admin.disabled,
     };
 
     Ok((StatusCode::CREATED, Json(response)))
 }
 
+#[utoipa::path(
+    get,
+    path = "/admin/oauth/clients",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Registered OAuth clients", body = [OAuthClientInfo]),
+        (status = 401, description = "Unauthorized - Invalid or missing token"),
+        (status = 403, description = "Forbidden - Admin access required")
+    )
+)]
+/// GET /admin/oauth/clients
+/// Lists the registered OAuth clients — only accessible by Admins.
+pub async fn list_clients(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Arc<Claims>>,
+) -> impl IntoResponse {
+    if claims.role != Role::Admin {
+        return Err((StatusCode::FORBIDDEN, Json(json!({ "error": "Admin access required" }))));
+    }
+
+    let clients: Vec<OAuthClientInfo> = state.oauth.list_clients().await?.iter().map(OAuthClientInfo::from).collect();
+    Ok((StatusCode::OK, Json(clients)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/oauth/clients",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = RegisterClientRequest,
+    responses(
+        (status = 201, description = "Client registered; the secret is only shown here", body = RegisteredClient),
+        (status = 400, description = "Bad request - Invalid client id, grants, redirect URIs or scopes"),
+        (status = 401, description = "Unauthorized - Invalid or missing token"),
+        (status = 403, description = "Forbidden - Admin access required"),
+        (status = 409, description = "Conflict - Client ID already registered")
+    )
+)]
+/// POST /admin/oauth/clients
+/// Registers an OAuth client, generating its secret if it is confidential —
+/// only accessible by Admins.
+pub async fn register_client(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Arc<Claims>>,
+    Json(payload): Json<RegisterClientRequest>,
+) -> impl IntoResponse {
+    if claims.role != Role::Admin {
+        return Err((StatusCode::FORBIDDEN, Json(json!({ "error": "Admin access required" }))));
+    }
+    let invalid = |reason: &str| (StatusCode::BAD_REQUEST, Json(json!({ "error": reason })));
+
+    let client_id = payload.client_id.unwrap_or_else(|| Uuid::new_v4().to_string());
+    if !is_valid_client_id(&client_id) {
+        return Err(invalid("client_id must be 1 to 64 letters, digits or -._~"));
+    }
+    let name = payload.name.trim();
+    if name.is_empty() {
+        return Err(invalid("name is required"));
+    }
+
+    let mut grant_types: Vec<GrantType> = Vec::new();
+    for grant in payload.grant_types {
+        if !grant_types.contains(&grant) {
+            grant_types.push(grant);
+        }
+    }
+    if grant_types.is_empty() {
+        return Err(invalid("At least one grant type is required"));
+    }
+    if grant_types.contains(&GrantType::ClientCredentials) && !payload.confidential {
+        return Err(invalid("The client_credentials grant requires a confidential client"));
+    }
+    if grant_types.contains(&GrantType::AuthorizationCode) && payload.redirect_uris.is_empty() {
+        return Err(invalid("The authorization_code grant requires at least one redirect URI"));
+    }
+    if let Some(uri) = payload.redirect_uris.iter().find(|uri| !is_valid_redirect_uri(uri)) {
+        return Err(invalid(&format!("Invalid redirect URI '{}': it must be absolute, without a fragment", uri)));
+    }
+    if let Some(scope) = payload.scopes.iter().find(|scope| !is_valid_scope(scope)) {
+        return Err(invalid(&format!("Invalid scope '{}'", scope)));
+    }
+    let mut scopes: Vec<String> = Vec::new();
+    for scope in payload.scopes {
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+
+    let secret = payload.confidential.then(generate_opaque_token);
+    let client = OAuthClient {
+        client_id,
+        name: name.to_string(),
+        secret_hash: secret.as_deref().map(hash_token),
+        grant_types,
+        redirect_uris: payload.redirect_uris,
+        scopes,
+        created_at: Utc::now(),
+    };
+    match state.oauth.insert_client(client.clone()).await {
+        Ok(()) => {}
+        Err(StoreError::Conflict) => {
+            return Err((StatusCode::CONFLICT, Json(json!({ "error": "Client ID already registered" }))));
+        }
+        Err(e) => return Err(e.into()),
+    }
+    tracing::info!("Registered OAuth client {}", client.client_id);
+
+    Ok((
+        StatusCode::CREATED,
+        Json(RegisteredClient {
+            client: OAuthClientInfo::from(&client),
+            client_secret: secret,
+        }),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/oauth/clients/{client_id}/secret",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        ("client_id" = String, Path, description = "Id of the confidential client")
+    ),
+    responses(
+        (status = 200, description = "New secret; the previous one stops working", body = ClientSecretResponse),
+        (status = 400, description = "Bad request - Public clients have no secret"),
+        (status = 401, description = "Unauthorized - Invalid or missing token"),
+        (status = 403, description = "Forbidden - Admin access required"),
+        (status = 404, description = "Not Found - Client not found")
+    )
+)]
+/// POST /admin/oauth/clients/{client_id}/secret
+/// Replaces a confidential client's secret — only accessible by Admins.
+pub async fn rotate_client_secret(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Arc<Claims>>,
+    Path(client_id): Path<String>,
+) -> impl IntoResponse {
+    if claims.role != Role::Admin {
+        return Err((StatusCode::FORBIDDEN, Json(json!({ "error": "Admin access required" }))));
+    }
+    let not_found = || (StatusCode::NOT_FOUND, Json(json!({ "error": "Client not found" })));
+
+    let Some(client) = state.oauth.find_client(&client_id).await? else {
+        return Err(not_found());
+    };
+    if client.secret_hash.is_none() {
+        return Err((StatusCode::BAD_REQUEST, Json(json!({ "error": "Public clients have no secret" }))));
+    }
+
+    let secret = generate_opaque_token();
+    match state.oauth.set_client_secret(&client.client_id, &hash_token(&secret)).await {
+        Ok(()) => {}
+        Err(StoreError::NotFound) => return Err(not_found()),
+        Err(e) => return Err(e.into()),
+    }
+    tracing::info!("Rotated the secret of OAuth client {}", client.client_id);
+
+    Ok((
+        StatusCode::OK,
+        Json(ClientSecretResponse {
+            client_id: client.client_id,
+            client_secret: secret,
+        }),
+    ))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/admin/oauth/clients/{client_id}",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        ("client_id" = String, Path, description = "Id of the client to delete")
+    ),
+    responses(
+        (status = 200, description = "Client deleted and the tokens it got for itself revoked"),
+        (status = 401, description = "Unauthorized - Invalid or missing token"),
+        (status = 403, description = "Forbidden - Admin access required"),
+        (status = 404, description = "Not Found - Client not found")
+    )
+)]
+/// DELETE /admin/oauth/clients/{client_id}
+/// Removes an OAuth client and revokes its client credentials tokens; tokens
+/// users delegated to it stop working too — only accessible by Admins.
+pub async fn delete_client(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Arc<Claims>>,
+    Path(client_id): Path<String>,
+) -> impl IntoResponse {
+    if claims.role != Role::Admin {
+        return Err((StatusCode::FORBIDDEN, Json(json!({ "error": "Admin access required" }))));
+    }
+
+    match state.oauth.delete_client(&client_id).await {
+        Ok(()) => {}
+        Err(StoreError::NotFound) => {
+            return Err((StatusCode::NOT_FOUND, Json(json!({ "error": "Client not found" }))));
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    // Covers its client credentials tokens and those users delegated to it,
+    // so registering the id again doesn't bring them back
+    let now = Utc::now();
+    let expires_at = now + Duration::seconds(state.config.jwt_expiration_secs as i64);
+    state.revocations.revoke_subject(&client_subject(&client_id), now, expires_at, None).await?;
+    tracing::info!("Deleted OAuth client {}", client_id);
+
+    Ok((StatusCode::OK, Json(json!({ "message": "Client deleted" }))))
+}
+
 #[utoipa::path(
     get,
     path = "/user/profile",
@@ -148,22 +424,20 @@ pub async fn register_admin(
 /// GET /user/profile
 /// Returns the authenticated user's profile info — accessible by both Users and Admins.
 pub async fn user_profile(
+    State(state): State<AppState>,
     Extension(claims): Extension<Arc<Claims>>,
-    Extension(users): Extension<Arc<Mutex<Vec<User>>>>,
 ) -> impl IntoResponse {
     // Allow both User and Admin roles to access their profile
     if claims.role != Role::User && claims.role != Role::Admin {
         return Err((StatusCode::FORBIDDEN, Json(json!({ "error": "Authentication required" }))));
     }
 
-    let users_guard = users.lock().unwrap();
-    let user_id = claims.sub.parse::<i32>().unwrap_or(0);
-    
-    if user_id == 0 {
+    // Tokens minted before the switch to UUIDs carry a numeric `sub`; they are rejected here
+    let Ok(user_id) = Uuid::parse_str(&claims.sub) else {
         return Err((StatusCode::BAD_REQUEST, Json(json!({ "error": "Invalid user ID" }))));
-    }
-    
-    match users_guard.iter().find(|u| u.id == user_id) {
+    };
+
+    match state.users.find_by_id(user_id).await? {
         Some(u) => {
             let profile = UserResponse {
                 id: u.id,
@@ -171,9 +445,370 @@ pub async fn user_profile(
                 first_name: u.first_name.clone(),
                 last_name: u.last_name.clone(),
                 role: u.role.clone(),
+                email_verified: u.email_verified,
+                disabled: u.disabled,
             };
             Ok((StatusCode::OK, Json(profile)))
         }
         None => Err((StatusCode::NOT_FOUND, Json(json!({ "error": "User not found" })))),
     }
 }
+
+#[utoipa::path(
+    post,
+    path = "/user/password",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = ChangePasswordRequest,
+    responses(
+        (status = 200, description = "Password changed", body = ChangePasswordResponse),
+        (status = 400, description = "Bad request - Wrong current password, mismatch or password policy violations", body = PasswordPolicyError),
+        (status = 401, description = "Unauthorized - Invalid or missing token"),
+        (status = 404, description = "Not Found - User not found")
+    )
+)]
+/// POST /user/password
+/// Changes the authenticated user's password after checking the current one —
+/// accessible by both Users and Admins.
+pub async fn change_password(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Arc<Claims>>,
+    Json(payload): Json<ChangePasswordRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+    let Ok(user_id) = Uuid::parse_str(&claims.sub) else {
+        return Err((StatusCode::BAD_REQUEST, Json(json!({ "error": "Invalid user ID" }))));
+    };
+    let Some(mut user) = state.users.find_by_id(user_id).await? else {
+        return Err((StatusCode::NOT_FOUND, Json(json!({ "error": "User not found" }))));
+    };
+
+    if payload.new_password != payload.confirm_password {
+        return Err((StatusCode::BAD_REQUEST, Json(json!({ "error": "Passwords do not match" }))));
+    }
+
+    let hash_failure = |e: crate::password::PasswordError| {
+        tracing::error!("{}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "Hash failure" })))
+    };
+
+    // A stolen access token alone must not be enough to take over the account
+    let verification = state
+        .passwords
+        .verify(&payload.current_password, &user.password)
+        .await
+        .map_err(hash_failure)?;
+    if verification == Verification::Invalid {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "Current password is incorrect", "field": "current_password" })),
+        ));
+    }
+    if payload.new_password == payload.current_password {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "New password must differ from the current one", "field": "password" })),
+        ));
+    }
+
+    let owner = PasswordOwner {
+        email: &user.email,
+        first_name: &user.first_name,
+        last_name: &user.last_name,
+    };
+    enforce_policy(&state.config.password_policy, &state.breached_passwords, &payload.new_password, &owner)?;
+
+    user.password = state.passwords.hash(&payload.new_password).await.map_err(hash_failure)?;
+    state.users.update(&user).await?;
+    tracing::info!("User {} changed their password", user.id);
+
+    if !payload.revoke_other_sessions {
+        return Ok((
+            StatusCode::OK,
+            Json(ChangePasswordResponse {
+                message: "Password changed".to_string(),
+                session: None,
+            }),
+        ));
+    }
+
+    // Revoke everything, the caller's tokens included, then hand the caller a
+    // new session. Its token is likely minted within the second of the
+    // revocation, so it is spared by its jti.
+    let now = Utc::now();
+    let expires_at = now + Duration::seconds(state.config.jwt_expiration_secs as i64);
+    let jti = Uuid::new_v4().to_string();
+    state.revocations.revoke_subject(&user.id.to_string(), now, expires_at, Some(&jti)).await?;
+    state.refresh_tokens.revoke_user(user.id).await?;
+    let session = issue_tokens_with_jti(&state, &user, jti, "Password changed").await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(ChangePasswordResponse {
+            message: "Password changed; other sessions signed out".to_string(),
+            session: Some(session),
+        }),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/logout",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body(content = Option<LogoutRequest>, description = "Optionally the session's refresh token"),
+    responses(
+        (status = 200, description = "Access token (and refresh token family) revoked"),
+        (status = 401, description = "Unauthorized - Invalid or missing token")
+    )
+)]
+/// POST /logout
+/// Revokes the presented access token and, when given, the refresh token family of the session.
+pub async fn logout(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Arc<Claims>>,
+    payload: Option<Json<LogoutRequest>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+    // The revocation entry only has to outlive the token itself
+    let expires_at = DateTime::from_timestamp(claims.exp as i64, 0).unwrap_or_else(Utc::now);
+    state.revocations.revoke_token(&claims.jti, expires_at).await?;
+
+    if let Some(refresh_token) = payload.and_then(|Json(p)| p.refresh_token) {
+        let token = state.refresh_tokens.find_by_hash(&hash_token(&refresh_token)).await?;
+
+        // Only the session's owner may end it with its refresh token
+        if let Some(token) = token.filter(|t| t.user_id.to_string() == claims.sub) {
+            state.refresh_tokens.revoke_family(token.family_id).await?;
+        }
+    }
+
+    Ok((StatusCode::OK, Json(json!({ "message": "Logged out" }))))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/users/{id}/revoke-sessions",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        ("id" = Uuid, Path, description = "Id of the user whose sessions are revoked")
+    ),
+    responses(
+        (status = 200, description = "All of the user's tokens revoked"),
+        (status = 401, description = "Unauthorized - Invalid or missing token"),
+        (status = 403, description = "Forbidden - Admin access required"),
+        (status = 404, description = "Not Found - User not found")
+    )
+)]
+/// POST /admin/users/{id}/revoke-sessions
+/// Invalidates every access and refresh token a user holds — only accessible by Admins.
+pub async fn revoke_sessions(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Arc<Claims>>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    if claims.role != Role::Admin {
+        return Err((StatusCode::FORBIDDEN, Json(json!({ "error": "Admin access required" }))));
+    }
+
+    let Some(user) = state.users.find_by_id(id).await? else {
+        return Err((StatusCode::NOT_FOUND, Json(json!({ "error": "User not found" }))));
+    };
+
+    revoke_all_sessions(&state, &user).await?;
+
+    Ok((StatusCode::OK, Json(json!({ "message": "Sessions revoked" }))))
+}
+
+/// Revokes every access and refresh token `user` holds.
+async fn revoke_all_sessions(state: &AppState, user: &User) -> Result<(), StoreError> {
+    // Every access token issued so far has expired once a full lifetime has passed
+    let now = Utc::now();
+    let expires_at = now + Duration::seconds(state.config.jwt_expiration_secs as i64);
+    state.revocations.revoke_subject(&user.id.to_string(), now, expires_at, None).await?;
+    state.refresh_tokens.revoke_user(user.id).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/users/{id}/disable",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        ("id" = Uuid, Path, description = "Id of the user to disable")
+    ),
+    responses(
+        (status = 200, description = "User disabled and all of their tokens revoked"),
+        (status = 400, description = "Bad request - Admins can't disable themselves"),
+        (status = 401, description = "Unauthorized - Invalid or missing token"),
+        (status = 403, description = "Forbidden - Admin access required"),
+        (status = 404, description = "Not Found - User not found")
+    )
+)]
+/// POST /admin/users/{id}/disable
+/// Locks a user out: logins are refused and the tokens they hold stop working
+/// — only accessible by Admins.
+pub async fn disable_user(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Arc<Claims>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+    set_disabled(&state, &claims, id, true).await?;
+    Ok((StatusCode::OK, Json(json!({ "message": "User disabled" }))))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/users/{id}/enable",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        ("id" = Uuid, Path, description = "Id of the user to enable")
+    ),
+    responses(
+        (status = 200, description = "User can log in again"),
+        (status = 401, description = "Unauthorized - Invalid or missing token"),
+        (status = 403, description = "Forbidden - Admin access required"),
+        (status = 404, description = "Not Found - User not found")
+    )
+)]
+/// POST /admin/users/{id}/enable
+/// Lets a disabled user log in again; the sessions revoked when they were
+/// disabled stay revoked — only accessible by Admins.
+pub async fn enable_user(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Arc<Claims>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+    set_disabled(&state, &claims, id, false).await?;
+    Ok((StatusCode::OK, Json(json!({ "message": "User enabled" }))))
+}
+
+async fn set_disabled(
+    state: &AppState,
+    claims: &Claims,
+    id: Uuid,
+    disabled: bool,
+) -> Result<(), (StatusCode, Json<Value>)> {
+    if claims.role != Role::Admin {
+        return Err((StatusCode::FORBIDDEN, Json(json!({ "error": "Admin access required" }))));
+    }
+    // Otherwise the last admin could lock everyone out of the admin endpoints
+    if disabled && claims.sub == id.to_string() {
+        return Err((StatusCode::BAD_REQUEST, Json(json!({ "error": "You can't disable your own account" }))));
+    }
+
+    let Some(mut user) = state.users.find_by_id(id).await? else {
+        return Err((StatusCode::NOT_FOUND, Json(json!({ "error": "User not found" }))));
+    };
+    user.disabled = disabled;
+    state.users.update(&user).await?;
+    if disabled {
+        revoke_all_sessions(state, &user).await?;
+    }
+    tracing::info!("User {} {} by admin {}", user.id, if disabled { "disabled" } else { "enabled" }, claims.sub);
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/outbox",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Every message sent since startup, oldest first", body = [Email]),
+        (status = 401, description = "Unauthorized - Invalid or missing token"),
+        (status = 403, description = "Forbidden - Admin access required"),
+        (status = 404, description = "Not Found - The mailer does not keep an outbox")
+    )
+)]
+/// GET /admin/outbox
+/// Lists the messages held by `MAILER=memory`, so emailed links can be followed
+/// locally — only accessible by Admins.
+pub async fn outbox(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Arc<Claims>>,
+) -> impl IntoResponse {
+    if claims.role != Role::Admin {
+        return Err((StatusCode::FORBIDDEN, Json(json!({ "error": "Admin access required" }))));
+    }
+
+    match state.mailer.outbox() {
+        Some(messages) => Ok((StatusCode::OK, Json(messages))),
+        None => Err((StatusCode::NOT_FOUND, Json(json!({ "error": "Outbox is only kept with MAILER=memory" })))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_support,
+        tokens::{decode_access_token, issue_tokens},
+    };
+
+    async fn admin(state: &AppState) -> User {
+        let mut admin = test_support::user(state, "admin@example.com").await;
+        admin.role = Role::Admin;
+        state.users.update(&admin).await.unwrap();
+        admin
+    }
+
+    #[tokio::test]
+    async fn disabling_a_user_revokes_their_sessions() {
+        let state = test_support::memory_state().await;
+        let admin = admin(&state).await;
+        let user = test_support::user(&state, "ada@example.com").await;
+        let session = issue_tokens(&state, &user, None, "Login successful").await.unwrap();
+
+        let claims = test_support::claims(&state, &admin);
+        disable_user(State(state.clone()), Extension(claims.clone()), Path(user.id)).await.unwrap();
+        assert!(state.users.find_by_id(user.id).await.unwrap().unwrap().disabled);
+        let refresh_token = state.refresh_tokens.find_by_hash(&hash_token(&session.refresh_token)).await.unwrap();
+        assert!(refresh_token.unwrap().revoked);
+
+        enable_user(State(state.clone()), Extension(claims), Path(user.id)).await.unwrap();
+        assert!(!state.users.find_by_id(user.id).await.unwrap().unwrap().disabled);
+    }
+
+    #[tokio::test]
+    async fn password_change_signs_out_everything_but_the_new_session() {
+        let state = test_support::memory_state().await;
+        let user = test_support::user(&state, "ada@example.com").await;
+        // Issued within the second of the change, just before it
+        let earlier = test_support::claims(&state, &user);
+
+        let payload = ChangePasswordRequest {
+            current_password: test_support::PASSWORD.to_string(),
+            new_password: "a different horse staple".to_string(),
+            confirm_password: "a different horse staple".to_string(),
+            revoke_other_sessions: true,
+        };
+        let response = change_password(State(state.clone()), Extension(earlier.clone()), Json(payload))
+            .await
+            .unwrap()
+            .into_response();
+        let body = test_support::json_body(response).await;
+        let token = body["session"]["access_token"].as_str().unwrap();
+        let current: Claims = decode_access_token(&state, token).unwrap();
+
+        let revocations = &state.revocations;
+        assert!(revocations.is_revoked(&earlier.jti, &earlier.sub, earlier.iat as i64).await.unwrap());
+        assert!(!revocations.is_revoked(&current.jti, &current.sub, current.iat as i64).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn admins_cannot_disable_themselves() {
+        let state = test_support::memory_state().await;
+        let admin = admin(&state).await;
+
+        let claims = test_support::claims(&state, &admin);
+        let (status, _) = disable_user(State(state.clone()), Extension(claims), Path(admin.id)).await.err().unwrap();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
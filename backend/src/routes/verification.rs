@@ -0,0 +1,159 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use chrono::{Duration, Utc};
+use serde_json::{json, Value};
+use tracing::warn;
+use utoipa::OpenApi;
+
+use crate::{
+    email::normalize_email,
+    mail::Email,
+    models::{ResendVerificationRequest, TokenPurpose, User, VerifyEmailRequest},
+    tokens::issue_action_token,
+    utils::{hash_token, Config},
+    AppState,
+};
+
+/// Email address verification: new accounts are mailed a link that sets
+/// `email_verified`. What unverified accounts may do is set by `UNVERIFIED_LOGIN`.
+#[derive(OpenApi)]
+#[openapi(
+    paths(verify_email, resend_verification),
+    components(schemas(VerifyEmailRequest, ResendVerificationRequest)),
+)]
+pub struct VerificationApi;
+
+/// Mails `user` a verification link. Failures are only logged; the user can
+/// ask for another link.
+pub async fn send_verification_link(state: AppState, user: User) {
+    let ttl = state.config.email_verification_ttl_secs;
+    let token = match issue_action_token(&state, &user, TokenPurpose::EmailVerification, ttl).await {
+        Ok(token) => token,
+        Err(e) => {
+            warn!("Failed to store email verification token for {}: {}", user.id, e);
+            return;
+        }
+    };
+
+    if let Err(e) = state.mailer.send(verification_email(&state.config, &user.email, &token)).await {
+        warn!("Failed to send verification email to {}: {}", user.id, e);
+    }
+}
+
+fn verification_email(config: &Config, email: &str, token: &str) -> Email {
+    Email {
+        to: email.to_string(),
+        subject: "Verify your email address".to_string(),
+        body: format!(
+            "Welcome! Confirm that this is your email address by opening this link within {} hours:\n\n\
+             {}/verify-email?token={}\n\n\
+             If you didn't create an account, you can ignore this message.",
+            config.email_verification_ttl_secs / 3600,
+            config.app_url,
+            token
+        ),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/verify-email",
+    tag = "auth",
+    request_body = VerifyEmailRequest,
+    responses(
+        (status = 200, description = "Email address verified; refresh the session for an unrestricted token"),
+        (status = 400, description = "Bad request - Invalid, used or expired token")
+    )
+)]
+/// POST /verify-email
+/// Marks the account's email as verified with the token from a verification link.
+pub async fn verify_email(
+    State(state): State<AppState>,
+    Json(payload): Json<VerifyEmailRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+    let invalid = || {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "Invalid or expired verification token", "field": "token" })),
+        )
+    };
+
+    let token_hash = hash_token(&payload.token);
+    let now = Utc::now();
+    let token = state
+        .action_tokens
+        .find_by_hash(&token_hash)
+        .await?
+        .filter(|t| t.purpose == TokenPurpose::EmailVerification && t.used_at.is_none() && t.expires_at > now)
+        .ok_or_else(invalid)?;
+    let Some(mut user) = state.users.find_by_id(token.user_id).await? else {
+        return Err(invalid());
+    };
+
+    if !state.action_tokens.mark_used(&token_hash, now).await? {
+        return Err(invalid());
+    }
+
+    user.email_verified = true;
+    state.users.update(&user).await?;
+    state.action_tokens.revoke_user(user.id, TokenPurpose::EmailVerification).await?;
+    tracing::info!("User {} verified their email address", user.id);
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({ "message": "Email address verified. Refresh your session or log in again for full access." })),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/verify-email/resend",
+    tag = "auth",
+    request_body = ResendVerificationRequest,
+    responses(
+        (status = 202, description = "A new link is mailed if the account exists, is unverified and wasn't sent one recently")
+    )
+)]
+/// POST /verify-email/resend
+/// Mails a fresh verification link, at most once per
+/// `VERIFICATION_RESEND_INTERVAL_SECS`. Answers the same way for every email,
+/// so it can't be used to probe for accounts.
+pub async fn resend_verification(
+    State(state): State<AppState>,
+    Json(payload): Json<ResendVerificationRequest>,
+) -> impl IntoResponse {
+    tokio::spawn(resend_if_due(state, payload.email));
+
+    (
+        StatusCode::ACCEPTED,
+        Json(json!({ "message": "If that account still needs verifying, a new link is on its way" })),
+    )
+}
+
+async fn resend_if_due(state: AppState, email: String) {
+    let Ok(email) = normalize_email(&email, state.config.email_local_part_folding) else {
+        return;
+    };
+    let user = match state.users.find_by_email(&email).await {
+        Ok(Some(user)) if !user.email_verified => user,
+        Ok(_) => return,
+        Err(e) => {
+            warn!("Failed to look up account for verification resend: {}", e);
+            return;
+        }
+    };
+
+    let interval = Duration::seconds(state.config.verification_resend_interval_secs as i64);
+    match state.action_tokens.last_issued(user.id, TokenPurpose::EmailVerification).await {
+        Ok(Some(last)) if Utc::now() - last < interval => {
+            tracing::info!("Throttled verification resend for {}", user.id);
+            return;
+        }
+        Ok(_) => {}
+        Err(e) => {
+            warn!("Failed to check verification resend for {}: {}", user.id, e);
+            return;
+        }
+    }
+
+    send_verification_link(state, user).await;
+}
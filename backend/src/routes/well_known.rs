@@ -0,0 +1,79 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use jsonwebtoken::jwk::JwkSet;
+use serde_json::{json, Value};
+use utoipa::OpenApi;
+
+use crate::{
+    models::{GrantType, OpenIdConfiguration},
+    oauth::{PKCE_METHOD, SUPPORTED_SCOPES},
+    AppState,
+};
+
+/// Public discovery documents served under `/.well-known`.
+#[derive(OpenApi)]
+#[openapi(paths(jwks, openid_configuration), components(schemas(OpenIdConfiguration)))]
+pub struct WellKnownApi;
+
+/// Claims that can appear in ID tokens and UserInfo responses.
+const CLAIMS_SUPPORTED: [&str; 11] = [
+    "sub", "iss", "aud", "exp", "iat", "nonce", "name", "given_name", "family_name", "email", "email_verified",
+];
+
+#[utoipa::path(
+    get,
+    path = "/.well-known/jwks.json",
+    tag = "well-known",
+    responses(
+        (status = 200, description = "JSON Web Key Set with the public keys that sign access tokens", content_type = "application/json")
+    )
+)]
+/// GET /.well-known/jwks.json
+/// Publishes the public signing keys so other services can verify access tokens:
+/// pending keys before they start signing and retired ones until their tokens expire.
+/// HS256 keys are never listed.
+pub async fn jwks(State(state): State<AppState>) -> impl IntoResponse {
+    Json(JwkSet { keys: state.keys.current().public_jwks() })
+}
+
+#[utoipa::path(
+    get,
+    path = "/.well-known/openid-configuration",
+    tag = "well-known",
+    responses(
+        (status = 200, description = "OpenID Provider metadata", body = OpenIdConfiguration),
+        (status = 404, description = "Not Found - Access tokens are signed with HS256, so ID tokens can't be issued")
+    )
+)]
+/// GET /.well-known/openid-configuration
+/// OpenID Connect discovery: where the endpoints are and what they support.
+/// URLs are built from `JWT_ISSUER`, which must be the public URL of this API.
+pub async fn openid_configuration(
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+    let Some(algorithm) = state.keys.current().id_token_signer().map(|key| key.algorithm) else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "OpenID Connect needs an RS256, ES256 or EdDSA signing key" })),
+        ));
+    };
+    let issuer = state.config.jwt_issuer.clone();
+    let base = issuer.trim_end_matches('/');
+    let strings = |values: &[&str]| values.iter().map(|v| v.to_string()).collect();
+
+    Ok(Json(OpenIdConfiguration {
+        authorization_endpoint: format!("{}/oauth/authorize", base),
+        token_endpoint: format!("{}/oauth/token", base),
+        device_authorization_endpoint: format!("{}/oauth/device_authorization", base),
+        userinfo_endpoint: format!("{}/userinfo", base),
+        jwks_uri: format!("{}/.well-known/jwks.json", base),
+        issuer,
+        scopes_supported: strings(&SUPPORTED_SCOPES),
+        response_types_supported: strings(&["code"]),
+        grant_types_supported: GrantType::ALL.iter().map(|g| g.as_str().to_string()).collect(),
+        subject_types_supported: strings(&["public"]),
+        id_token_signing_alg_values_supported: vec![format!("{:?}", algorithm)],
+        token_endpoint_auth_methods_supported: strings(&["client_secret_basic", "client_secret_post", "none"]),
+        code_challenge_methods_supported: strings(&[PKCE_METHOD]),
+        claims_supported: strings(&CLAIMS_SUPPORTED),
+    }))
+}
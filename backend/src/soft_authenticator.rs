@@ -0,0 +1,203 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ciborium::value::Value;
+use p256::ecdsa::{signature::Signer, Signature, SigningKey};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::{fmt, fs, io::Read};
+
+use crate::models::{
+    AssertionResponse, AttestationResponse, AuthenticationCredential, PasskeyCreationOptions, PasskeyRequestOptions,
+    RegistrationCredential,
+};
+use crate::webauthn::COSE_ES256;
+
+#[derive(Debug, Clone)]
+pub struct SoftAuthenticatorError(pub String);
+
+impl fmt::Display for SoftAuthenticatorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "soft authenticator: {}", self.0)
+    }
+}
+
+impl std::error::Error for SoftAuthenticatorError {}
+
+const USAGE: &str = "usage: auth_api soft-authenticator <create|get> <state.json> <origin> [--no-user-verification]";
+
+/// A passkey held by the software authenticator. The private key is stored in
+/// the clear, so state files are for testing only.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct StoredCredential {
+    credential_id: String,
+    rp_id: String,
+    user_handle: String,
+    /// Base64url P-256 private scalar.
+    private_key: String,
+    sign_count: u32,
+}
+
+/// `auth_api soft-authenticator <create|get> <state.json> <origin> [--no-user-verification]`
+///
+/// A software ES256 authenticator for exercising the passkey endpoints without
+/// hardware. It reads the options returned by `/user/passkeys/register/options`
+/// or `/login/passkey/options` on stdin and prints the credential JSON to send
+/// back. Credentials are kept in `state.json`.
+pub fn command(args: &[String]) -> Result<(), SoftAuthenticatorError> {
+    let (action, state_path, origin) = match args {
+        [action, state_path, origin, ..] => (action.as_str(), state_path, origin),
+        _ => return Err(SoftAuthenticatorError(USAGE.to_string())),
+    };
+    let user_verified = !args[3..].iter().any(|a| a == "--no-user-verification");
+
+    let mut input = String::new();
+    std::io::stdin()
+        .read_to_string(&mut input)
+        .map_err(|e| SoftAuthenticatorError(format!("cannot read options: {}", e)))?;
+    let mut credentials: Vec<StoredCredential> = match fs::read_to_string(state_path) {
+        Ok(text) => serde_json::from_str(&text)
+            .map_err(|e| SoftAuthenticatorError(format!("malformed {}: {}", state_path, e)))?,
+        Err(_) => Vec::new(),
+    };
+
+    let output = match action {
+        "create" => {
+            let options: PasskeyCreationOptions = serde_json::from_str(&input)
+                .map_err(|e| SoftAuthenticatorError(format!("malformed creation options: {}", e)))?;
+            serde_json::to_string(&create(&mut credentials, &options, origin, user_verified)?)
+        }
+        "get" => {
+            let options: PasskeyRequestOptions = serde_json::from_str(&input)
+                .map_err(|e| SoftAuthenticatorError(format!("malformed request options: {}", e)))?;
+            serde_json::to_string(&get(&mut credentials, &options, origin, user_verified)?)
+        }
+        _ => return Err(SoftAuthenticatorError(USAGE.to_string())),
+    }
+    .expect("credentials serialize");
+
+    let state = serde_json::to_string_pretty(&credentials).expect("state serializes");
+    fs::write(state_path, state).map_err(|e| SoftAuthenticatorError(format!("cannot write {}: {}", state_path, e)))?;
+    println!("{}", output);
+    Ok(())
+}
+
+pub(crate) fn create(
+    credentials: &mut Vec<StoredCredential>,
+    options: &PasskeyCreationOptions,
+    origin: &str,
+    user_verified: bool,
+) -> Result<RegistrationCredential, SoftAuthenticatorError> {
+    if !options.pub_key_cred_params.iter().any(|p| p.alg == COSE_ES256) {
+        return Err(SoftAuthenticatorError("the relying party does not accept ES256".to_string()));
+    }
+    if let Some(existing) = credentials
+        .iter()
+        .find(|c| options.exclude_credentials.iter().any(|d| d.id == c.credential_id))
+    {
+        return Err(SoftAuthenticatorError(format!("credential {} is already registered", existing.credential_id)));
+    }
+
+    let key = SigningKey::random(&mut rand::rngs::OsRng);
+    let point = key.verifying_key().to_encoded_point(false);
+    let mut credential_id = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut credential_id);
+
+    // COSE_Key: kty EC2, alg ES256, crv P-256, x, y
+    let cose_key = Value::Map(vec![
+        (Value::Integer(1.into()), Value::Integer(2.into())),
+        (Value::Integer(3.into()), Value::Integer(COSE_ES256.into())),
+        (Value::Integer((-1).into()), Value::Integer(1.into())),
+        (Value::Integer((-2).into()), Value::Bytes(point.x().expect("uncompressed point").to_vec())),
+        (Value::Integer((-3).into()), Value::Bytes(point.y().expect("uncompressed point").to_vec())),
+    ]);
+
+    let mut auth_data = authenticator_data(&options.rp.id, user_verified, 0x40, 0);
+    auth_data.extend_from_slice(&[0u8; 16]); // AAGUID
+    auth_data.extend_from_slice(&(credential_id.len() as u16).to_be_bytes());
+    auth_data.extend_from_slice(&credential_id);
+    ciborium::ser::into_writer(&cose_key, &mut auth_data).expect("writing to a Vec cannot fail");
+
+    let attestation = Value::Map(vec![
+        (Value::Text("fmt".to_string()), Value::Text("none".to_string())),
+        (Value::Text("attStmt".to_string()), Value::Map(Vec::new())),
+        (Value::Text("authData".to_string()), Value::Bytes(auth_data)),
+    ]);
+    let mut attestation_object = Vec::new();
+    ciborium::ser::into_writer(&attestation, &mut attestation_object).expect("writing to a Vec cannot fail");
+
+    let credential_id = URL_SAFE_NO_PAD.encode(credential_id);
+    credentials.push(StoredCredential {
+        credential_id: credential_id.clone(),
+        rp_id: options.rp.id.clone(),
+        user_handle: options.user.id.clone(),
+        private_key: URL_SAFE_NO_PAD.encode(key.to_bytes()),
+        sign_count: 0,
+    });
+
+    Ok(RegistrationCredential {
+        id: credential_id.clone(),
+        raw_id: credential_id,
+        kind: "public-key".to_string(),
+        response: AttestationResponse {
+            client_data_json: client_data("webauthn.create", &options.challenge, origin),
+            attestation_object: URL_SAFE_NO_PAD.encode(attestation_object),
+        },
+    })
+}
+
+pub(crate) fn get(
+    credentials: &mut [StoredCredential],
+    options: &PasskeyRequestOptions,
+    origin: &str,
+    user_verified: bool,
+) -> Result<AuthenticationCredential, SoftAuthenticatorError> {
+    // An empty allow list asks for any passkey the authenticator holds for the site
+    let stored = credentials
+        .iter_mut()
+        .rev()
+        .find(|c| {
+            c.rp_id == options.rp_id
+                && (options.allow_credentials.is_empty()
+                    || options.allow_credentials.iter().any(|d| d.id == c.credential_id))
+        })
+        .ok_or_else(|| SoftAuthenticatorError(format!("no passkey for {}", options.rp_id)))?;
+
+    let key_bytes = URL_SAFE_NO_PAD
+        .decode(&stored.private_key)
+        .map_err(|_| SoftAuthenticatorError("malformed private key".to_string()))?;
+    let key = SigningKey::from_slice(&key_bytes).map_err(|_| SoftAuthenticatorError("malformed private key".to_string()))?;
+
+    stored.sign_count += 1;
+    let auth_data = authenticator_data(&stored.rp_id, user_verified, 0, stored.sign_count);
+    let client_data_json = client_data("webauthn.get", &options.challenge, origin);
+
+    let mut signed = auth_data.clone();
+    signed.extend_from_slice(&Sha256::digest(URL_SAFE_NO_PAD.decode(&client_data_json).expect("just encoded")));
+    let signature: Signature = key.sign(&signed);
+
+    Ok(AuthenticationCredential {
+        id: stored.credential_id.clone(),
+        raw_id: stored.credential_id.clone(),
+        kind: "public-key".to_string(),
+        response: AssertionResponse {
+            client_data_json,
+            authenticator_data: URL_SAFE_NO_PAD.encode(auth_data),
+            signature: URL_SAFE_NO_PAD.encode(signature.to_der().as_bytes()),
+            user_handle: Some(stored.user_handle.clone()),
+        },
+    })
+}
+
+/// rpIdHash, flags (user present, plus `extra_flags`) and the signature counter.
+fn authenticator_data(rp_id: &str, user_verified: bool, extra_flags: u8, sign_count: u32) -> Vec<u8> {
+    let mut data = Sha256::digest(rp_id.as_bytes()).to_vec();
+    data.push(0x01 | if user_verified { 0x04 } else { 0 } | extra_flags);
+    data.extend_from_slice(&sign_count.to_be_bytes());
+    data
+}
+
+fn client_data(kind: &str, challenge: &str, origin: &str) -> String {
+    let json = json!({ "type": kind, "challenge": challenge, "origin": origin, "crossOrigin": false });
+    URL_SAFE_NO_PAD.encode(json.to_string())
+}
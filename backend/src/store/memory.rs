@@ -0,0 +1,609 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use uuid::Uuid;
+
+use super::{
+    ActionTokenStore, MfaStore, OAuthStore, PasskeyStore, RefreshTokenStore, RevocationStore, SigningKeyStore,
+    StoreError, UserStore,
+};
+use crate::models::{
+    ActionToken, AuthorizationCode, DeviceAuthorization, NewUser, OAuthClient, Passkey, PasskeyChallenge, RefreshToken,
+    SigningKeyRecord, TokenPurpose, TotpCredential, User,
+};
+
+/// Keeps everything in process memory behind mutexes. Everything is lost on
+/// restart, which is fine for local development and demos.
+#[derive(Debug, Default)]
+pub struct InMemoryStore {
+    users: Mutex<Vec<User>>,
+    refresh_tokens: Mutex<HashMap<String, RefreshToken>>,
+    /// Revoked `jti` -> when the token would have expired.
+    revoked_tokens: Mutex<HashMap<String, DateTime<Utc>>>,
+    revoked_subjects: Mutex<HashMap<String, SubjectRevocation>>,
+    signing_keys: Mutex<Vec<SigningKeyRecord>>,
+    action_tokens: Mutex<HashMap<String, ActionToken>>,
+    totp: Mutex<HashMap<Uuid, TotpCredential>>,
+    recovery_codes: Mutex<HashMap<Uuid, RecoveryCodes>>,
+    passkeys: Mutex<Vec<Passkey>>,
+    passkey_challenges: Mutex<HashMap<String, PasskeyChallenge>>,
+    oauth_clients: Mutex<Vec<OAuthClient>>,
+    authorization_codes: Mutex<HashMap<String, AuthorizationCode>>,
+    device_authorizations: Mutex<HashMap<String, DeviceAuthorization>>,
+}
+
+/// Recovery code hash -> when it was used.
+type RecoveryCodes = HashMap<String, Option<DateTime<Utc>>>;
+
+#[derive(Debug)]
+struct SubjectRevocation {
+    revoked_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    spared_jti: Option<String>,
+}
+
+impl InMemoryStore {
+    pub fn new(users: Vec<User>) -> Self {
+        Self {
+            users: Mutex::new(users),
+            ..Default::default()
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> Result<MutexGuard<'_, T>, StoreError> {
+    mutex
+        .lock()
+        .map_err(|_| StoreError::Backend("in-memory store lock poisoned".to_string()))
+}
+
+#[async_trait]
+impl UserStore for InMemoryStore {
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<User>, StoreError> {
+        Ok(lock(&self.users)?.iter().find(|u| u.id == id).cloned())
+    }
+
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, StoreError> {
+        Ok(lock(&self.users)?.iter().find(|u| u.email == email).cloned())
+    }
+
+    async fn insert(&self, user: NewUser) -> Result<User, StoreError> {
+        let mut users = lock(&self.users)?;
+
+        if users.iter().any(|u| u.email == user.email) {
+            return Err(StoreError::Conflict);
+        }
+
+        let user = user.into_user();
+        users.push(user.clone());
+
+        Ok(user)
+    }
+
+    async fn update(&self, user: &User) -> Result<(), StoreError> {
+        let mut users = lock(&self.users)?;
+
+        if users.iter().any(|u| u.id != user.id && u.email == user.email) {
+            return Err(StoreError::Conflict);
+        }
+
+        match users.iter_mut().find(|u| u.id == user.id) {
+            Some(existing) => {
+                *existing = user.clone();
+                Ok(())
+            }
+            None => Err(StoreError::NotFound),
+        }
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<(), StoreError> {
+        let mut users = lock(&self.users)?;
+        let before = users.len();
+        users.retain(|u| u.id != id);
+
+        if users.len() == before {
+            return Err(StoreError::NotFound);
+        }
+        // Mirror the ON DELETE CASCADE of the SQL backends
+        lock(&self.refresh_tokens)?.retain(|_, t| t.user_id != id);
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<Vec<User>, StoreError> {
+        Ok(lock(&self.users)?.clone())
+    }
+}
+
+#[async_trait]
+impl RefreshTokenStore for InMemoryStore {
+    async fn insert(&self, token: RefreshToken) -> Result<(), StoreError> {
+        let mut tokens = lock(&self.refresh_tokens)?;
+
+        if tokens.contains_key(&token.token_hash) {
+            return Err(StoreError::Conflict);
+        }
+        tokens.insert(token.token_hash.clone(), token);
+        Ok(())
+    }
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<RefreshToken>, StoreError> {
+        Ok(lock(&self.refresh_tokens)?.get(token_hash).cloned())
+    }
+
+    async fn mark_used(&self, token_hash: &str, at: DateTime<Utc>) -> Result<bool, StoreError> {
+        match lock(&self.refresh_tokens)?.get_mut(token_hash) {
+            Some(token) if token.used_at.is_none() => {
+                token.used_at = Some(at);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn revoke_family(&self, family_id: Uuid) -> Result<(), StoreError> {
+        lock(&self.refresh_tokens)?
+            .values_mut()
+            .filter(|t| t.family_id == family_id)
+            .for_each(|t| t.revoked = true);
+        Ok(())
+    }
+
+    async fn revoke_user(&self, user_id: Uuid) -> Result<(), StoreError> {
+        lock(&self.refresh_tokens)?
+            .values_mut()
+            .filter(|t| t.user_id == user_id)
+            .for_each(|t| t.revoked = true);
+        Ok(())
+    }
+
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, StoreError> {
+        let mut tokens = lock(&self.refresh_tokens)?;
+        let before = tokens.len();
+        tokens.retain(|_, t| t.expires_at > now);
+        Ok((before - tokens.len()) as u64)
+    }
+}
+
+#[async_trait]
+impl RevocationStore for InMemoryStore {
+    async fn revoke_token(&self, jti: &str, expires_at: DateTime<Utc>) -> Result<(), StoreError> {
+        lock(&self.revoked_tokens)?.insert(jti.to_string(), expires_at);
+        Ok(())
+    }
+
+    async fn revoke_subject(
+        &self,
+        subject: &str,
+        revoked_at: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+        spared_jti: Option<&str>,
+    ) -> Result<(), StoreError> {
+        lock(&self.revoked_subjects)?.insert(
+            subject.to_string(),
+            SubjectRevocation {
+                revoked_at,
+                expires_at,
+                spared_jti: spared_jti.map(str::to_string),
+            },
+        );
+        Ok(())
+    }
+
+    async fn is_revoked(&self, jti: &str, subject: &str, issued_at: i64) -> Result<bool, StoreError> {
+        if lock(&self.revoked_tokens)?.contains_key(jti) {
+            return Ok(true);
+        }
+        Ok(lock(&self.revoked_subjects)?
+            .get(subject)
+            .is_some_and(|r| issued_at <= r.revoked_at.timestamp() && r.spared_jti.as_deref() != Some(jti)))
+    }
+
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, StoreError> {
+        let mut tokens = lock(&self.revoked_tokens)?;
+        let mut subjects = lock(&self.revoked_subjects)?;
+        let before = tokens.len() + subjects.len();
+
+        tokens.retain(|_, expires_at| *expires_at > now);
+        subjects.retain(|_, r| r.expires_at > now);
+        Ok((before - tokens.len() - subjects.len()) as u64)
+    }
+}
+
+#[async_trait]
+impl SigningKeyStore for InMemoryStore {
+    async fn list(&self) -> Result<Vec<SigningKeyRecord>, StoreError> {
+        Ok(lock(&self.signing_keys)?.clone())
+    }
+
+    async fn insert(&self, key: SigningKeyRecord) -> Result<(), StoreError> {
+        let mut keys = lock(&self.signing_keys)?;
+
+        if keys.iter().any(|k| k.kid == key.kid) {
+            return Err(StoreError::Conflict);
+        }
+        keys.push(key);
+        Ok(())
+    }
+
+    async fn replace_private_key(&self, kid: &str, private_key: &str) -> Result<(), StoreError> {
+        match lock(&self.signing_keys)?.iter_mut().find(|k| k.kid == kid) {
+            Some(key) => {
+                key.private_key = private_key.to_string();
+                Ok(())
+            }
+            None => Err(StoreError::NotFound),
+        }
+    }
+
+    async fn promote(&self, kid: &str, at: DateTime<Utc>) -> Result<(), StoreError> {
+        match lock(&self.signing_keys)?
+            .iter_mut()
+            .find(|k| k.kid == kid && k.retired_at.is_none())
+        {
+            Some(key) => {
+                key.activated_at = Some(at);
+                Ok(())
+            }
+            None => Err(StoreError::NotFound),
+        }
+    }
+
+    async fn retire(&self, kid: &str, at: DateTime<Utc>, verify_until: DateTime<Utc>) -> Result<(), StoreError> {
+        match lock(&self.signing_keys)?.iter_mut().find(|k| k.kid == kid) {
+            Some(key) => {
+                key.retired_at = Some(at);
+                key.verify_until = Some(verify_until);
+                Ok(())
+            }
+            None => Err(StoreError::NotFound),
+        }
+    }
+
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, StoreError> {
+        let mut keys = lock(&self.signing_keys)?;
+        let before = keys.len();
+        keys.retain(|k| k.verify_until.is_none_or(|until| until > now));
+        Ok((before - keys.len()) as u64)
+    }
+}
+
+#[async_trait]
+impl ActionTokenStore for InMemoryStore {
+    async fn insert(&self, token: ActionToken) -> Result<(), StoreError> {
+        let mut tokens = lock(&self.action_tokens)?;
+
+        if tokens.contains_key(&token.token_hash) {
+            return Err(StoreError::Conflict);
+        }
+        tokens.insert(token.token_hash.clone(), token);
+        Ok(())
+    }
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<ActionToken>, StoreError> {
+        Ok(lock(&self.action_tokens)?.get(token_hash).cloned())
+    }
+
+    async fn mark_used(&self, token_hash: &str, at: DateTime<Utc>) -> Result<bool, StoreError> {
+        match lock(&self.action_tokens)?.get_mut(token_hash) {
+            Some(token) if token.used_at.is_none() => {
+                token.used_at = Some(at);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn last_issued(&self, user_id: Uuid, purpose: TokenPurpose) -> Result<Option<DateTime<Utc>>, StoreError> {
+        Ok(lock(&self.action_tokens)?
+            .values()
+            .filter(|t| t.user_id == user_id && t.purpose == purpose)
+            .map(|t| t.created_at)
+            .max())
+    }
+
+    async fn revoke_user(&self, user_id: Uuid, purpose: TokenPurpose) -> Result<(), StoreError> {
+        lock(&self.action_tokens)?
+            .retain(|_, t| t.user_id != user_id || t.purpose != purpose || t.used_at.is_some());
+        Ok(())
+    }
+
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, StoreError> {
+        let mut tokens = lock(&self.action_tokens)?;
+        let before = tokens.len();
+        tokens.retain(|_, t| t.expires_at > now);
+        Ok((before - tokens.len()) as u64)
+    }
+}
+
+#[async_trait]
+impl MfaStore for InMemoryStore {
+    async fn find_totp(&self, user_id: Uuid) -> Result<Option<TotpCredential>, StoreError> {
+        Ok(lock(&self.totp)?.get(&user_id).cloned())
+    }
+
+    async fn begin_totp(&self, credential: TotpCredential) -> Result<(), StoreError> {
+        let mut totp = lock(&self.totp)?;
+
+        if totp.get(&credential.user_id).is_some_and(|t| t.enabled_at.is_some()) {
+            return Err(StoreError::Conflict);
+        }
+        totp.insert(credential.user_id, credential);
+        Ok(())
+    }
+
+    async fn enable_totp(&self, user_id: Uuid, at: DateTime<Utc>) -> Result<bool, StoreError> {
+        match lock(&self.totp)?.get_mut(&user_id) {
+            Some(credential) if credential.enabled_at.is_none() => {
+                credential.enabled_at = Some(at);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn list_totp(&self) -> Result<Vec<TotpCredential>, StoreError> {
+        Ok(lock(&self.totp)?.values().cloned().collect())
+    }
+
+    async fn replace_totp_secret(&self, user_id: Uuid, current: &str, replacement: &str) -> Result<bool, StoreError> {
+        match lock(&self.totp)?.get_mut(&user_id) {
+            Some(credential) if credential.secret == current => {
+                credential.secret = replacement.to_string();
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn use_totp_step(&self, user_id: Uuid, step: i64) -> Result<bool, StoreError> {
+        match lock(&self.totp)?.get_mut(&user_id) {
+            Some(credential) if credential.last_used_step.is_none_or(|last| last < step) => {
+                credential.last_used_step = Some(step);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn disable_totp(&self, user_id: Uuid) -> Result<(), StoreError> {
+        lock(&self.totp)?.remove(&user_id);
+        lock(&self.recovery_codes)?.remove(&user_id);
+        Ok(())
+    }
+
+    async fn replace_recovery_codes(
+        &self,
+        user_id: Uuid,
+        code_hashes: &[String],
+        _at: DateTime<Utc>,
+    ) -> Result<(), StoreError> {
+        let codes = code_hashes.iter().map(|hash| (hash.clone(), None)).collect();
+        lock(&self.recovery_codes)?.insert(user_id, codes);
+        Ok(())
+    }
+
+    async fn use_recovery_code(&self, user_id: Uuid, code_hash: &str, at: DateTime<Utc>) -> Result<bool, StoreError> {
+        let mut codes = lock(&self.recovery_codes)?;
+        match codes.get_mut(&user_id).and_then(|codes| codes.get_mut(code_hash)) {
+            Some(used_at) if used_at.is_none() => {
+                *used_at = Some(at);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn remaining_recovery_codes(&self, user_id: Uuid) -> Result<u64, StoreError> {
+        Ok(lock(&self.recovery_codes)?
+            .get(&user_id)
+            .map_or(0, |codes| codes.values().filter(|used_at| used_at.is_none()).count() as u64))
+    }
+}
+
+#[async_trait]
+impl PasskeyStore for InMemoryStore {
+    async fn insert(&self, passkey: Passkey) -> Result<(), StoreError> {
+        let mut passkeys = lock(&self.passkeys)?;
+
+        if passkeys.iter().any(|p| p.credential_id == passkey.credential_id) {
+            return Err(StoreError::Conflict);
+        }
+        passkeys.push(passkey);
+        Ok(())
+    }
+
+    async fn find_by_credential_id(&self, credential_id: &str) -> Result<Option<Passkey>, StoreError> {
+        Ok(lock(&self.passkeys)?.iter().find(|p| p.credential_id == credential_id).cloned())
+    }
+
+    async fn list_for_user(&self, user_id: Uuid) -> Result<Vec<Passkey>, StoreError> {
+        Ok(lock(&self.passkeys)?.iter().filter(|p| p.user_id == user_id).cloned().collect())
+    }
+
+    async fn record_use(
+        &self,
+        id: Uuid,
+        previous_count: u32,
+        sign_count: u32,
+        at: DateTime<Utc>,
+    ) -> Result<bool, StoreError> {
+        match lock(&self.passkeys)?.iter_mut().find(|p| p.id == id) {
+            Some(passkey) if passkey.sign_count == previous_count => {
+                passkey.sign_count = sign_count;
+                passkey.last_used_at = Some(at);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn delete(&self, user_id: Uuid, id: Uuid) -> Result<(), StoreError> {
+        let mut passkeys = lock(&self.passkeys)?;
+        let before = passkeys.len();
+        passkeys.retain(|p| p.id != id || p.user_id != user_id);
+
+        if passkeys.len() == before {
+            return Err(StoreError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn insert_challenge(&self, challenge: PasskeyChallenge) -> Result<(), StoreError> {
+        lock(&self.passkey_challenges)?.insert(challenge.challenge_hash.clone(), challenge);
+        Ok(())
+    }
+
+    async fn take_challenge(&self, challenge_hash: &str) -> Result<Option<PasskeyChallenge>, StoreError> {
+        Ok(lock(&self.passkey_challenges)?.remove(challenge_hash))
+    }
+
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, StoreError> {
+        let mut challenges = lock(&self.passkey_challenges)?;
+        let before = challenges.len();
+        challenges.retain(|_, c| c.expires_at > now);
+        Ok((before - challenges.len()) as u64)
+    }
+}
+
+#[async_trait]
+impl OAuthStore for InMemoryStore {
+    async fn insert_client(&self, client: OAuthClient) -> Result<(), StoreError> {
+        let mut clients = lock(&self.oauth_clients)?;
+
+        if clients.iter().any(|c| c.client_id == client.client_id) {
+            return Err(StoreError::Conflict);
+        }
+        clients.push(client);
+        Ok(())
+    }
+
+    async fn find_client(&self, client_id: &str) -> Result<Option<OAuthClient>, StoreError> {
+        Ok(lock(&self.oauth_clients)?.iter().find(|c| c.client_id == client_id).cloned())
+    }
+
+    async fn list_clients(&self) -> Result<Vec<OAuthClient>, StoreError> {
+        Ok(lock(&self.oauth_clients)?.clone())
+    }
+
+    async fn set_client_secret(&self, client_id: &str, secret_hash: &str) -> Result<(), StoreError> {
+        let mut clients = lock(&self.oauth_clients)?;
+        let client = clients
+            .iter_mut()
+            .find(|c| c.client_id == client_id)
+            .ok_or(StoreError::NotFound)?;
+        client.secret_hash = Some(secret_hash.to_string());
+        Ok(())
+    }
+
+    async fn delete_client(&self, client_id: &str) -> Result<(), StoreError> {
+        let mut clients = lock(&self.oauth_clients)?;
+        let before = clients.len();
+        clients.retain(|c| c.client_id != client_id);
+
+        if clients.len() == before {
+            return Err(StoreError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn insert_code(&self, code: AuthorizationCode) -> Result<(), StoreError> {
+        let mut codes = lock(&self.authorization_codes)?;
+
+        if codes.contains_key(&code.code_hash) {
+            return Err(StoreError::Conflict);
+        }
+        codes.insert(code.code_hash.clone(), code);
+        Ok(())
+    }
+
+    async fn find_code(&self, code_hash: &str) -> Result<Option<AuthorizationCode>, StoreError> {
+        Ok(lock(&self.authorization_codes)?.get(code_hash).cloned())
+    }
+
+    async fn mark_code_used(&self, code_hash: &str, at: DateTime<Utc>) -> Result<bool, StoreError> {
+        match lock(&self.authorization_codes)?.get_mut(code_hash) {
+            Some(code) if code.used_at.is_none() => {
+                code.used_at = Some(at);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn insert_device_authorization(&self, authorization: DeviceAuthorization) -> Result<(), StoreError> {
+        let mut authorizations = lock(&self.device_authorizations)?;
+
+        if authorizations.contains_key(&authorization.device_code_hash)
+            || authorizations.values().any(|a| a.user_code_hash == authorization.user_code_hash)
+        {
+            return Err(StoreError::Conflict);
+        }
+        authorizations.insert(authorization.device_code_hash.clone(), authorization);
+        Ok(())
+    }
+
+    async fn find_device_authorization(&self, device_code_hash: &str) -> Result<Option<DeviceAuthorization>, StoreError> {
+        Ok(lock(&self.device_authorizations)?.get(device_code_hash).cloned())
+    }
+
+    async fn find_device_authorization_by_user_code(
+        &self,
+        user_code_hash: &str,
+    ) -> Result<Option<DeviceAuthorization>, StoreError> {
+        Ok(lock(&self.device_authorizations)?
+            .values()
+            .find(|a| a.user_code_hash == user_code_hash)
+            .cloned())
+    }
+
+    async fn decide_device_authorization(
+        &self,
+        user_code_hash: &str,
+        user_id: Uuid,
+        approved: bool,
+        at: DateTime<Utc>,
+    ) -> Result<bool, StoreError> {
+        let mut authorizations = lock(&self.device_authorizations)?;
+        match authorizations.values_mut().find(|a| a.user_code_hash == user_code_hash) {
+            Some(authorization) if authorization.approved.is_none() && authorization.expires_at > at => {
+                authorization.user_id = Some(user_id);
+                authorization.approved = Some(approved);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn record_device_poll(
+        &self,
+        device_code_hash: &str,
+        at: DateTime<Utc>,
+        interval_secs: u32,
+    ) -> Result<(), StoreError> {
+        if let Some(authorization) = lock(&self.device_authorizations)?.get_mut(device_code_hash) {
+            authorization.last_polled_at = Some(at);
+            authorization.interval_secs = interval_secs;
+        }
+        Ok(())
+    }
+
+    async fn mark_device_authorization_used(&self, device_code_hash: &str, at: DateTime<Utc>) -> Result<bool, StoreError> {
+        match lock(&self.device_authorizations)?.get_mut(device_code_hash) {
+            Some(authorization) if authorization.used_at.is_none() => {
+                authorization.used_at = Some(at);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, StoreError> {
+        let mut codes = lock(&self.authorization_codes)?;
+        let mut authorizations = lock(&self.device_authorizations)?;
+        let before = codes.len() + authorizations.len();
+        codes.retain(|_, c| c.expires_at > now);
+        authorizations.retain(|_, a| a.expires_at > now);
+        Ok((before - codes.len() - authorizations.len()) as u64)
+    }
+}
@@ -0,0 +1,456 @@
+pub mod memory;
+pub mod postgres;
+pub mod sqlite;
+
+pub use memory::InMemoryStore;
+pub use postgres::PostgresStore;
+pub use sqlite::SqliteStore;
+
+use async_trait::async_trait;
+use axum::{http::StatusCode, response::IntoResponse, response::Response, Json};
+use serde_json::{json, Value};
+use chrono::{DateTime, Utc};
+use std::{fmt, sync::Arc};
+use uuid::Uuid;
+
+use crate::models::{
+    ActionToken, AuthorizationCode, DeviceAuthorization, NewUser, OAuthClient, Passkey, PasskeyChallenge, RefreshToken,
+    SigningKeyRecord, TokenPurpose, TotpCredential, User,
+};
+use crate::utils::StorageBackend;
+
+/// Errors a store implementation can report back to the handlers.
+#[derive(Debug)]
+pub enum StoreError {
+    /// A record with the same unique key (e.g. a user's email) already exists.
+    Conflict,
+    /// No record matched the given id.
+    NotFound,
+    /// The underlying storage failed (connection, query, poisoned lock, ...).
+    Backend(String),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Conflict => write!(f, "record already exists"),
+            StoreError::NotFound => write!(f, "record not found"),
+            StoreError::Backend(msg) => write!(f, "storage error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+/// Generic responses for handlers that pass store errors straight through.
+/// Handlers that can say which record clashed or is missing match on the
+/// error themselves.
+impl From<StoreError> for (StatusCode, Json<Value>) {
+    fn from(err: StoreError) -> Self {
+        match err {
+            StoreError::Conflict => (StatusCode::CONFLICT, Json(json!({ "error": "Resource already exists" }))),
+            StoreError::NotFound => (StatusCode::NOT_FOUND, Json(json!({ "error": "Resource not found" }))),
+            StoreError::Backend(msg) => {
+                tracing::error!("Store failure: {}", msg);
+                (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "Internal server error" })))
+            }
+        }
+    }
+}
+
+impl IntoResponse for StoreError {
+    fn into_response(self) -> Response {
+        <(StatusCode, Json<Value>)>::from(self).into_response()
+    }
+}
+
+/// Persistence for user accounts. Handlers only ever talk to this trait,
+/// so the backing storage can be swapped without touching route logic.
+#[async_trait]
+pub trait UserStore: Send + Sync + fmt::Debug {
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<User>, StoreError>;
+
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, StoreError>;
+
+    /// Inserts a new user and returns it with its assigned id.
+    /// Fails with `StoreError::Conflict` if the email is already taken.
+    async fn insert(&self, user: NewUser) -> Result<User, StoreError>;
+
+    /// Replaces the stored user with the same id.
+    async fn update(&self, user: &User) -> Result<(), StoreError>;
+
+    async fn delete(&self, id: Uuid) -> Result<(), StoreError>;
+
+    async fn list(&self) -> Result<Vec<User>, StoreError>;
+}
+
+/// Server-side state for refresh tokens, keyed by the hash of the opaque token.
+#[async_trait]
+pub trait RefreshTokenStore: Send + Sync + fmt::Debug {
+    async fn insert(&self, token: RefreshToken) -> Result<(), StoreError>;
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<RefreshToken>, StoreError>;
+
+    /// Atomically marks the token as used. Returns `false` if it had already
+    /// been used (it is being replayed) or does not exist.
+    async fn mark_used(&self, token_hash: &str, at: DateTime<Utc>) -> Result<bool, StoreError>;
+
+    /// Revokes every token descended from the same login.
+    async fn revoke_family(&self, family_id: Uuid) -> Result<(), StoreError>;
+
+    /// Revokes every refresh token held by the user.
+    async fn revoke_user(&self, user_id: Uuid) -> Result<(), StoreError>;
+
+    /// Drops tokens that expired before `now`; returns how many were removed.
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, StoreError>;
+}
+
+/// Access tokens revoked before their `exp`. Entries only need to outlive the
+/// tokens they cover, so each one carries an `expires_at` after which it is
+/// purged.
+#[async_trait]
+pub trait RevocationStore: Send + Sync + fmt::Debug {
+    /// Revokes a single access token by its `jti`.
+    async fn revoke_token(&self, jti: &str, expires_at: DateTime<Utc>) -> Result<(), StoreError>;
+
+    /// Revokes every access token of `subject` issued at or before `revoked_at`,
+    /// except the one with `spared_jti`. `iat` only has whole seconds, so a
+    /// token minted later in the same second is revoked too; a session that
+    /// must survive, like the one replacing the revoked ones, is spared by jti.
+    /// A later revocation of the subject replaces this one, spared jti included.
+    async fn revoke_subject(
+        &self,
+        subject: &str,
+        revoked_at: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+        spared_jti: Option<&str>,
+    ) -> Result<(), StoreError>;
+
+    /// Whether a token with this `jti`, `subject` and issue time (`iat`, in
+    /// seconds) has been revoked.
+    async fn is_revoked(&self, jti: &str, subject: &str, issued_at: i64) -> Result<bool, StoreError>;
+
+    /// Drops entries whose tokens have all expired; returns how many were removed.
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, StoreError>;
+}
+
+/// The keyset that signs access tokens, shared by every instance.
+#[async_trait]
+pub trait SigningKeyStore: Send + Sync + fmt::Debug {
+    async fn list(&self) -> Result<Vec<SigningKeyRecord>, StoreError>;
+
+    /// Fails with `StoreError::Conflict` if the `kid` is already in use.
+    async fn insert(&self, key: SigningKeyRecord) -> Result<(), StoreError>;
+
+    /// Rewrites the stored key material, e.g. to encrypt a key stored in the clear.
+    /// Fails with `StoreError::NotFound` if the key does not exist.
+    async fn replace_private_key(&self, kid: &str, private_key: &str) -> Result<(), StoreError>;
+
+    /// Makes the key the newest promoted one, so it signs new tokens.
+    /// Fails with `StoreError::NotFound` if it does not exist or was retired.
+    async fn promote(&self, kid: &str, at: DateTime<Utc>) -> Result<(), StoreError>;
+
+    /// Stops the key from signing; it still verifies tokens until `verify_until`.
+    async fn retire(&self, kid: &str, at: DateTime<Utc>, verify_until: DateTime<Utc>) -> Result<(), StoreError>;
+
+    /// Drops retired keys that no longer verify anything; returns how many were removed.
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, StoreError>;
+}
+
+/// Single-use tokens issued to users, such as mailed links, keyed by the hash
+/// of the opaque token.
+#[async_trait]
+pub trait ActionTokenStore: Send + Sync + fmt::Debug {
+    async fn insert(&self, token: ActionToken) -> Result<(), StoreError>;
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<ActionToken>, StoreError>;
+
+    /// Atomically marks the token as used. Returns `false` if it had already
+    /// been used or does not exist.
+    async fn mark_used(&self, token_hash: &str, at: DateTime<Utc>) -> Result<bool, StoreError>;
+
+    /// When the user was last sent a token for `purpose`, if ever.
+    async fn last_issued(&self, user_id: Uuid, purpose: TokenPurpose) -> Result<Option<DateTime<Utc>>, StoreError>;
+
+    /// Drops the user's unused tokens for `purpose`, e.g. older reset links once one was used.
+    async fn revoke_user(&self, user_id: Uuid, purpose: TokenPurpose) -> Result<(), StoreError>;
+
+    /// Drops tokens that expired before `now`; returns how many were removed.
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, StoreError>;
+}
+
+/// Second factors: each user's TOTP secret and the hashes of their recovery codes.
+#[async_trait]
+pub trait MfaStore: Send + Sync + fmt::Debug {
+    async fn find_totp(&self, user_id: Uuid) -> Result<Option<TotpCredential>, StoreError>;
+
+    /// Stores a secret awaiting confirmation, replacing an earlier unconfirmed one.
+    /// Fails with `StoreError::Conflict` if the user already has TOTP enabled.
+    async fn begin_totp(&self, credential: TotpCredential) -> Result<(), StoreError>;
+
+    /// Enables the pending secret. Returns `false` if there is none.
+    async fn enable_totp(&self, user_id: Uuid, at: DateTime<Utc>) -> Result<bool, StoreError>;
+
+    /// Atomically records `step` as the last accepted time step. Returns
+    /// `false` if a code from this step or a later one was already accepted.
+    async fn use_totp_step(&self, user_id: Uuid, step: i64) -> Result<bool, StoreError>;
+
+    /// Every stored secret, enabled or pending, for maintenance at startup.
+    async fn list_totp(&self) -> Result<Vec<TotpCredential>, StoreError>;
+
+    /// Replaces the stored form of a secret, e.g. to encrypt it. Returns
+    /// `false` if the secret changed since it was read.
+    async fn replace_totp_secret(&self, user_id: Uuid, current: &str, replacement: &str) -> Result<bool, StoreError>;
+
+    /// Removes the secret together with every recovery code.
+    async fn disable_totp(&self, user_id: Uuid) -> Result<(), StoreError>;
+
+    /// Replaces all of the user's recovery codes with new ones.
+    async fn replace_recovery_codes(
+        &self,
+        user_id: Uuid,
+        code_hashes: &[String],
+        at: DateTime<Utc>,
+    ) -> Result<(), StoreError>;
+
+    /// Atomically marks a recovery code as used. Returns `false` if it had
+    /// already been used or does not exist.
+    async fn use_recovery_code(&self, user_id: Uuid, code_hash: &str, at: DateTime<Utc>) -> Result<bool, StoreError>;
+
+    async fn remaining_recovery_codes(&self, user_id: Uuid) -> Result<u64, StoreError>;
+}
+
+/// WebAuthn credentials and the challenges of ceremonies in progress.
+#[async_trait]
+pub trait PasskeyStore: Send + Sync + fmt::Debug {
+    /// Fails with `StoreError::Conflict` if the credential id is already registered.
+    async fn insert(&self, passkey: Passkey) -> Result<(), StoreError>;
+
+    async fn find_by_credential_id(&self, credential_id: &str) -> Result<Option<Passkey>, StoreError>;
+
+    async fn list_for_user(&self, user_id: Uuid) -> Result<Vec<Passkey>, StoreError>;
+
+    /// Atomically moves the signature counter from `previous_count` to
+    /// `sign_count`. Returns `false` if another login updated it first.
+    async fn record_use(
+        &self,
+        id: Uuid,
+        previous_count: u32,
+        sign_count: u32,
+        at: DateTime<Utc>,
+    ) -> Result<bool, StoreError>;
+
+    /// Fails with `StoreError::NotFound` unless the user owns the passkey.
+    async fn delete(&self, user_id: Uuid, id: Uuid) -> Result<(), StoreError>;
+
+    async fn insert_challenge(&self, challenge: PasskeyChallenge) -> Result<(), StoreError>;
+
+    /// Removes and returns the challenge, so each one is answered at most once.
+    async fn take_challenge(&self, challenge_hash: &str) -> Result<Option<PasskeyChallenge>, StoreError>;
+
+    /// Drops challenges that expired before `now`; returns how many were removed.
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, StoreError>;
+}
+
+/// State of the OAuth authorization server: the client registry, and
+/// authorization codes keyed by the hash of the code handed to the client.
+#[async_trait]
+pub trait OAuthStore: Send + Sync + fmt::Debug {
+    /// Fails with `StoreError::Conflict` if the client id is taken.
+    async fn insert_client(&self, client: OAuthClient) -> Result<(), StoreError>;
+
+    async fn find_client(&self, client_id: &str) -> Result<Option<OAuthClient>, StoreError>;
+
+    async fn list_clients(&self) -> Result<Vec<OAuthClient>, StoreError>;
+
+    /// Replaces the client's secret. Fails with `StoreError::NotFound` for unknown clients.
+    async fn set_client_secret(&self, client_id: &str, secret_hash: &str) -> Result<(), StoreError>;
+
+    /// Fails with `StoreError::NotFound` for unknown clients.
+    async fn delete_client(&self, client_id: &str) -> Result<(), StoreError>;
+
+    async fn insert_code(&self, code: AuthorizationCode) -> Result<(), StoreError>;
+
+    async fn find_code(&self, code_hash: &str) -> Result<Option<AuthorizationCode>, StoreError>;
+
+    /// Atomically marks the code as redeemed. Returns `false` if it had
+    /// already been redeemed or does not exist.
+    async fn mark_code_used(&self, code_hash: &str, at: DateTime<Utc>) -> Result<bool, StoreError>;
+
+    /// Fails with `StoreError::Conflict` if the user code is taken.
+    async fn insert_device_authorization(&self, authorization: DeviceAuthorization) -> Result<(), StoreError>;
+
+    async fn find_device_authorization(&self, device_code_hash: &str) -> Result<Option<DeviceAuthorization>, StoreError>;
+
+    async fn find_device_authorization_by_user_code(
+        &self,
+        user_code_hash: &str,
+    ) -> Result<Option<DeviceAuthorization>, StoreError>;
+
+    /// Atomically records the user's answer. Returns `false` if the request
+    /// was already answered, has expired or does not exist.
+    async fn decide_device_authorization(
+        &self,
+        user_code_hash: &str,
+        user_id: Uuid,
+        approved: bool,
+        at: DateTime<Utc>,
+    ) -> Result<bool, StoreError>;
+
+    /// Remembers when the device last polled and the interval it must keep.
+    async fn record_device_poll(
+        &self,
+        device_code_hash: &str,
+        at: DateTime<Utc>,
+        interval_secs: u32,
+    ) -> Result<(), StoreError>;
+
+    /// Atomically marks an approved request as redeemed. Returns `false` if
+    /// it had already been redeemed or does not exist.
+    async fn mark_device_authorization_used(&self, device_code_hash: &str, at: DateTime<Utc>) -> Result<bool, StoreError>;
+
+    /// Drops codes and device authorizations that expired before `now`;
+    /// returns how many were removed.
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, StoreError>;
+}
+
+/// Every store the handlers need, backed by the same storage.
+#[derive(Debug, Clone)]
+pub struct Stores {
+    pub users: Arc<dyn UserStore>,
+    pub refresh_tokens: Arc<dyn RefreshTokenStore>,
+    pub revocations: Arc<dyn RevocationStore>,
+    pub signing_keys: Arc<dyn SigningKeyStore>,
+    pub action_tokens: Arc<dyn ActionTokenStore>,
+    pub mfa: Arc<dyn MfaStore>,
+    pub passkeys: Arc<dyn PasskeyStore>,
+    pub oauth: Arc<dyn OAuthStore>,
+}
+
+impl Stores {
+    fn from_backend<S>(store: S) -> Self
+    where
+        S: UserStore
+            + RefreshTokenStore
+            + RevocationStore
+            + SigningKeyStore
+            + ActionTokenStore
+            + MfaStore
+            + PasskeyStore
+            + OAuthStore
+            + 'static,
+    {
+        let store = Arc::new(store);
+        Stores {
+            users: store.clone(),
+            refresh_tokens: store.clone(),
+            revocations: store.clone(),
+            signing_keys: store.clone(),
+            action_tokens: store.clone(),
+            mfa: store.clone(),
+            passkeys: store.clone(),
+            oauth: store,
+        }
+    }
+}
+
+/// Builds the stores selected in the configuration.
+pub async fn connect(backend: &StorageBackend) -> Result<Stores, StoreError> {
+    match backend {
+        StorageBackend::Memory => Ok(Stores::from_backend(InMemoryStore::default())),
+        StorageBackend::Sqlite { path } => Ok(Stores::from_backend(SqliteStore::connect(path).await?)),
+        StorageBackend::Postgres { database_url, max_connections } => Ok(Stores::from_backend(
+            PostgresStore::connect(database_url, *max_connections).await?,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Role;
+    use chrono::{Duration, TimeZone};
+
+    /// A fresh store of every kind, Postgres only when `DATABASE_URL` is set.
+    async fn backends() -> Vec<Stores> {
+        let mut backends = vec![
+            Stores::from_backend(InMemoryStore::default()),
+            Stores::from_backend(SqliteStore::connect("sqlite::memory:").await.unwrap()),
+        ];
+        if let Some(url) = crate::test_support::postgres_url().await {
+            backends.push(Stores::from_backend(PostgresStore::connect(&url, 2).await.unwrap()));
+        }
+        backends
+    }
+
+    fn new_user(email: &str) -> NewUser {
+        NewUser {
+            email: email.to_string(),
+            first_name: "Ada".to_string(),
+            last_name: "Lovelace".to_string(),
+            password: "not-a-real-hash".to_string(),
+            role: Role::User,
+            email_verified: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn duplicate_email_conflicts() {
+        for stores in backends().await {
+            let users = stores.users;
+            let ada = users.insert(new_user("ada@example.com")).await.unwrap();
+            assert!(matches!(users.insert(new_user("ada@example.com")).await, Err(StoreError::Conflict)));
+
+            let mut grace = users.insert(new_user("grace@example.com")).await.unwrap();
+            grace.email = ada.email.clone();
+            assert!(matches!(users.update(&grace).await, Err(StoreError::Conflict)));
+            assert_eq!(users.list().await.unwrap().len(), 2);
+        }
+    }
+
+    #[tokio::test]
+    async fn missing_user_is_not_found() {
+        for stores in backends().await {
+            let users = stores.users;
+            let mut ada = users.insert(new_user("ada@example.com")).await.unwrap();
+            users.delete(ada.id).await.unwrap();
+
+            assert!(matches!(users.delete(ada.id).await, Err(StoreError::NotFound)));
+            ada.first_name = "Augusta".to_string();
+            assert!(matches!(users.update(&ada).await, Err(StoreError::NotFound)));
+            assert!(users.find_by_id(ada.id).await.unwrap().is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn subject_revocation_covers_its_whole_second() {
+        let revoked_at = Utc.timestamp_opt(1_700_000_000, 500_000_000).unwrap();
+        let expires_at = revoked_at + Duration::hours(1);
+        for stores in backends().await {
+            let revocations = stores.revocations;
+            revocations.revoke_subject("alice", revoked_at, expires_at, None).await.unwrap();
+
+            assert!(revocations.is_revoked("t1", "alice", 1_699_999_999).await.unwrap());
+            // Issued in the same second, possibly just before the revocation
+            assert!(revocations.is_revoked("t2", "alice", 1_700_000_000).await.unwrap());
+            assert!(!revocations.is_revoked("t3", "alice", 1_700_000_001).await.unwrap());
+            assert!(!revocations.is_revoked("t4", "bob", 1_699_999_999).await.unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn subject_revocation_spares_one_token() {
+        let revoked_at = Utc.timestamp_opt(1_700_000_000, 500_000_000).unwrap();
+        let expires_at = revoked_at + Duration::hours(1);
+        for stores in backends().await {
+            let revocations = stores.revocations;
+            revocations.revoke_subject("alice", revoked_at, expires_at, Some("spared")).await.unwrap();
+            assert!(!revocations.is_revoked("spared", "alice", 1_700_000_000).await.unwrap());
+            assert!(revocations.is_revoked("other", "alice", 1_700_000_000).await.unwrap());
+
+            // Revoking the subject again takes the exemption away
+            revocations.revoke_subject("alice", revoked_at, expires_at, None).await.unwrap();
+            assert!(revocations.is_revoked("spared", "alice", 1_700_000_000).await.unwrap());
+        }
+    }
+}
@@ -0,0 +1,1081 @@
+use async_trait::async_trait;
+use sqlx::{
+    postgres::{PgPool, PgPoolOptions, PgRow},
+    Row,
+};
+use chrono::{DateTime, Utc};
+use std::str::FromStr;
+use uuid::Uuid;
+
+use super::{
+    ActionTokenStore, MfaStore, OAuthStore, PasskeyStore, RefreshTokenStore, RevocationStore, SigningKeyStore,
+    StoreError, UserStore,
+};
+use crate::models::{
+    ActionToken, AuthorizationCode, Ceremony, DeviceAuthorization, GrantType, NewUser, OAuthClient, Passkey,
+    PasskeyChallenge, RefreshToken, Role, SigningKeyRecord, TokenPurpose, TotpCredential, User,
+};
+
+/// Stores users and tokens in PostgreSQL through a shared connection pool, so
+/// several API instances can serve the same accounts and sessions. Migrations under
+/// `migrations/postgres` are embedded and applied when the store is opened.
+#[derive(Debug, Clone)]
+pub struct PostgresStore {
+    pool: PgPool,
+}
+
+impl PostgresStore {
+    pub async fn connect(url: &str, max_connections: u32) -> Result<Self, StoreError> {
+        let pool = PgPoolOptions::new()
+            .max_connections(max_connections)
+            .connect(url)
+            .await
+            .map_err(backend)?;
+
+        sqlx::migrate!("./migrations/postgres")
+            .run(&pool)
+            .await
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+
+        Ok(Self { pool })
+    }
+}
+
+fn backend(err: sqlx::Error) -> StoreError {
+    match err {
+        sqlx::Error::Database(ref db) if db.is_unique_violation() => StoreError::Conflict,
+        other => StoreError::Backend(other.to_string()),
+    }
+}
+
+fn user_from_row(row: &PgRow) -> Result<User, StoreError> {
+    let role: String = row.try_get("role").map_err(backend)?;
+
+    Ok(User {
+        id: row.try_get("id").map_err(backend)?,
+        email: row.try_get("email").map_err(backend)?,
+        first_name: row.try_get("first_name").map_err(backend)?,
+        last_name: row.try_get("last_name").map_err(backend)?,
+        password: row.try_get("password").map_err(backend)?,
+        role: Role::from_str(&role).map_err(StoreError::Backend)?,
+        email_verified: row.try_get("email_verified").map_err(backend)?,
+        disabled: row.try_get("disabled").map_err(backend)?,
+    })
+}
+
+fn refresh_token_from_row(row: &PgRow) -> Result<RefreshToken, StoreError> {
+    Ok(RefreshToken {
+        id: row.try_get("id").map_err(backend)?,
+        family_id: row.try_get("family_id").map_err(backend)?,
+        user_id: row.try_get("user_id").map_err(backend)?,
+        token_hash: row.try_get("token_hash").map_err(backend)?,
+        created_at: row.try_get("created_at").map_err(backend)?,
+        expires_at: row.try_get("expires_at").map_err(backend)?,
+        used_at: row.try_get("used_at").map_err(backend)?,
+        revoked: row.try_get("revoked").map_err(backend)?,
+    })
+}
+
+fn action_token_from_row(row: &PgRow) -> Result<ActionToken, StoreError> {
+    let purpose: String = row.try_get("purpose").map_err(backend)?;
+    Ok(ActionToken {
+        token_hash: row.try_get("token_hash").map_err(backend)?,
+        user_id: row.try_get("user_id").map_err(backend)?,
+        purpose: TokenPurpose::parse(&purpose)
+            .ok_or_else(|| StoreError::Backend(format!("unknown token purpose '{}'", purpose)))?,
+        created_at: row.try_get("created_at").map_err(backend)?,
+        expires_at: row.try_get("expires_at").map_err(backend)?,
+        used_at: row.try_get("used_at").map_err(backend)?,
+    })
+}
+
+fn totp_credential_from_row(row: &PgRow) -> Result<TotpCredential, StoreError> {
+    Ok(TotpCredential {
+        user_id: row.try_get("user_id").map_err(backend)?,
+        secret: row.try_get("secret").map_err(backend)?,
+        created_at: row.try_get("created_at").map_err(backend)?,
+        enabled_at: row.try_get("enabled_at").map_err(backend)?,
+        last_used_step: row.try_get("last_used_step").map_err(backend)?,
+    })
+}
+
+fn passkey_from_row(row: &PgRow) -> Result<Passkey, StoreError> {
+    let sign_count: i64 = row.try_get("sign_count").map_err(backend)?;
+    Ok(Passkey {
+        id: row.try_get("id").map_err(backend)?,
+        user_id: row.try_get("user_id").map_err(backend)?,
+        credential_id: row.try_get("credential_id").map_err(backend)?,
+        public_key: row.try_get("public_key").map_err(backend)?,
+        sign_count: sign_count as u32,
+        name: row.try_get("name").map_err(backend)?,
+        created_at: row.try_get("created_at").map_err(backend)?,
+        last_used_at: row.try_get("last_used_at").map_err(backend)?,
+    })
+}
+
+fn passkey_challenge_from_row(row: &PgRow) -> Result<PasskeyChallenge, StoreError> {
+    let ceremony: String = row.try_get("ceremony").map_err(backend)?;
+    Ok(PasskeyChallenge {
+        challenge_hash: row.try_get("challenge_hash").map_err(backend)?,
+        user_id: row.try_get("user_id").map_err(backend)?,
+        ceremony: Ceremony::parse(&ceremony)
+            .ok_or_else(|| StoreError::Backend(format!("unknown ceremony '{}'", ceremony)))?,
+        expires_at: row.try_get("expires_at").map_err(backend)?,
+    })
+}
+
+fn oauth_client_from_row(row: &PgRow) -> Result<OAuthClient, StoreError> {
+    let grant_types: Vec<String> = row.try_get("grant_types").map_err(backend)?;
+    let grant_types = grant_types
+        .iter()
+        .map(|g| GrantType::parse(g).ok_or_else(|| StoreError::Backend(format!("unknown grant type '{}'", g))))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(OAuthClient {
+        client_id: row.try_get("client_id").map_err(backend)?,
+        name: row.try_get("name").map_err(backend)?,
+        secret_hash: row.try_get("secret_hash").map_err(backend)?,
+        grant_types,
+        redirect_uris: row.try_get("redirect_uris").map_err(backend)?,
+        scopes: row.try_get("scopes").map_err(backend)?,
+        created_at: row.try_get("created_at").map_err(backend)?,
+    })
+}
+
+fn authorization_code_from_row(row: &PgRow) -> Result<AuthorizationCode, StoreError> {
+    Ok(AuthorizationCode {
+        code_hash: row.try_get("code_hash").map_err(backend)?,
+        client_id: row.try_get("client_id").map_err(backend)?,
+        user_id: row.try_get("user_id").map_err(backend)?,
+        redirect_uri: row.try_get("redirect_uri").map_err(backend)?,
+        scope: row.try_get("scope").map_err(backend)?,
+        code_challenge: row.try_get("code_challenge").map_err(backend)?,
+        nonce: row.try_get("nonce").map_err(backend)?,
+        token_id: row.try_get("token_id").map_err(backend)?,
+        created_at: row.try_get("created_at").map_err(backend)?,
+        expires_at: row.try_get("expires_at").map_err(backend)?,
+        used_at: row.try_get("used_at").map_err(backend)?,
+    })
+}
+
+fn device_authorization_from_row(row: &PgRow) -> Result<DeviceAuthorization, StoreError> {
+    let interval_secs: i64 = row.try_get("interval_secs").map_err(backend)?;
+    Ok(DeviceAuthorization {
+        device_code_hash: row.try_get("device_code_hash").map_err(backend)?,
+        user_code_hash: row.try_get("user_code_hash").map_err(backend)?,
+        client_id: row.try_get("client_id").map_err(backend)?,
+        scope: row.try_get("scope").map_err(backend)?,
+        user_id: row.try_get("user_id").map_err(backend)?,
+        approved: row.try_get("approved").map_err(backend)?,
+        interval_secs: interval_secs as u32,
+        last_polled_at: row.try_get("last_polled_at").map_err(backend)?,
+        created_at: row.try_get("created_at").map_err(backend)?,
+        expires_at: row.try_get("expires_at").map_err(backend)?,
+        used_at: row.try_get("used_at").map_err(backend)?,
+    })
+}
+
+fn signing_key_from_row(row: &PgRow) -> Result<SigningKeyRecord, StoreError> {
+    Ok(SigningKeyRecord {
+        kid: row.try_get("kid").map_err(backend)?,
+        algorithm: row.try_get("algorithm").map_err(backend)?,
+        private_key: row.try_get("private_key").map_err(backend)?,
+        created_at: row.try_get("created_at").map_err(backend)?,
+        activated_at: row.try_get("activated_at").map_err(backend)?,
+        retired_at: row.try_get("retired_at").map_err(backend)?,
+        verify_until: row.try_get("verify_until").map_err(backend)?,
+    })
+}
+
+const SELECT_USER: &str = "SELECT id, email, first_name, last_name, password, role, email_verified, disabled FROM users";
+
+#[async_trait]
+impl UserStore for PostgresStore {
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<User>, StoreError> {
+        sqlx::query(&format!("{} WHERE id = $1", SELECT_USER))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(backend)?
+            .map(|row| user_from_row(&row))
+            .transpose()
+    }
+
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, StoreError> {
+        sqlx::query(&format!("{} WHERE email = $1", SELECT_USER))
+            .bind(email)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(backend)?
+            .map(|row| user_from_row(&row))
+            .transpose()
+    }
+
+    async fn insert(&self, user: NewUser) -> Result<User, StoreError> {
+        let user = user.into_user();
+
+        // A single statement: the unique index on email turns a concurrent
+        // registration of the same address into zero affected rows.
+        let inserted = sqlx::query(
+            "INSERT INTO users (id, email, first_name, last_name, password, role, email_verified, disabled) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8) \
+             ON CONFLICT (email) DO NOTHING",
+        )
+        .bind(user.id)
+        .bind(&user.email)
+        .bind(&user.first_name)
+        .bind(&user.last_name)
+        .bind(&user.password)
+        .bind(user.role.as_str())
+        .bind(user.email_verified)
+        .bind(user.disabled)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+
+        if inserted.rows_affected() == 0 {
+            return Err(StoreError::Conflict);
+        }
+
+        Ok(user)
+    }
+
+    async fn update(&self, user: &User) -> Result<(), StoreError> {
+        let result = sqlx::query(
+            "UPDATE users SET email = $1, first_name = $2, last_name = $3, password = $4, role = $5, email_verified = $6, \
+             disabled = $7 WHERE id = $8",
+        )
+        .bind(&user.email)
+        .bind(&user.first_name)
+        .bind(&user.last_name)
+        .bind(&user.password)
+        .bind(user.role.as_str())
+        .bind(user.email_verified)
+        .bind(user.disabled)
+        .bind(user.id)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+
+        if result.rows_affected() == 0 {
+            return Err(StoreError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<(), StoreError> {
+        let result = sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+
+        if result.rows_affected() == 0 {
+            return Err(StoreError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<Vec<User>, StoreError> {
+        sqlx::query(&format!("{} ORDER BY id", SELECT_USER))
+            .fetch_all(&self.pool)
+            .await
+            .map_err(backend)?
+            .iter()
+            .map(user_from_row)
+            .collect()
+    }
+}
+
+#[async_trait]
+impl RefreshTokenStore for PostgresStore {
+    async fn insert(&self, token: RefreshToken) -> Result<(), StoreError> {
+        sqlx::query(
+            "INSERT INTO refresh_tokens (id, family_id, user_id, token_hash, created_at, expires_at, used_at, revoked) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        )
+        .bind(token.id)
+        .bind(token.family_id)
+        .bind(token.user_id)
+        .bind(&token.token_hash)
+        .bind(token.created_at)
+        .bind(token.expires_at)
+        .bind(token.used_at)
+        .bind(token.revoked)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<RefreshToken>, StoreError> {
+        sqlx::query("SELECT * FROM refresh_tokens WHERE token_hash = $1")
+            .bind(token_hash)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(backend)?
+            .map(|row| refresh_token_from_row(&row))
+            .transpose()
+    }
+
+    async fn mark_used(&self, token_hash: &str, at: DateTime<Utc>) -> Result<bool, StoreError> {
+        let result = sqlx::query(
+            "UPDATE refresh_tokens SET used_at = $1 WHERE token_hash = $2 AND used_at IS NULL",
+        )
+        .bind(at)
+        .bind(token_hash)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn revoke_family(&self, family_id: Uuid) -> Result<(), StoreError> {
+        sqlx::query("UPDATE refresh_tokens SET revoked = TRUE WHERE family_id = $1")
+            .bind(family_id)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn revoke_user(&self, user_id: Uuid) -> Result<(), StoreError> {
+        sqlx::query("UPDATE refresh_tokens SET revoked = TRUE WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, StoreError> {
+        let result = sqlx::query("DELETE FROM refresh_tokens WHERE expires_at <= $1")
+            .bind(now)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[async_trait]
+impl RevocationStore for PostgresStore {
+    async fn revoke_token(&self, jti: &str, expires_at: DateTime<Utc>) -> Result<(), StoreError> {
+        sqlx::query(
+            "INSERT INTO revoked_tokens (jti, expires_at) VALUES ($1, $2) ON CONFLICT (jti) DO NOTHING",
+        )
+        .bind(jti)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn revoke_subject(
+        &self,
+        subject: &str,
+        revoked_at: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+        spared_jti: Option<&str>,
+    ) -> Result<(), StoreError> {
+        sqlx::query(
+            "INSERT INTO revoked_subjects (subject, revoked_at, expires_at, spared_jti) VALUES ($1, $2, $3, $4) \
+             ON CONFLICT (subject) DO UPDATE SET revoked_at = excluded.revoked_at, expires_at = excluded.expires_at, \
+             spared_jti = excluded.spared_jti",
+        )
+        .bind(subject)
+        .bind(revoked_at)
+        .bind(expires_at)
+        .bind(spared_jti)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn is_revoked(&self, jti: &str, subject: &str, issued_at: i64) -> Result<bool, StoreError> {
+        let issued_at = DateTime::from_timestamp(issued_at, 0)
+            .ok_or_else(|| StoreError::Backend("invalid token issue time".to_string()))?;
+
+        let revoked: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM revoked_tokens WHERE jti = $1) \
+             OR EXISTS (SELECT 1 FROM revoked_subjects WHERE subject = $2 \
+                        AND date_trunc('second', revoked_at) >= $3 AND spared_jti IS DISTINCT FROM $1)",
+        )
+        .bind(jti)
+        .bind(subject)
+        .bind(issued_at)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(backend)?;
+
+        Ok(revoked)
+    }
+
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, StoreError> {
+        let tokens = sqlx::query("DELETE FROM revoked_tokens WHERE expires_at <= $1")
+            .bind(now)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+        let subjects = sqlx::query("DELETE FROM revoked_subjects WHERE expires_at <= $1")
+            .bind(now)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+
+        Ok(tokens.rows_affected() + subjects.rows_affected())
+    }
+}
+
+#[async_trait]
+impl SigningKeyStore for PostgresStore {
+    async fn list(&self) -> Result<Vec<SigningKeyRecord>, StoreError> {
+        sqlx::query("SELECT * FROM signing_keys ORDER BY created_at")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(backend)?
+            .iter()
+            .map(signing_key_from_row)
+            .collect()
+    }
+
+    async fn insert(&self, key: SigningKeyRecord) -> Result<(), StoreError> {
+        sqlx::query(
+            "INSERT INTO signing_keys (kid, algorithm, private_key, created_at, activated_at, retired_at, verify_until) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(&key.kid)
+        .bind(&key.algorithm)
+        .bind(&key.private_key)
+        .bind(key.created_at)
+        .bind(key.activated_at)
+        .bind(key.retired_at)
+        .bind(key.verify_until)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn replace_private_key(&self, kid: &str, private_key: &str) -> Result<(), StoreError> {
+        let result = sqlx::query("UPDATE signing_keys SET private_key = $1 WHERE kid = $2")
+            .bind(private_key)
+            .bind(kid)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+
+        if result.rows_affected() == 0 {
+            return Err(StoreError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn promote(&self, kid: &str, at: DateTime<Utc>) -> Result<(), StoreError> {
+        let result = sqlx::query("UPDATE signing_keys SET activated_at = $1 WHERE kid = $2 AND retired_at IS NULL")
+            .bind(at)
+            .bind(kid)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+
+        if result.rows_affected() == 0 {
+            return Err(StoreError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn retire(&self, kid: &str, at: DateTime<Utc>, verify_until: DateTime<Utc>) -> Result<(), StoreError> {
+        let result = sqlx::query("UPDATE signing_keys SET retired_at = $1, verify_until = $2 WHERE kid = $3")
+            .bind(at)
+            .bind(verify_until)
+            .bind(kid)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+
+        if result.rows_affected() == 0 {
+            return Err(StoreError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, StoreError> {
+        let result = sqlx::query("DELETE FROM signing_keys WHERE verify_until <= $1")
+            .bind(now)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[async_trait]
+impl ActionTokenStore for PostgresStore {
+    async fn insert(&self, token: ActionToken) -> Result<(), StoreError> {
+        sqlx::query(
+            "INSERT INTO action_tokens (token_hash, user_id, purpose, created_at, expires_at, used_at) \
+             VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(&token.token_hash)
+        .bind(token.user_id)
+        .bind(token.purpose.as_str())
+        .bind(token.created_at)
+        .bind(token.expires_at)
+        .bind(token.used_at)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<ActionToken>, StoreError> {
+        sqlx::query("SELECT * FROM action_tokens WHERE token_hash = $1")
+            .bind(token_hash)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(backend)?
+            .map(|row| action_token_from_row(&row))
+            .transpose()
+    }
+
+    async fn mark_used(&self, token_hash: &str, at: DateTime<Utc>) -> Result<bool, StoreError> {
+        let result = sqlx::query(
+            "UPDATE action_tokens SET used_at = $1 WHERE token_hash = $2 AND used_at IS NULL",
+        )
+        .bind(at)
+        .bind(token_hash)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn last_issued(&self, user_id: Uuid, purpose: TokenPurpose) -> Result<Option<DateTime<Utc>>, StoreError> {
+        sqlx::query_scalar(
+            "SELECT created_at FROM action_tokens WHERE user_id = $1 AND purpose = $2 ORDER BY created_at DESC LIMIT 1",
+        )
+        .bind(user_id)
+        .bind(purpose.as_str())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(backend)
+    }
+
+    async fn revoke_user(&self, user_id: Uuid, purpose: TokenPurpose) -> Result<(), StoreError> {
+        sqlx::query("DELETE FROM action_tokens WHERE user_id = $1 AND purpose = $2 AND used_at IS NULL")
+            .bind(user_id)
+            .bind(purpose.as_str())
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, StoreError> {
+        let result = sqlx::query("DELETE FROM action_tokens WHERE expires_at <= $1")
+            .bind(now)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[async_trait]
+impl MfaStore for PostgresStore {
+    async fn find_totp(&self, user_id: Uuid) -> Result<Option<TotpCredential>, StoreError> {
+        sqlx::query("SELECT * FROM totp_credentials WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(backend)?
+            .map(|row| totp_credential_from_row(&row))
+            .transpose()
+    }
+
+    async fn begin_totp(&self, credential: TotpCredential) -> Result<(), StoreError> {
+        // An enabled secret is never overwritten; the upsert then changes nothing
+        let result = sqlx::query(
+            "INSERT INTO totp_credentials (user_id, secret, created_at, enabled_at, last_used_step) \
+             VALUES ($1, $2, $3, NULL, NULL) \
+             ON CONFLICT (user_id) DO UPDATE SET secret = excluded.secret, created_at = excluded.created_at, \
+             last_used_step = NULL WHERE totp_credentials.enabled_at IS NULL",
+        )
+        .bind(credential.user_id)
+        .bind(&credential.secret)
+        .bind(credential.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+
+        if result.rows_affected() == 0 {
+            return Err(StoreError::Conflict);
+        }
+        Ok(())
+    }
+
+    async fn enable_totp(&self, user_id: Uuid, at: DateTime<Utc>) -> Result<bool, StoreError> {
+        let result = sqlx::query("UPDATE totp_credentials SET enabled_at = $1 WHERE user_id = $2 AND enabled_at IS NULL")
+            .bind(at)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn use_totp_step(&self, user_id: Uuid, step: i64) -> Result<bool, StoreError> {
+        let result = sqlx::query(
+            "UPDATE totp_credentials SET last_used_step = $1 \
+             WHERE user_id = $2 AND (last_used_step IS NULL OR last_used_step < $3)",
+        )
+        .bind(step)
+        .bind(user_id)
+        .bind(step)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn list_totp(&self) -> Result<Vec<TotpCredential>, StoreError> {
+        sqlx::query("SELECT * FROM totp_credentials")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(backend)?
+            .iter()
+            .map(totp_credential_from_row)
+            .collect()
+    }
+
+    async fn replace_totp_secret(&self, user_id: Uuid, current: &str, replacement: &str) -> Result<bool, StoreError> {
+        let result = sqlx::query("UPDATE totp_credentials SET secret = $1 WHERE user_id = $2 AND secret = $3")
+            .bind(replacement)
+            .bind(user_id)
+            .bind(current)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn disable_totp(&self, user_id: Uuid) -> Result<(), StoreError> {
+        let mut tx = self.pool.begin().await.map_err(backend)?;
+        sqlx::query("DELETE FROM recovery_codes WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(backend)?;
+        sqlx::query("DELETE FROM totp_credentials WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(backend)?;
+        tx.commit().await.map_err(backend)
+    }
+
+    async fn replace_recovery_codes(
+        &self,
+        user_id: Uuid,
+        code_hashes: &[String],
+        at: DateTime<Utc>,
+    ) -> Result<(), StoreError> {
+        let mut tx = self.pool.begin().await.map_err(backend)?;
+        sqlx::query("DELETE FROM recovery_codes WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(backend)?;
+        for code_hash in code_hashes {
+            sqlx::query("INSERT INTO recovery_codes (user_id, code_hash, created_at, used_at) VALUES ($1, $2, $3, NULL)")
+                .bind(user_id)
+                .bind(code_hash)
+                .bind(at)
+                .execute(&mut *tx)
+                .await
+                .map_err(backend)?;
+        }
+        tx.commit().await.map_err(backend)
+    }
+
+    async fn use_recovery_code(&self, user_id: Uuid, code_hash: &str, at: DateTime<Utc>) -> Result<bool, StoreError> {
+        let result = sqlx::query(
+            "UPDATE recovery_codes SET used_at = $1 WHERE user_id = $2 AND code_hash = $3 AND used_at IS NULL",
+        )
+        .bind(at)
+        .bind(user_id)
+        .bind(code_hash)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn remaining_recovery_codes(&self, user_id: Uuid) -> Result<u64, StoreError> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM recovery_codes WHERE user_id = $1 AND used_at IS NULL")
+            .bind(user_id)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(backend)?;
+        Ok(count as u64)
+    }
+}
+
+#[async_trait]
+impl PasskeyStore for PostgresStore {
+    async fn insert(&self, passkey: Passkey) -> Result<(), StoreError> {
+        sqlx::query(
+            "INSERT INTO passkeys (id, user_id, credential_id, public_key, sign_count, name, created_at, last_used_at) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        )
+        .bind(passkey.id)
+        .bind(passkey.user_id)
+        .bind(&passkey.credential_id)
+        .bind(&passkey.public_key)
+        .bind(passkey.sign_count as i64)
+        .bind(&passkey.name)
+        .bind(passkey.created_at)
+        .bind(passkey.last_used_at)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn find_by_credential_id(&self, credential_id: &str) -> Result<Option<Passkey>, StoreError> {
+        sqlx::query("SELECT * FROM passkeys WHERE credential_id = $1")
+            .bind(credential_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(backend)?
+            .map(|row| passkey_from_row(&row))
+            .transpose()
+    }
+
+    async fn list_for_user(&self, user_id: Uuid) -> Result<Vec<Passkey>, StoreError> {
+        sqlx::query("SELECT * FROM passkeys WHERE user_id = $1 ORDER BY created_at")
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(backend)?
+            .iter()
+            .map(passkey_from_row)
+            .collect()
+    }
+
+    async fn record_use(
+        &self,
+        id: Uuid,
+        previous_count: u32,
+        sign_count: u32,
+        at: DateTime<Utc>,
+    ) -> Result<bool, StoreError> {
+        let result = sqlx::query(
+            "UPDATE passkeys SET sign_count = $1, last_used_at = $2 WHERE id = $3 AND sign_count = $4",
+        )
+        .bind(sign_count as i64)
+        .bind(at)
+        .bind(id)
+        .bind(previous_count as i64)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn delete(&self, user_id: Uuid, id: Uuid) -> Result<(), StoreError> {
+        let result = sqlx::query("DELETE FROM passkeys WHERE id = $1 AND user_id = $2")
+            .bind(id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+
+        if result.rows_affected() == 0 {
+            return Err(StoreError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn insert_challenge(&self, challenge: PasskeyChallenge) -> Result<(), StoreError> {
+        sqlx::query("INSERT INTO passkey_challenges (challenge_hash, user_id, ceremony, expires_at) VALUES ($1, $2, $3, $4)")
+            .bind(&challenge.challenge_hash)
+            .bind(challenge.user_id)
+            .bind(challenge.ceremony.as_str())
+            .bind(challenge.expires_at)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn take_challenge(&self, challenge_hash: &str) -> Result<Option<PasskeyChallenge>, StoreError> {
+        sqlx::query("DELETE FROM passkey_challenges WHERE challenge_hash = $1 RETURNING *")
+            .bind(challenge_hash)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(backend)?
+            .map(|row| passkey_challenge_from_row(&row))
+            .transpose()
+    }
+
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, StoreError> {
+        let result = sqlx::query("DELETE FROM passkey_challenges WHERE expires_at <= $1")
+            .bind(now)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[async_trait]
+impl OAuthStore for PostgresStore {
+    async fn insert_client(&self, client: OAuthClient) -> Result<(), StoreError> {
+        sqlx::query(
+            "INSERT INTO oauth_clients (client_id, name, secret_hash, grant_types, redirect_uris, scopes, created_at) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(&client.client_id)
+        .bind(&client.name)
+        .bind(&client.secret_hash)
+        .bind(client.grant_types.iter().map(GrantType::as_str).collect::<Vec<_>>())
+        .bind(&client.redirect_uris)
+        .bind(&client.scopes)
+        .bind(client.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn find_client(&self, client_id: &str) -> Result<Option<OAuthClient>, StoreError> {
+        sqlx::query("SELECT * FROM oauth_clients WHERE client_id = $1")
+            .bind(client_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(backend)?
+            .map(|row| oauth_client_from_row(&row))
+            .transpose()
+    }
+
+    async fn list_clients(&self) -> Result<Vec<OAuthClient>, StoreError> {
+        sqlx::query("SELECT * FROM oauth_clients ORDER BY created_at")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(backend)?
+            .iter()
+            .map(oauth_client_from_row)
+            .collect()
+    }
+
+    async fn set_client_secret(&self, client_id: &str, secret_hash: &str) -> Result<(), StoreError> {
+        let result = sqlx::query("UPDATE oauth_clients SET secret_hash = $1 WHERE client_id = $2")
+            .bind(secret_hash)
+            .bind(client_id)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+
+        if result.rows_affected() == 0 {
+            return Err(StoreError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn delete_client(&self, client_id: &str) -> Result<(), StoreError> {
+        let result = sqlx::query("DELETE FROM oauth_clients WHERE client_id = $1")
+            .bind(client_id)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+
+        if result.rows_affected() == 0 {
+            return Err(StoreError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn insert_code(&self, code: AuthorizationCode) -> Result<(), StoreError> {
+        sqlx::query(
+            "INSERT INTO oauth_authorization_codes \
+             (code_hash, client_id, user_id, redirect_uri, scope, code_challenge, nonce, token_id, created_at, expires_at, used_at) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)",
+        )
+        .bind(&code.code_hash)
+        .bind(&code.client_id)
+        .bind(code.user_id)
+        .bind(&code.redirect_uri)
+        .bind(&code.scope)
+        .bind(&code.code_challenge)
+        .bind(&code.nonce)
+        .bind(&code.token_id)
+        .bind(code.created_at)
+        .bind(code.expires_at)
+        .bind(code.used_at)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn find_code(&self, code_hash: &str) -> Result<Option<AuthorizationCode>, StoreError> {
+        sqlx::query("SELECT * FROM oauth_authorization_codes WHERE code_hash = $1")
+            .bind(code_hash)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(backend)?
+            .map(|row| authorization_code_from_row(&row))
+            .transpose()
+    }
+
+    async fn mark_code_used(&self, code_hash: &str, at: DateTime<Utc>) -> Result<bool, StoreError> {
+        let result = sqlx::query(
+            "UPDATE oauth_authorization_codes SET used_at = $1 WHERE code_hash = $2 AND used_at IS NULL",
+        )
+        .bind(at)
+        .bind(code_hash)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn insert_device_authorization(&self, authorization: DeviceAuthorization) -> Result<(), StoreError> {
+        sqlx::query(
+            "INSERT INTO oauth_device_authorizations \
+             (device_code_hash, user_code_hash, client_id, scope, user_id, approved, interval_secs, last_polled_at, \
+             created_at, expires_at, used_at) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)",
+        )
+        .bind(&authorization.device_code_hash)
+        .bind(&authorization.user_code_hash)
+        .bind(&authorization.client_id)
+        .bind(&authorization.scope)
+        .bind(authorization.user_id)
+        .bind(authorization.approved)
+        .bind(authorization.interval_secs as i64)
+        .bind(authorization.last_polled_at)
+        .bind(authorization.created_at)
+        .bind(authorization.expires_at)
+        .bind(authorization.used_at)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn find_device_authorization(&self, device_code_hash: &str) -> Result<Option<DeviceAuthorization>, StoreError> {
+        sqlx::query("SELECT * FROM oauth_device_authorizations WHERE device_code_hash = $1")
+            .bind(device_code_hash)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(backend)?
+            .map(|row| device_authorization_from_row(&row))
+            .transpose()
+    }
+
+    async fn find_device_authorization_by_user_code(
+        &self,
+        user_code_hash: &str,
+    ) -> Result<Option<DeviceAuthorization>, StoreError> {
+        sqlx::query("SELECT * FROM oauth_device_authorizations WHERE user_code_hash = $1")
+            .bind(user_code_hash)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(backend)?
+            .map(|row| device_authorization_from_row(&row))
+            .transpose()
+    }
+
+    async fn decide_device_authorization(
+        &self,
+        user_code_hash: &str,
+        user_id: Uuid,
+        approved: bool,
+        at: DateTime<Utc>,
+    ) -> Result<bool, StoreError> {
+        let result = sqlx::query(
+            "UPDATE oauth_device_authorizations SET user_id = $1, approved = $2 \
+             WHERE user_code_hash = $3 AND approved IS NULL AND expires_at > $4",
+        )
+        .bind(user_id)
+        .bind(approved)
+        .bind(user_code_hash)
+        .bind(at)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn record_device_poll(
+        &self,
+        device_code_hash: &str,
+        at: DateTime<Utc>,
+        interval_secs: u32,
+    ) -> Result<(), StoreError> {
+        sqlx::query(
+            "UPDATE oauth_device_authorizations SET last_polled_at = $1, interval_secs = $2 WHERE device_code_hash = $3",
+        )
+        .bind(at)
+        .bind(interval_secs as i64)
+        .bind(device_code_hash)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn mark_device_authorization_used(&self, device_code_hash: &str, at: DateTime<Utc>) -> Result<bool, StoreError> {
+        let result = sqlx::query(
+            "UPDATE oauth_device_authorizations SET used_at = $1 WHERE device_code_hash = $2 AND used_at IS NULL",
+        )
+        .bind(at)
+        .bind(device_code_hash)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, StoreError> {
+        let codes = sqlx::query("DELETE FROM oauth_authorization_codes WHERE expires_at <= $1")
+            .bind(now)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+        let authorizations = sqlx::query("DELETE FROM oauth_device_authorizations WHERE expires_at <= $1")
+            .bind(now)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+        Ok(codes.rows_affected() + authorizations.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn applies_every_migration() {
+        let Some(url) = crate::test_support::postgres_url().await else {
+            return;
+        };
+        let store = PostgresStore::connect(&url, 2).await.unwrap();
+        let applied: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM _sqlx_migrations WHERE success")
+            .fetch_one(&store.pool)
+            .await
+            .unwrap();
+        assert_eq!(applied as usize, sqlx::migrate!("./migrations/postgres").iter().count());
+        assert!(UserStore::list(&store).await.unwrap().is_empty());
+    }
+}
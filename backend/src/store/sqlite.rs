@@ -0,0 +1,1175 @@
+use async_trait::async_trait;
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions, SqliteRow},
+    Row,
+};
+use chrono::{DateTime, Utc};
+use std::str::FromStr;
+use uuid::Uuid;
+
+use super::{
+    ActionTokenStore, MfaStore, OAuthStore, PasskeyStore, RefreshTokenStore, RevocationStore, SigningKeyStore,
+    StoreError, UserStore,
+};
+use crate::models::{
+    ActionToken, AuthorizationCode, Ceremony, DeviceAuthorization, GrantType, NewUser, OAuthClient, Passkey,
+    PasskeyChallenge, RefreshToken, Role, SigningKeyRecord, TokenPurpose, TotpCredential, User,
+};
+
+/// Stores users and tokens in a SQLite database. Migrations under
+/// `migrations/sqlite` are embedded in the binary and applied when the store
+/// is opened.
+#[derive(Debug, Clone)]
+pub struct SqliteStore {
+    pool: SqlitePool,
+}
+
+impl SqliteStore {
+    /// Opens (or creates) the database at `path` and runs pending migrations.
+    /// `:memory:` (or `sqlite::memory:`) gives a throwaway database that lives
+    /// as long as the store.
+    pub async fn connect(path: &str) -> Result<Self, StoreError> {
+        let pool = if matches!(path, ":memory:" | "sqlite::memory:") {
+            // Every connection to `:memory:` is its own database, so keep exactly one alive.
+            SqlitePoolOptions::new()
+                .max_connections(1)
+                .min_connections(1)
+                .idle_timeout(None)
+                .max_lifetime(None)
+                .connect_with(SqliteConnectOptions::from_str("sqlite::memory:").map_err(backend)?)
+                .await
+        } else {
+            SqlitePoolOptions::new()
+                .connect_with(
+                    SqliteConnectOptions::new()
+                        .filename(path)
+                        .create_if_missing(true),
+                )
+                .await
+        }
+        .map_err(backend)?;
+
+        sqlx::migrate!("./migrations/sqlite")
+            .run(&pool)
+            .await
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+
+        Ok(Self { pool })
+    }
+}
+
+fn backend(err: sqlx::Error) -> StoreError {
+    match err {
+        sqlx::Error::Database(ref db) if db.is_unique_violation() => StoreError::Conflict,
+        other => StoreError::Backend(other.to_string()),
+    }
+}
+
+fn user_from_row(row: &SqliteRow) -> Result<User, StoreError> {
+    let role: String = row.try_get("role").map_err(backend)?;
+
+    Ok(User {
+        id: uuid_from_text(row.try_get("id").map_err(backend)?)?,
+        email: row.try_get("email").map_err(backend)?,
+        first_name: row.try_get("first_name").map_err(backend)?,
+        last_name: row.try_get("last_name").map_err(backend)?,
+        password: row.try_get("password").map_err(backend)?,
+        role: Role::from_str(&role).map_err(StoreError::Backend)?,
+        email_verified: row.try_get("email_verified").map_err(backend)?,
+        disabled: row.try_get("disabled").map_err(backend)?,
+    })
+}
+
+fn uuid_from_text(value: &str) -> Result<Uuid, StoreError> {
+    Uuid::parse_str(value).map_err(|e| StoreError::Backend(e.to_string()))
+}
+
+fn refresh_token_from_row(row: &SqliteRow) -> Result<RefreshToken, StoreError> {
+    Ok(RefreshToken {
+        id: uuid_from_text(row.try_get("id").map_err(backend)?)?,
+        family_id: uuid_from_text(row.try_get("family_id").map_err(backend)?)?,
+        user_id: uuid_from_text(row.try_get("user_id").map_err(backend)?)?,
+        token_hash: row.try_get("token_hash").map_err(backend)?,
+        created_at: row.try_get("created_at").map_err(backend)?,
+        expires_at: row.try_get("expires_at").map_err(backend)?,
+        used_at: row.try_get("used_at").map_err(backend)?,
+        revoked: row.try_get("revoked").map_err(backend)?,
+    })
+}
+
+fn action_token_from_row(row: &SqliteRow) -> Result<ActionToken, StoreError> {
+    let purpose: String = row.try_get("purpose").map_err(backend)?;
+    Ok(ActionToken {
+        token_hash: row.try_get("token_hash").map_err(backend)?,
+        user_id: uuid_from_text(row.try_get("user_id").map_err(backend)?)?,
+        purpose: TokenPurpose::parse(&purpose)
+            .ok_or_else(|| StoreError::Backend(format!("unknown token purpose '{}'", purpose)))?,
+        created_at: row.try_get("created_at").map_err(backend)?,
+        expires_at: row.try_get("expires_at").map_err(backend)?,
+        used_at: row.try_get("used_at").map_err(backend)?,
+    })
+}
+
+fn totp_credential_from_row(row: &SqliteRow) -> Result<TotpCredential, StoreError> {
+    Ok(TotpCredential {
+        user_id: uuid_from_text(row.try_get("user_id").map_err(backend)?)?,
+        secret: row.try_get("secret").map_err(backend)?,
+        created_at: row.try_get("created_at").map_err(backend)?,
+        enabled_at: row.try_get("enabled_at").map_err(backend)?,
+        last_used_step: row.try_get("last_used_step").map_err(backend)?,
+    })
+}
+
+fn passkey_from_row(row: &SqliteRow) -> Result<Passkey, StoreError> {
+    let sign_count: i64 = row.try_get("sign_count").map_err(backend)?;
+    Ok(Passkey {
+        id: uuid_from_text(row.try_get("id").map_err(backend)?)?,
+        user_id: uuid_from_text(row.try_get("user_id").map_err(backend)?)?,
+        credential_id: row.try_get("credential_id").map_err(backend)?,
+        public_key: row.try_get("public_key").map_err(backend)?,
+        sign_count: sign_count as u32,
+        name: row.try_get("name").map_err(backend)?,
+        created_at: row.try_get("created_at").map_err(backend)?,
+        last_used_at: row.try_get("last_used_at").map_err(backend)?,
+    })
+}
+
+fn passkey_challenge_from_row(row: &SqliteRow) -> Result<PasskeyChallenge, StoreError> {
+    let user_id: Option<String> = row.try_get("user_id").map_err(backend)?;
+    let ceremony: String = row.try_get("ceremony").map_err(backend)?;
+    Ok(PasskeyChallenge {
+        challenge_hash: row.try_get("challenge_hash").map_err(backend)?,
+        user_id: user_id.as_deref().map(uuid_from_text).transpose()?,
+        ceremony: Ceremony::parse(&ceremony)
+            .ok_or_else(|| StoreError::Backend(format!("unknown ceremony '{}'", ceremony)))?,
+        expires_at: row.try_get("expires_at").map_err(backend)?,
+    })
+}
+
+fn oauth_client_from_row(row: &SqliteRow) -> Result<OAuthClient, StoreError> {
+    let grant_types: String = row.try_get("grant_types").map_err(backend)?;
+    let redirect_uris: String = row.try_get("redirect_uris").map_err(backend)?;
+    let scopes: String = row.try_get("scopes").map_err(backend)?;
+    let grant_types = grant_types
+        .split_whitespace()
+        .map(|g| GrantType::parse(g).ok_or_else(|| StoreError::Backend(format!("unknown grant type '{}'", g))))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(OAuthClient {
+        client_id: row.try_get("client_id").map_err(backend)?,
+        name: row.try_get("name").map_err(backend)?,
+        secret_hash: row.try_get("secret_hash").map_err(backend)?,
+        grant_types,
+        redirect_uris: redirect_uris.split_whitespace().map(str::to_string).collect(),
+        scopes: scopes.split_whitespace().map(str::to_string).collect(),
+        created_at: row.try_get("created_at").map_err(backend)?,
+    })
+}
+
+fn authorization_code_from_row(row: &SqliteRow) -> Result<AuthorizationCode, StoreError> {
+    Ok(AuthorizationCode {
+        code_hash: row.try_get("code_hash").map_err(backend)?,
+        client_id: row.try_get("client_id").map_err(backend)?,
+        user_id: uuid_from_text(row.try_get("user_id").map_err(backend)?)?,
+        redirect_uri: row.try_get("redirect_uri").map_err(backend)?,
+        scope: row.try_get("scope").map_err(backend)?,
+        code_challenge: row.try_get("code_challenge").map_err(backend)?,
+        nonce: row.try_get("nonce").map_err(backend)?,
+        token_id: row.try_get("token_id").map_err(backend)?,
+        created_at: row.try_get("created_at").map_err(backend)?,
+        expires_at: row.try_get("expires_at").map_err(backend)?,
+        used_at: row.try_get("used_at").map_err(backend)?,
+    })
+}
+
+fn device_authorization_from_row(row: &SqliteRow) -> Result<DeviceAuthorization, StoreError> {
+    let interval_secs: i64 = row.try_get("interval_secs").map_err(backend)?;
+    Ok(DeviceAuthorization {
+        device_code_hash: row.try_get("device_code_hash").map_err(backend)?,
+        user_code_hash: row.try_get("user_code_hash").map_err(backend)?,
+        client_id: row.try_get("client_id").map_err(backend)?,
+        scope: row.try_get("scope").map_err(backend)?,
+        user_id: row.try_get::<Option<String>, _>("user_id").map_err(backend)?.as_deref().map(uuid_from_text).transpose()?,
+        approved: row.try_get("approved").map_err(backend)?,
+        interval_secs: interval_secs as u32,
+        last_polled_at: row.try_get("last_polled_at").map_err(backend)?,
+        created_at: row.try_get("created_at").map_err(backend)?,
+        expires_at: row.try_get("expires_at").map_err(backend)?,
+        used_at: row.try_get("used_at").map_err(backend)?,
+    })
+}
+
+fn signing_key_from_row(row: &SqliteRow) -> Result<SigningKeyRecord, StoreError> {
+    Ok(SigningKeyRecord {
+        kid: row.try_get("kid").map_err(backend)?,
+        algorithm: row.try_get("algorithm").map_err(backend)?,
+        private_key: row.try_get("private_key").map_err(backend)?,
+        created_at: row.try_get("created_at").map_err(backend)?,
+        activated_at: row.try_get("activated_at").map_err(backend)?,
+        retired_at: row.try_get("retired_at").map_err(backend)?,
+        verify_until: row.try_get("verify_until").map_err(backend)?,
+    })
+}
+
+const SELECT_USER: &str = "SELECT id, email, first_name, last_name, password, role, email_verified, disabled FROM users";
+
+#[async_trait]
+impl UserStore for SqliteStore {
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<User>, StoreError> {
+        sqlx::query(&format!("{} WHERE id = ?", SELECT_USER))
+            .bind(id.to_string())
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(backend)?
+            .map(|row| user_from_row(&row))
+            .transpose()
+    }
+
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, StoreError> {
+        sqlx::query(&format!("{} WHERE email = ?", SELECT_USER))
+            .bind(email)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(backend)?
+            .map(|row| user_from_row(&row))
+            .transpose()
+    }
+
+    async fn insert(&self, user: NewUser) -> Result<User, StoreError> {
+        let user = user.into_user();
+
+        sqlx::query(
+            "INSERT INTO users (id, email, first_name, last_name, password, role, email_verified, disabled) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(user.id.to_string())
+        .bind(&user.email)
+        .bind(&user.first_name)
+        .bind(&user.last_name)
+        .bind(&user.password)
+        .bind(user.role.as_str())
+        .bind(user.email_verified)
+        .bind(user.disabled)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+
+        Ok(user)
+    }
+
+    async fn update(&self, user: &User) -> Result<(), StoreError> {
+        let result = sqlx::query(
+            "UPDATE users SET email = ?, first_name = ?, last_name = ?, password = ?, role = ?, email_verified = ?, disabled = ? \
+             WHERE id = ?",
+        )
+        .bind(&user.email)
+        .bind(&user.first_name)
+        .bind(&user.last_name)
+        .bind(&user.password)
+        .bind(user.role.as_str())
+        .bind(user.email_verified)
+        .bind(user.disabled)
+        .bind(user.id.to_string())
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+
+        if result.rows_affected() == 0 {
+            return Err(StoreError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<(), StoreError> {
+        let result = sqlx::query("DELETE FROM users WHERE id = ?")
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+
+        if result.rows_affected() == 0 {
+            return Err(StoreError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<Vec<User>, StoreError> {
+        sqlx::query(&format!("{} ORDER BY id", SELECT_USER))
+            .fetch_all(&self.pool)
+            .await
+            .map_err(backend)?
+            .iter()
+            .map(user_from_row)
+            .collect()
+    }
+}
+
+#[async_trait]
+impl RefreshTokenStore for SqliteStore {
+    async fn insert(&self, token: RefreshToken) -> Result<(), StoreError> {
+        sqlx::query(
+            "INSERT INTO refresh_tokens (id, family_id, user_id, token_hash, created_at, expires_at, used_at, revoked) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(token.id.to_string())
+        .bind(token.family_id.to_string())
+        .bind(token.user_id.to_string())
+        .bind(&token.token_hash)
+        .bind(token.created_at)
+        .bind(token.expires_at)
+        .bind(token.used_at)
+        .bind(token.revoked)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<RefreshToken>, StoreError> {
+        sqlx::query("SELECT * FROM refresh_tokens WHERE token_hash = ?")
+            .bind(token_hash)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(backend)?
+            .map(|row| refresh_token_from_row(&row))
+            .transpose()
+    }
+
+    async fn mark_used(&self, token_hash: &str, at: DateTime<Utc>) -> Result<bool, StoreError> {
+        let result = sqlx::query(
+            "UPDATE refresh_tokens SET used_at = ? WHERE token_hash = ? AND used_at IS NULL",
+        )
+        .bind(at)
+        .bind(token_hash)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn revoke_family(&self, family_id: Uuid) -> Result<(), StoreError> {
+        sqlx::query("UPDATE refresh_tokens SET revoked = TRUE WHERE family_id = ?")
+            .bind(family_id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn revoke_user(&self, user_id: Uuid) -> Result<(), StoreError> {
+        sqlx::query("UPDATE refresh_tokens SET revoked = TRUE WHERE user_id = ?")
+            .bind(user_id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, StoreError> {
+        let result = sqlx::query("DELETE FROM refresh_tokens WHERE expires_at <= ?")
+            .bind(now)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[async_trait]
+impl RevocationStore for SqliteStore {
+    async fn revoke_token(&self, jti: &str, expires_at: DateTime<Utc>) -> Result<(), StoreError> {
+        sqlx::query(
+            "INSERT INTO revoked_tokens (jti, expires_at) VALUES (?, ?) ON CONFLICT (jti) DO NOTHING",
+        )
+        .bind(jti)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn revoke_subject(
+        &self,
+        subject: &str,
+        revoked_at: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+        spared_jti: Option<&str>,
+    ) -> Result<(), StoreError> {
+        sqlx::query(
+            "INSERT INTO revoked_subjects (subject, revoked_at, expires_at, spared_jti) VALUES (?, ?, ?, ?) \
+             ON CONFLICT (subject) DO UPDATE SET revoked_at = excluded.revoked_at, expires_at = excluded.expires_at, \
+             spared_jti = excluded.spared_jti",
+        )
+        .bind(subject)
+        .bind(revoked_at)
+        .bind(expires_at)
+        .bind(spared_jti)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn is_revoked(&self, jti: &str, subject: &str, issued_at: i64) -> Result<bool, StoreError> {
+        // Timestamps are RFC 3339 text with a variable number of fractional
+        // digits, so compare them as whole seconds rather than strings.
+        let revoked: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM revoked_tokens WHERE jti = ?) \
+             OR EXISTS (SELECT 1 FROM revoked_subjects WHERE subject = ? \
+                        AND CAST(strftime('%s', revoked_at) AS INTEGER) >= ? AND spared_jti IS NOT ?)",
+        )
+        .bind(jti)
+        .bind(subject)
+        .bind(issued_at)
+        .bind(jti)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(backend)?;
+
+        Ok(revoked)
+    }
+
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, StoreError> {
+        let tokens = sqlx::query("DELETE FROM revoked_tokens WHERE expires_at <= ?")
+            .bind(now)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+        let subjects = sqlx::query("DELETE FROM revoked_subjects WHERE expires_at <= ?")
+            .bind(now)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+
+        Ok(tokens.rows_affected() + subjects.rows_affected())
+    }
+}
+
+#[async_trait]
+impl SigningKeyStore for SqliteStore {
+    async fn list(&self) -> Result<Vec<SigningKeyRecord>, StoreError> {
+        sqlx::query("SELECT * FROM signing_keys ORDER BY created_at")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(backend)?
+            .iter()
+            .map(signing_key_from_row)
+            .collect()
+    }
+
+    async fn insert(&self, key: SigningKeyRecord) -> Result<(), StoreError> {
+        sqlx::query(
+            "INSERT INTO signing_keys (kid, algorithm, private_key, created_at, activated_at, retired_at, verify_until) \
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&key.kid)
+        .bind(&key.algorithm)
+        .bind(&key.private_key)
+        .bind(key.created_at)
+        .bind(key.activated_at)
+        .bind(key.retired_at)
+        .bind(key.verify_until)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn replace_private_key(&self, kid: &str, private_key: &str) -> Result<(), StoreError> {
+        let result = sqlx::query("UPDATE signing_keys SET private_key = ? WHERE kid = ?")
+            .bind(private_key)
+            .bind(kid)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+
+        if result.rows_affected() == 0 {
+            return Err(StoreError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn promote(&self, kid: &str, at: DateTime<Utc>) -> Result<(), StoreError> {
+        let result = sqlx::query("UPDATE signing_keys SET activated_at = ? WHERE kid = ? AND retired_at IS NULL")
+            .bind(at)
+            .bind(kid)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+
+        if result.rows_affected() == 0 {
+            return Err(StoreError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn retire(&self, kid: &str, at: DateTime<Utc>, verify_until: DateTime<Utc>) -> Result<(), StoreError> {
+        let result = sqlx::query("UPDATE signing_keys SET retired_at = ?, verify_until = ? WHERE kid = ?")
+            .bind(at)
+            .bind(verify_until)
+            .bind(kid)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+
+        if result.rows_affected() == 0 {
+            return Err(StoreError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, StoreError> {
+        let result = sqlx::query("DELETE FROM signing_keys WHERE verify_until <= ?")
+            .bind(now)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[async_trait]
+impl ActionTokenStore for SqliteStore {
+    async fn insert(&self, token: ActionToken) -> Result<(), StoreError> {
+        sqlx::query(
+            "INSERT INTO action_tokens (token_hash, user_id, purpose, created_at, expires_at, used_at) \
+             VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&token.token_hash)
+        .bind(token.user_id.to_string())
+        .bind(token.purpose.as_str())
+        .bind(token.created_at)
+        .bind(token.expires_at)
+        .bind(token.used_at)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<ActionToken>, StoreError> {
+        sqlx::query("SELECT * FROM action_tokens WHERE token_hash = ?")
+            .bind(token_hash)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(backend)?
+            .map(|row| action_token_from_row(&row))
+            .transpose()
+    }
+
+    async fn mark_used(&self, token_hash: &str, at: DateTime<Utc>) -> Result<bool, StoreError> {
+        let result = sqlx::query(
+            "UPDATE action_tokens SET used_at = ? WHERE token_hash = ? AND used_at IS NULL",
+        )
+        .bind(at)
+        .bind(token_hash)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn last_issued(&self, user_id: Uuid, purpose: TokenPurpose) -> Result<Option<DateTime<Utc>>, StoreError> {
+        sqlx::query_scalar(
+            "SELECT created_at FROM action_tokens WHERE user_id = ? AND purpose = ? ORDER BY created_at DESC LIMIT 1",
+        )
+        .bind(user_id.to_string())
+        .bind(purpose.as_str())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(backend)
+    }
+
+    async fn revoke_user(&self, user_id: Uuid, purpose: TokenPurpose) -> Result<(), StoreError> {
+        sqlx::query("DELETE FROM action_tokens WHERE user_id = ? AND purpose = ? AND used_at IS NULL")
+            .bind(user_id.to_string())
+            .bind(purpose.as_str())
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, StoreError> {
+        let result = sqlx::query("DELETE FROM action_tokens WHERE expires_at <= ?")
+            .bind(now)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[async_trait]
+impl MfaStore for SqliteStore {
+    async fn find_totp(&self, user_id: Uuid) -> Result<Option<TotpCredential>, StoreError> {
+        sqlx::query("SELECT * FROM totp_credentials WHERE user_id = ?")
+            .bind(user_id.to_string())
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(backend)?
+            .map(|row| totp_credential_from_row(&row))
+            .transpose()
+    }
+
+    async fn begin_totp(&self, credential: TotpCredential) -> Result<(), StoreError> {
+        // An enabled secret is never overwritten; the upsert then changes nothing
+        let result = sqlx::query(
+            "INSERT INTO totp_credentials (user_id, secret, created_at, enabled_at, last_used_step) \
+             VALUES (?, ?, ?, NULL, NULL) \
+             ON CONFLICT (user_id) DO UPDATE SET secret = excluded.secret, created_at = excluded.created_at, \
+             last_used_step = NULL WHERE totp_credentials.enabled_at IS NULL",
+        )
+        .bind(credential.user_id.to_string())
+        .bind(&credential.secret)
+        .bind(credential.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+
+        if result.rows_affected() == 0 {
+            return Err(StoreError::Conflict);
+        }
+        Ok(())
+    }
+
+    async fn enable_totp(&self, user_id: Uuid, at: DateTime<Utc>) -> Result<bool, StoreError> {
+        let result = sqlx::query("UPDATE totp_credentials SET enabled_at = ? WHERE user_id = ? AND enabled_at IS NULL")
+            .bind(at)
+            .bind(user_id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn use_totp_step(&self, user_id: Uuid, step: i64) -> Result<bool, StoreError> {
+        let result = sqlx::query(
+            "UPDATE totp_credentials SET last_used_step = ? \
+             WHERE user_id = ? AND (last_used_step IS NULL OR last_used_step < ?)",
+        )
+        .bind(step)
+        .bind(user_id.to_string())
+        .bind(step)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn list_totp(&self) -> Result<Vec<TotpCredential>, StoreError> {
+        sqlx::query("SELECT * FROM totp_credentials")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(backend)?
+            .iter()
+            .map(totp_credential_from_row)
+            .collect()
+    }
+
+    async fn replace_totp_secret(&self, user_id: Uuid, current: &str, replacement: &str) -> Result<bool, StoreError> {
+        let result = sqlx::query("UPDATE totp_credentials SET secret = ? WHERE user_id = ? AND secret = ?")
+            .bind(replacement)
+            .bind(user_id.to_string())
+            .bind(current)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn disable_totp(&self, user_id: Uuid) -> Result<(), StoreError> {
+        let mut tx = self.pool.begin().await.map_err(backend)?;
+        sqlx::query("DELETE FROM recovery_codes WHERE user_id = ?")
+            .bind(user_id.to_string())
+            .execute(&mut *tx)
+            .await
+            .map_err(backend)?;
+        sqlx::query("DELETE FROM totp_credentials WHERE user_id = ?")
+            .bind(user_id.to_string())
+            .execute(&mut *tx)
+            .await
+            .map_err(backend)?;
+        tx.commit().await.map_err(backend)
+    }
+
+    async fn replace_recovery_codes(
+        &self,
+        user_id: Uuid,
+        code_hashes: &[String],
+        at: DateTime<Utc>,
+    ) -> Result<(), StoreError> {
+        let mut tx = self.pool.begin().await.map_err(backend)?;
+        sqlx::query("DELETE FROM recovery_codes WHERE user_id = ?")
+            .bind(user_id.to_string())
+            .execute(&mut *tx)
+            .await
+            .map_err(backend)?;
+        for code_hash in code_hashes {
+            sqlx::query("INSERT INTO recovery_codes (user_id, code_hash, created_at, used_at) VALUES (?, ?, ?, NULL)")
+                .bind(user_id.to_string())
+                .bind(code_hash)
+                .bind(at)
+                .execute(&mut *tx)
+                .await
+                .map_err(backend)?;
+        }
+        tx.commit().await.map_err(backend)
+    }
+
+    async fn use_recovery_code(&self, user_id: Uuid, code_hash: &str, at: DateTime<Utc>) -> Result<bool, StoreError> {
+        let result = sqlx::query(
+            "UPDATE recovery_codes SET used_at = ? WHERE user_id = ? AND code_hash = ? AND used_at IS NULL",
+        )
+        .bind(at)
+        .bind(user_id.to_string())
+        .bind(code_hash)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn remaining_recovery_codes(&self, user_id: Uuid) -> Result<u64, StoreError> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM recovery_codes WHERE user_id = ? AND used_at IS NULL")
+            .bind(user_id.to_string())
+            .fetch_one(&self.pool)
+            .await
+            .map_err(backend)?;
+        Ok(count as u64)
+    }
+}
+
+#[async_trait]
+impl PasskeyStore for SqliteStore {
+    async fn insert(&self, passkey: Passkey) -> Result<(), StoreError> {
+        sqlx::query(
+            "INSERT INTO passkeys (id, user_id, credential_id, public_key, sign_count, name, created_at, last_used_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(passkey.id.to_string())
+        .bind(passkey.user_id.to_string())
+        .bind(&passkey.credential_id)
+        .bind(&passkey.public_key)
+        .bind(passkey.sign_count as i64)
+        .bind(&passkey.name)
+        .bind(passkey.created_at)
+        .bind(passkey.last_used_at)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn find_by_credential_id(&self, credential_id: &str) -> Result<Option<Passkey>, StoreError> {
+        sqlx::query("SELECT * FROM passkeys WHERE credential_id = ?")
+            .bind(credential_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(backend)?
+            .map(|row| passkey_from_row(&row))
+            .transpose()
+    }
+
+    async fn list_for_user(&self, user_id: Uuid) -> Result<Vec<Passkey>, StoreError> {
+        sqlx::query("SELECT * FROM passkeys WHERE user_id = ? ORDER BY created_at")
+            .bind(user_id.to_string())
+            .fetch_all(&self.pool)
+            .await
+            .map_err(backend)?
+            .iter()
+            .map(passkey_from_row)
+            .collect()
+    }
+
+    async fn record_use(
+        &self,
+        id: Uuid,
+        previous_count: u32,
+        sign_count: u32,
+        at: DateTime<Utc>,
+    ) -> Result<bool, StoreError> {
+        let result = sqlx::query(
+            "UPDATE passkeys SET sign_count = ?, last_used_at = ? WHERE id = ? AND sign_count = ?",
+        )
+        .bind(sign_count as i64)
+        .bind(at)
+        .bind(id.to_string())
+        .bind(previous_count as i64)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn delete(&self, user_id: Uuid, id: Uuid) -> Result<(), StoreError> {
+        let result = sqlx::query("DELETE FROM passkeys WHERE id = ? AND user_id = ?")
+            .bind(id.to_string())
+            .bind(user_id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+
+        if result.rows_affected() == 0 {
+            return Err(StoreError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn insert_challenge(&self, challenge: PasskeyChallenge) -> Result<(), StoreError> {
+        sqlx::query("INSERT INTO passkey_challenges (challenge_hash, user_id, ceremony, expires_at) VALUES (?, ?, ?, ?)")
+            .bind(&challenge.challenge_hash)
+            .bind(challenge.user_id.map(|id| id.to_string()))
+            .bind(challenge.ceremony.as_str())
+            .bind(challenge.expires_at)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn take_challenge(&self, challenge_hash: &str) -> Result<Option<PasskeyChallenge>, StoreError> {
+        sqlx::query("DELETE FROM passkey_challenges WHERE challenge_hash = ? RETURNING *")
+            .bind(challenge_hash)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(backend)?
+            .map(|row| passkey_challenge_from_row(&row))
+            .transpose()
+    }
+
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, StoreError> {
+        let result = sqlx::query("DELETE FROM passkey_challenges WHERE expires_at <= ?")
+            .bind(now)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[async_trait]
+impl OAuthStore for SqliteStore {
+    async fn insert_client(&self, client: OAuthClient) -> Result<(), StoreError> {
+        sqlx::query(
+            "INSERT INTO oauth_clients (client_id, name, secret_hash, grant_types, redirect_uris, scopes, created_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&client.client_id)
+        .bind(&client.name)
+        .bind(&client.secret_hash)
+        .bind(client.grant_types.iter().map(GrantType::as_str).collect::<Vec<_>>().join(" "))
+        .bind(client.redirect_uris.join(" "))
+        .bind(client.scopes.join(" "))
+        .bind(client.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn find_client(&self, client_id: &str) -> Result<Option<OAuthClient>, StoreError> {
+        sqlx::query("SELECT * FROM oauth_clients WHERE client_id = ?")
+            .bind(client_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(backend)?
+            .map(|row| oauth_client_from_row(&row))
+            .transpose()
+    }
+
+    async fn list_clients(&self) -> Result<Vec<OAuthClient>, StoreError> {
+        sqlx::query("SELECT * FROM oauth_clients ORDER BY created_at")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(backend)?
+            .iter()
+            .map(oauth_client_from_row)
+            .collect()
+    }
+
+    async fn set_client_secret(&self, client_id: &str, secret_hash: &str) -> Result<(), StoreError> {
+        let result = sqlx::query("UPDATE oauth_clients SET secret_hash = ? WHERE client_id = ?")
+            .bind(secret_hash)
+            .bind(client_id)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+
+        if result.rows_affected() == 0 {
+            return Err(StoreError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn delete_client(&self, client_id: &str) -> Result<(), StoreError> {
+        let result = sqlx::query("DELETE FROM oauth_clients WHERE client_id = ?")
+            .bind(client_id)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+
+        if result.rows_affected() == 0 {
+            return Err(StoreError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn insert_code(&self, code: AuthorizationCode) -> Result<(), StoreError> {
+        sqlx::query(
+            "INSERT INTO oauth_authorization_codes \
+             (code_hash, client_id, user_id, redirect_uri, scope, code_challenge, nonce, token_id, created_at, expires_at, used_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&code.code_hash)
+        .bind(&code.client_id)
+        .bind(code.user_id.to_string())
+        .bind(&code.redirect_uri)
+        .bind(&code.scope)
+        .bind(&code.code_challenge)
+        .bind(&code.nonce)
+        .bind(&code.token_id)
+        .bind(code.created_at)
+        .bind(code.expires_at)
+        .bind(code.used_at)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn find_code(&self, code_hash: &str) -> Result<Option<AuthorizationCode>, StoreError> {
+        sqlx::query("SELECT * FROM oauth_authorization_codes WHERE code_hash = ?")
+            .bind(code_hash)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(backend)?
+            .map(|row| authorization_code_from_row(&row))
+            .transpose()
+    }
+
+    async fn mark_code_used(&self, code_hash: &str, at: DateTime<Utc>) -> Result<bool, StoreError> {
+        let result = sqlx::query(
+            "UPDATE oauth_authorization_codes SET used_at = ? WHERE code_hash = ? AND used_at IS NULL",
+        )
+        .bind(at)
+        .bind(code_hash)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn insert_device_authorization(&self, authorization: DeviceAuthorization) -> Result<(), StoreError> {
+        sqlx::query(
+            "INSERT INTO oauth_device_authorizations \
+             (device_code_hash, user_code_hash, client_id, scope, user_id, approved, interval_secs, last_polled_at, \
+             created_at, expires_at, used_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&authorization.device_code_hash)
+        .bind(&authorization.user_code_hash)
+        .bind(&authorization.client_id)
+        .bind(&authorization.scope)
+        .bind(authorization.user_id.map(|id| id.to_string()))
+        .bind(authorization.approved)
+        .bind(authorization.interval_secs as i64)
+        .bind(authorization.last_polled_at)
+        .bind(authorization.created_at)
+        .bind(authorization.expires_at)
+        .bind(authorization.used_at)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn find_device_authorization(&self, device_code_hash: &str) -> Result<Option<DeviceAuthorization>, StoreError> {
+        sqlx::query("SELECT * FROM oauth_device_authorizations WHERE device_code_hash = ?")
+            .bind(device_code_hash)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(backend)?
+            .map(|row| device_authorization_from_row(&row))
+            .transpose()
+    }
+
+    async fn find_device_authorization_by_user_code(
+        &self,
+        user_code_hash: &str,
+    ) -> Result<Option<DeviceAuthorization>, StoreError> {
+        sqlx::query("SELECT * FROM oauth_device_authorizations WHERE user_code_hash = ?")
+            .bind(user_code_hash)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(backend)?
+            .map(|row| device_authorization_from_row(&row))
+            .transpose()
+    }
+
+    async fn decide_device_authorization(
+        &self,
+        user_code_hash: &str,
+        user_id: Uuid,
+        approved: bool,
+        at: DateTime<Utc>,
+    ) -> Result<bool, StoreError> {
+        let result = sqlx::query(
+            "UPDATE oauth_device_authorizations SET user_id = ?, approved = ? \
+             WHERE user_code_hash = ? AND approved IS NULL AND expires_at > ?",
+        )
+        .bind(user_id.to_string())
+        .bind(approved)
+        .bind(user_code_hash)
+        .bind(at)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn record_device_poll(
+        &self,
+        device_code_hash: &str,
+        at: DateTime<Utc>,
+        interval_secs: u32,
+    ) -> Result<(), StoreError> {
+        sqlx::query(
+            "UPDATE oauth_device_authorizations SET last_polled_at = ?, interval_secs = ? WHERE device_code_hash = ?",
+        )
+        .bind(at)
+        .bind(interval_secs as i64)
+        .bind(device_code_hash)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn mark_device_authorization_used(&self, device_code_hash: &str, at: DateTime<Utc>) -> Result<bool, StoreError> {
+        let result = sqlx::query(
+            "UPDATE oauth_device_authorizations SET used_at = ? WHERE device_code_hash = ? AND used_at IS NULL",
+        )
+        .bind(at)
+        .bind(device_code_hash)
+        .execute(&self.pool)
+        .await
+        .map_err(backend)?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, StoreError> {
+        let codes = sqlx::query("DELETE FROM oauth_authorization_codes WHERE expires_at <= ?")
+            .bind(now)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+        let authorizations = sqlx::query("DELETE FROM oauth_device_authorizations WHERE expires_at <= ?")
+            .bind(now)
+            .execute(&self.pool)
+            .await
+            .map_err(backend)?;
+        Ok(codes.rows_affected() + authorizations.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn applies_every_migration() {
+        let store = SqliteStore::connect("sqlite::memory:").await.unwrap();
+        let applied: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM _sqlx_migrations WHERE success")
+            .fetch_one(&store.pool)
+            .await
+            .unwrap();
+        assert_eq!(applied as usize, sqlx::migrate!("./migrations/sqlite").iter().count());
+        assert!(UserStore::list(&store).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn numeric_user_ids_become_uuids() {
+        let path = std::env::temp_dir().join(format!("auth-api-test-{}.db", Uuid::new_v4()));
+        let path = path.to_string_lossy().into_owned();
+
+        // A database from before 0002, with gaps in its sequence
+        let pool = SqlitePoolOptions::new()
+            .connect_with(SqliteConnectOptions::new().filename(&path).create_if_missing(true))
+            .await
+            .unwrap();
+        let mut first = sqlx::migrate!("./migrations/sqlite");
+        first.migrations = std::borrow::Cow::Owned(first.migrations[..1].to_vec());
+        first.run(&pool).await.unwrap();
+        for (id, email) in [(1, "ada@example.com"), (2, "grace@example.com"), (7, "edsger@example.com")] {
+            sqlx::query("INSERT INTO users (id, email, first_name, last_name, password, role) VALUES (?, ?, 'A', 'B', 'h', 'User')")
+                .bind(id)
+                .bind(email)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        pool.close().await;
+
+        let store = SqliteStore::connect(&path).await.unwrap();
+        let rows: Vec<(String, Option<i64>, String)> = sqlx::query_as("SELECT id, legacy_id, email FROM users ORDER BY legacy_id")
+            .fetch_all(&store.pool)
+            .await
+            .unwrap();
+        let legacy: Vec<(Option<i64>, &str)> = rows.iter().map(|(_, legacy_id, email)| (*legacy_id, email.as_str())).collect();
+        assert_eq!(legacy, [(Some(1), "ada@example.com"), (Some(2), "grace@example.com"), (Some(7), "edsger@example.com")]);
+
+        let mut ids = std::collections::HashSet::new();
+        for (id, _, email) in &rows {
+            let id = Uuid::parse_str(id).unwrap();
+            assert_eq!(id.get_version_num(), 4);
+            assert_eq!(UserStore::find_by_email(&store, email).await.unwrap().unwrap().id, id);
+            assert_eq!(UserStore::find_by_id(&store, id).await.unwrap().unwrap().email, *email);
+            ids.insert(id);
+        }
+        assert_eq!(ids.len(), 3);
+
+        // Tables created after 0002 reference the new ids and cascade on them
+        let ada = Uuid::parse_str(&rows[0].0).unwrap();
+        let token = RefreshToken {
+            id: Uuid::new_v4(),
+            family_id: Uuid::new_v4(),
+            user_id: ada,
+            token_hash: "hash".to_string(),
+            created_at: Utc::now(),
+            expires_at: Utc::now() + chrono::Duration::days(1),
+            used_at: None,
+            revoked: false,
+        };
+        RefreshTokenStore::insert(&store, token.clone()).await.unwrap();
+        let orphan = RefreshToken { id: Uuid::new_v4(), user_id: Uuid::new_v4(), token_hash: "other".to_string(), ..token };
+        assert!(RefreshTokenStore::insert(&store, orphan).await.is_err());
+        UserStore::delete(&store, ada).await.unwrap();
+        assert!(RefreshTokenStore::find_by_hash(&store, "hash").await.unwrap().is_none());
+
+        // Users registered afterwards have no legacy id
+        let alan = NewUser {
+            email: "alan@example.com".to_string(),
+            first_name: "Alan".to_string(),
+            last_name: "Turing".to_string(),
+            password: "hash".to_string(),
+            role: Role::User,
+            email_verified: true,
+        };
+        let alan = UserStore::insert(&store, alan).await.unwrap();
+        let legacy_id: Option<i64> = sqlx::query_scalar("SELECT legacy_id FROM users WHERE id = ?")
+            .bind(alan.id.to_string())
+            .fetch_one(&store.pool)
+            .await
+            .unwrap();
+        assert_eq!(legacy_id, None);
+
+        store.pool.close().await;
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,173 @@
+//! Builds application state for unit tests: a fixed configuration, cheap
+//! password hashing and the in-memory mailer, over whichever store the
+//! test needs.
+
+use axum::response::Response;
+use jsonwebtoken::Algorithm;
+use serde_json::Value;
+use std::sync::Arc;
+
+use crate::{
+    breach::BreachedPasswords,
+    email::LocalPartFolding,
+    keys::{generate_key_material, KeyRing},
+    mail::MemoryOutbox,
+    middleware::auth::Claims,
+    models::{NewUser, Role, User},
+    password::PasswordPool,
+    store,
+    tokens::access_claims,
+    utils::{Argon2Settings, Config, MailBackend, PasswordPolicy, StorageBackend, UnverifiedLogin},
+    AppState,
+};
+
+pub const PASSWORD: &str = "correct horse battery staple";
+
+/// The configuration `load_env` gives an empty environment, minus the costly
+/// Argon2 parameters, and storing in `storage`.
+pub fn config(storage: StorageBackend) -> Config {
+    Config {
+        jwt_secret: "test-secret-that-is-long-enough-for-hs256".to_string(),
+        jwt_algorithm: Algorithm::HS256,
+        jwt_private_key_path: None,
+        jwt_key_id: None,
+        jwt_issuer: "http://localhost:3000".to_string(),
+        jwt_audience: "auth-api".to_string(),
+        jwt_expiration_secs: 900,
+        signing_key_encryption_key: None,
+        argon2: Argon2Settings { memory_kib: 1024, iterations: 1, parallelism: 1 },
+        password_hash_concurrency: 4,
+        password_policy: PasswordPolicy {
+            min_length: 8,
+            max_length: 128,
+            require_lowercase: false,
+            require_uppercase: false,
+            require_digit: false,
+            require_symbol: false,
+            disallow_personal_info: true,
+        },
+        breached_passwords_path: None,
+        refresh_token_expiration_secs: 2592000,
+        storage,
+        mail: MailBackend::Memory,
+        mail_from: "Auth API <no-reply@localhost>".to_string(),
+        app_url: "http://localhost:5173".to_string(),
+        password_reset_ttl_secs: 3600,
+        email_verification_ttl_secs: 86400,
+        verification_resend_interval_secs: 60,
+        unverified_login: UnverifiedLogin::Restricted,
+        conceal_existing_accounts: false,
+        email_local_part_folding: LocalPartFolding::Lowercase,
+        mfa_issuer: "Auth API".to_string(),
+        mfa_challenge_ttl_secs: 300,
+        webauthn_rp_id: "localhost".to_string(),
+        webauthn_rp_name: "Auth API".to_string(),
+        webauthn_origins: vec!["http://localhost:5173".to_string()],
+        webauthn_challenge_ttl_secs: 300,
+        oauth_clients: Vec::new(),
+        oauth_code_ttl_secs: 60,
+        oauth_device_code_ttl_secs: 600,
+        oauth_device_poll_interval_secs: 5,
+    }
+}
+
+/// State over a fresh in-memory store.
+pub async fn memory_state() -> AppState {
+    state(config(StorageBackend::Memory)).await
+}
+
+/// State over a fresh in-memory store, signing with a new ES256 key so it
+/// can issue ID tokens.
+pub async fn es256_state() -> AppState {
+    let pem = generate_key_material(Algorithm::ES256).expect("failed to generate a key");
+    let path = std::env::temp_dir().join(format!("auth-api-test-{}.pem", uuid::Uuid::new_v4()));
+    std::fs::write(&path, pem).expect("failed to write the key");
+
+    let mut config = config(StorageBackend::Memory);
+    config.jwt_algorithm = Algorithm::ES256;
+    config.jwt_private_key_path = Some(path.to_string_lossy().into_owned());
+    let state = state(config).await;
+    let _ = std::fs::remove_file(path);
+    state
+}
+
+/// State over a fresh in-memory SQLite database, migrations applied.
+pub async fn sqlite_state() -> AppState {
+    let mut config = config(StorageBackend::Sqlite { path: "sqlite::memory:".to_string() });
+    config.signing_key_encryption_key = Some("MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY=".to_string());
+    state(config).await
+}
+
+/// A URL for a fresh, empty schema of the database in `DATABASE_URL`, or
+/// `None` when it isn't set and Postgres tests should be skipped. Schemas are
+/// left behind, so point it at a throwaway database.
+pub async fn postgres_url() -> Option<String> {
+    let database_url = std::env::var("DATABASE_URL").ok().filter(|url| !url.is_empty())?;
+    let schema = format!("auth_api_test_{}", uuid::Uuid::new_v4().simple());
+    let pool = sqlx::PgPool::connect(&database_url).await.expect("failed to connect to DATABASE_URL");
+    sqlx::query(&format!("CREATE SCHEMA {}", schema)).execute(&pool).await.expect("failed to create a test schema");
+    pool.close().await;
+
+    let separator = if database_url.contains('?') { '&' } else { '?' };
+    Some(format!("{}{}options=-c%20search_path%3D{}", database_url, separator, schema))
+}
+
+/// State over a fresh Postgres schema, migrations applied, or `None` when
+/// `DATABASE_URL` isn't set.
+pub async fn postgres_state() -> Option<AppState> {
+    let database_url = postgres_url().await?;
+    let mut config = config(StorageBackend::Postgres { database_url, max_connections: 2 });
+    config.signing_key_encryption_key = Some("MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY=".to_string());
+    Some(state(config).await)
+}
+
+/// Connects the stores and keys `config` describes, like `main` does.
+pub async fn state(config: Config) -> AppState {
+    let stores = store::connect(&config.storage).await.expect("failed to connect the store");
+    let keys = KeyRing::bootstrap(&config, stores.signing_keys).await.expect("failed to load signing keys");
+    AppState {
+        passwords: Arc::new(
+            PasswordPool::new(config.argon2.clone(), config.password_hash_concurrency)
+                .expect("failed to set up password hashing"),
+        ),
+        config: Arc::new(config),
+        keys: Arc::new(keys),
+        breached_passwords: Arc::new(BreachedPasswords::Disabled),
+        mailer: Arc::new(MemoryOutbox::default()),
+        users: stores.users,
+        refresh_tokens: stores.refresh_tokens,
+        revocations: stores.revocations,
+        action_tokens: stores.action_tokens,
+        mfa: stores.mfa,
+        passkeys: stores.passkeys,
+        oauth: stores.oauth,
+    }
+}
+
+/// Registers a verified user whose password is [`PASSWORD`].
+pub async fn user(state: &AppState, email: &str) -> User {
+    state
+        .users
+        .insert(NewUser {
+            email: email.to_string(),
+            first_name: "Ada".to_string(),
+            last_name: "Lovelace".to_string(),
+            password: state.passwords.hash(PASSWORD).await.expect("failed to hash password"),
+            role: Role::User,
+            email_verified: true,
+        })
+        .await
+        .expect("failed to insert user")
+}
+
+/// Claims of a fresh first-party access token for `user`, as `auth_middleware`
+/// hands them to protected handlers.
+pub fn claims(state: &AppState, user: &User) -> Arc<Claims> {
+    Arc::new(access_claims(&state.config, user, chrono::Utc::now()))
+}
+
+/// Reads a JSON response body.
+pub async fn json_body(response: Response) -> Value {
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.expect("failed to read body");
+    serde_json::from_slice(&bytes).expect("body is not JSON")
+}
@@ -0,0 +1,196 @@
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{decode_header, Algorithm, Validation};
+use serde::{de::DeserializeOwned, Serialize};
+use uuid::Uuid;
+
+use crate::middleware::auth::{Claims, ClientClaims};
+use crate::models::{ActionToken, IdTokenClaims, LoginResponse, OAuthClient, RefreshToken, TokenPurpose, User};
+use crate::oauth::{client_subject, user_info};
+use crate::store::StoreError;
+use crate::utils::{generate_opaque_token, hash_token, Config};
+use crate::AppState;
+
+/// Claims of an access token for `user` issued at `now`. Lifetime, issuer and
+/// audience all come from the configuration.
+pub fn access_claims(config: &Config, user: &User, now: DateTime<Utc>) -> Claims {
+    Claims {
+        sub: user.id.to_string(),
+        email: user.email.clone(),
+        first_name: user.first_name.clone(),
+        last_name: user.last_name.clone(),
+        role: user.role.clone(),
+        email_verified: user.email_verified,
+        iss: config.jwt_issuer.clone(),
+        aud: config.jwt_audience.clone(),
+        exp: (now + Duration::seconds(config.jwt_expiration_secs as i64)).timestamp() as usize,
+        nbf: now.timestamp() as usize,
+        iat: now.timestamp() as usize,
+        jti: Uuid::new_v4().to_string(),
+        client_id: None,
+        scope: None,
+    }
+}
+
+/// Claims of an access token `client` requested for itself, carrying `scope`.
+pub fn client_claims(config: &Config, client: &OAuthClient, scope: &str, now: DateTime<Utc>) -> ClientClaims {
+    ClientClaims {
+        sub: client_subject(&client.client_id),
+        client_id: client.client_id.clone(),
+        scope: scope.to_string(),
+        iss: config.jwt_issuer.clone(),
+        aud: config.jwt_audience.clone(),
+        exp: (now + Duration::seconds(config.jwt_expiration_secs as i64)).timestamp() as usize,
+        nbf: now.timestamp() as usize,
+        iat: now.timestamp() as usize,
+        jti: Uuid::new_v4().to_string(),
+    }
+}
+
+/// Claims of an ID token telling `client_id` that `user` logged in, with the
+/// user claims `scope` allows and the `nonce` of the authorization request.
+pub fn id_token_claims(
+    config: &Config,
+    user: &User,
+    client_id: &str,
+    scope: &str,
+    nonce: Option<String>,
+    now: DateTime<Utc>,
+) -> IdTokenClaims {
+    IdTokenClaims {
+        iss: config.jwt_issuer.clone(),
+        aud: client_id.to_string(),
+        exp: (now + Duration::seconds(config.jwt_expiration_secs as i64)).timestamp() as usize,
+        iat: now.timestamp() as usize,
+        nonce,
+        user: user_info(user, Some(scope)),
+    }
+}
+
+/// Signs an access token for `user` with the current signing key.
+pub fn mint_access_token(state: &AppState, user: &User, now: DateTime<Utc>) -> String {
+    sign_token(state, &access_claims(&state.config, user, now))
+}
+
+/// Signs access token `claims` with the current signing key.
+pub fn sign_token<T: Serialize>(state: &AppState, claims: &T) -> String {
+    // Keys are validated when loaded into the keyset, so signing cannot fail
+    state.keys.current().signer().encode(claims).expect("failed to sign token")
+}
+
+/// Signs ID token `claims` with the current signing key, or returns `None`
+/// if that key is HS256 and clients could not verify the result.
+pub fn sign_id_token(state: &AppState, claims: &IdTokenClaims) -> Option<String> {
+    let keys = state.keys.current();
+    let key = keys.id_token_signer()?;
+    Some(key.encode(claims).expect("failed to sign token"))
+}
+
+/// How `auth_middleware` checks access tokens: signature, expiry, not-before,
+/// and that they were issued by and for this deployment.
+pub fn access_token_validation(config: &Config, algorithm: Algorithm) -> Validation {
+    let mut validation = Validation::new(algorithm);
+    validation.set_issuer(&[&config.jwt_issuer]);
+    validation.set_audience(&[&config.jwt_audience]);
+    validation.set_required_spec_claims(&["exp", "nbf", "iss", "aud", "sub"]);
+    validation.validate_nbf = true;
+    validation
+}
+
+/// Checks a token's signature, lifetime, issuer and audience like
+/// `auth_middleware` does and returns its claims, or `None` if any check
+/// fails or the claims don't have the shape of `T`. Revocations are left to
+/// the caller.
+pub fn decode_access_token<T: DeserializeOwned>(state: &AppState, token: &str) -> Option<T> {
+    let header = decode_header(token).ok()?;
+    let keys = state.keys.current();
+    let key = keys.verifier(header.kid.as_deref())?;
+    key.decode::<T>(token, &access_token_validation(&state.config, key.algorithm))
+        .ok()
+        .map(|data| data.claims)
+}
+
+/// Mints a short-lived access token for `user` together with a new refresh
+/// token. Rotation passes the `family_id` of the refresh token being replaced;
+/// a fresh login starts a new family.
+pub async fn issue_tokens(
+    state: &AppState,
+    user: &User,
+    family_id: Option<Uuid>,
+    message: &str,
+) -> Result<LoginResponse, StoreError> {
+    let now = Utc::now();
+    let access_token = mint_access_token(state, user, now);
+    issue_session(state, user, family_id, access_token, message, now).await
+}
+
+/// Like [`issue_tokens`] for a new login, with `jti` as the access token's id,
+/// so the caller can refer to the session before handing it out.
+pub async fn issue_tokens_with_jti(
+    state: &AppState,
+    user: &User,
+    jti: String,
+    message: &str,
+) -> Result<LoginResponse, StoreError> {
+    let now = Utc::now();
+    let access_token = sign_token(state, &Claims { jti, ..access_claims(&state.config, user, now) });
+    issue_session(state, user, None, access_token, message, now).await
+}
+
+/// Stores a refresh token to go with `access_token`.
+async fn issue_session(
+    state: &AppState,
+    user: &User,
+    family_id: Option<Uuid>,
+    access_token: String,
+    message: &str,
+    now: DateTime<Utc>,
+) -> Result<LoginResponse, StoreError> {
+    let config = &state.config;
+
+    let refresh_token = generate_opaque_token();
+    state
+        .refresh_tokens
+        .insert(RefreshToken {
+            id: Uuid::now_v7(),
+            family_id: family_id.unwrap_or_else(Uuid::now_v7),
+            user_id: user.id,
+            token_hash: hash_token(&refresh_token),
+            created_at: now,
+            expires_at: now + Duration::seconds(config.refresh_token_expiration_secs as i64),
+            used_at: None,
+            revoked: false,
+        })
+        .await?;
+
+    Ok(LoginResponse {
+        access_token,
+        message: message.to_string(),
+        token_type: "Bearer".to_string(),
+        refresh_token,
+        expires_in: config.jwt_expiration_secs as u64,
+    })
+}
+
+/// Stores a new single-use token for a link mailed to `user` and returns the
+/// opaque token to put in the link. It expires `ttl_secs` from now.
+pub async fn issue_action_token(
+    state: &AppState,
+    user: &User,
+    purpose: TokenPurpose,
+    ttl_secs: u32,
+) -> Result<String, StoreError> {
+    let token = generate_opaque_token();
+    let now = Utc::now();
+    state
+        .action_tokens
+        .insert(ActionToken {
+            token_hash: hash_token(&token),
+            user_id: user.id,
+            purpose,
+            created_at: now,
+            expires_at: now + Duration::seconds(ttl_secs as i64),
+            used_at: None,
+        })
+        .await?;
+    Ok(token)
+}
@@ -0,0 +1,172 @@
+use chrono::{DateTime, Utc};
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use rand::{Rng, RngCore};
+use sha1::Sha1;
+use uuid::Uuid;
+
+use crate::utils::constant_time_eq;
+
+/// Parameters every authenticator app assumes when the otpauth URI leaves them
+/// out: HMAC-SHA1, 6 digits, 30 second steps (RFC 6238).
+pub const DIGITS: usize = 6;
+pub const PERIOD_SECS: i64 = 30;
+/// Steps either side of the current one that are still accepted, for clock drift.
+const ALLOWED_SKEW: i64 = 1;
+/// 160 bits, the length of an HMAC-SHA1 key (RFC 4226 section 4).
+const SECRET_BYTES: usize = 20;
+
+pub const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+const RECOVERY_CODE_LENGTH: usize = 10;
+
+/// What a user's secret is sealed under at rest, so it can't be copied onto
+/// another account.
+pub fn sealing_label(user_id: Uuid) -> String {
+    format!("totp:{}", user_id)
+}
+
+/// A fresh random secret, base32-encoded as authenticator apps expect it.
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; SECRET_BYTES];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    BASE32_NOPAD.encode(&bytes)
+}
+
+/// The time step `at` falls in.
+pub fn step_at(at: DateTime<Utc>) -> i64 {
+    at.timestamp().div_euclid(PERIOD_SECS)
+}
+
+/// The code for `step` (RFC 4226 HOTP with the step as counter).
+pub fn code_at(key: &[u8], step: i64) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(&step.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    // Dynamic truncation
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let value = u32::from_be_bytes([digest[offset], digest[offset + 1], digest[offset + 2], digest[offset + 3]])
+        & 0x7fff_ffff;
+    format!("{:0width$}", value % 10u32.pow(DIGITS as u32), width = DIGITS)
+}
+
+/// Checks `code` against the steps around `now` and returns the one it
+/// belongs to. Callers must reject steps at or before the last one accepted,
+/// so a code can't be replayed.
+pub fn matching_step(secret: &str, code: &str, now: DateTime<Utc>) -> Option<i64> {
+    let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+    if code.len() != DIGITS || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let key = BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+
+    let current = step_at(now);
+    (current - ALLOWED_SKEW..=current + ALLOWED_SKEW)
+        .find(|step| constant_time_eq(code_at(&key, *step).as_bytes(), code.as_bytes()))
+}
+
+/// The `otpauth://` URI that authenticator apps import, usually from a QR code
+/// (Key Uri Format of Google Authenticator).
+pub fn provisioning_uri(issuer: &str, account: &str, secret: &str) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        percent_encode(issuer),
+        percent_encode(account),
+        secret,
+        percent_encode(issuer),
+        DIGITS,
+        PERIOD_SECS
+    )
+}
+
+/// A set of one-time recovery codes such as `k7mq2-xv9rd`, shown to the user once.
+pub fn generate_recovery_codes() -> Vec<String> {
+    let mut rng = rand::thread_rng();
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let code: String = (0..RECOVERY_CODE_LENGTH)
+                .map(|_| RECOVERY_CODE_ALPHABET[rng.gen_range(0..RECOVERY_CODE_ALPHABET.len())] as char)
+                .collect();
+            format!("{}-{}", &code[..RECOVERY_CODE_LENGTH / 2], &code[RECOVERY_CODE_LENGTH / 2..])
+        })
+        .collect()
+}
+
+/// The form recovery codes are hashed in: no separators, lowercase.
+pub fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Whether `input` looks like a TOTP code rather than a recovery code.
+pub fn is_totp_code(input: &str) -> bool {
+    let digits: Vec<char> = input.chars().filter(|c| !c.is_whitespace()).collect();
+    digits.len() == DIGITS && digits.iter().all(|c| c.is_ascii_digit())
+}
+
+/// Percent-encodes everything but RFC 3986 unreserved characters.
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// The SHA1 key of the RFC 6238 test vectors.
+    const RFC_KEY: &[u8] = b"12345678901234567890";
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(secs, 0).unwrap()
+    }
+
+    #[test]
+    fn matches_the_rfc_6238_vectors() {
+        // Appendix B lists 8 digits; 6-digit codes are their last six
+        let vectors = [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+            (20000000000, "353130"),
+        ];
+        for (time, code) in vectors {
+            assert_eq!(code_at(RFC_KEY, step_at(at(time))), code, "at {}", time);
+        }
+    }
+
+    #[test]
+    fn accepts_one_step_either_side() {
+        let secret = BASE32_NOPAD.encode(RFC_KEY);
+        let now = at(1111111111);
+        let current = step_at(now);
+
+        for offset in -1..=1 {
+            let code = code_at(RFC_KEY, current + offset);
+            assert_eq!(matching_step(&secret, &code, now), Some(current + offset));
+        }
+        for offset in [-2, 2] {
+            assert_eq!(matching_step(&secret, &code_at(RFC_KEY, current + offset), now), None);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_codes() {
+        let secret = BASE32_NOPAD.encode(RFC_KEY);
+        let now = at(59);
+        assert_eq!(matching_step(&secret, "287 082", now), Some(1));
+        assert_eq!(matching_step(&secret, "28708", now), None);
+        assert_eq!(matching_step(&secret, "28708a", now), None);
+        assert_eq!(matching_step("not base32!", "287082", now), None);
+    }
+}
@@ -1,41 +1,458 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use dotenvy::dotenv;
+use jsonwebtoken::Algorithm;
+use rand::RngCore;
 use sha2::{Sha256, Digest};
 
+use crate::email::LocalPartFolding;
+use crate::models::{GrantType, OAuthClient};
+use crate::oauth::SUPPORTED_SCOPES;
+
+/// Where user accounts are persisted, selected with `USER_STORE`.
+#[derive(Debug, Clone)]
+pub enum StorageBackend {
+    /// Users live in process memory and are lost on restart.
+    Memory,
+    /// Users live in a SQLite file (`SQLITE_PATH`), or `:memory:` for a throwaway database.
+    Sqlite { path: String },
+    /// Users live in PostgreSQL (`DATABASE_URL`), shared by every API instance.
+    Postgres { database_url: String, max_connections: u32 },
+}
+
+impl StorageBackend {
+    /// Short name safe to log (the Postgres URL may carry credentials).
+    pub fn name(&self) -> &'static str {
+        match self {
+            StorageBackend::Memory => "memory",
+            StorageBackend::Sqlite { .. } => "sqlite",
+            StorageBackend::Postgres { .. } => "postgres",
+        }
+    }
+}
+
+/// Argon2id cost parameters for new password hashes. Hashes made with other
+/// values are upgraded the next time their owner logs in.
+#[derive(Debug, Clone)]
+pub struct Argon2Settings {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+/// Rules every new password must satisfy, wherever it is set.
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub require_lowercase: bool,
+    pub require_uppercase: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+    /// Rejects passwords containing the user's email or names.
+    pub disallow_personal_info: bool,
+}
+
+/// What logging in gives a user who hasn't verified their email, selected
+/// with `UNVERIFIED_LOGIN`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnverifiedLogin {
+    /// A normal access token.
+    Allow,
+    /// An access token that only reaches the profile and logout endpoints.
+    Restricted,
+}
+
+/// How account notices are delivered, selected with `MAILER`.
+#[derive(Debug, Clone)]
+pub enum MailBackend {
+    /// Messages are only written to the log.
+    Log,
+    /// Messages are appended as JSON lines to `MAIL_OUTBOX_PATH`.
+    File { path: String },
+    /// Messages are kept in process memory and listed by `GET /admin/outbox`.
+    Memory,
+    /// Messages are delivered through an SMTP relay.
+    Smtp(SmtpSettings),
+}
+
+/// How the SMTP connection is secured, selected with `SMTP_TLS`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SmtpTls {
+    /// Plain connection upgraded with STARTTLS (usually port 587).
+    StartTls,
+    /// TLS from the first byte (usually port 465).
+    Wrapper,
+    /// No encryption, for local test servers only.
+    None,
+}
+
+#[derive(Debug, Clone)]
+pub struct SmtpSettings {
+    pub host: String,
+    pub port: u16,
+    pub tls: SmtpTls,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Config {
-    pub jwt_salt: [u8; 16],
     pub jwt_secret: String,
+    pub jwt_algorithm: Algorithm,
+    pub jwt_private_key_path: Option<String>,
+    pub jwt_key_id: Option<String>,
+    pub jwt_issuer: String,
+    pub jwt_audience: String,
     pub jwt_expiration_secs: u32,
+    /// Base64-encoded 256-bit key that encrypts signing keys in the store.
+    pub signing_key_encryption_key: Option<String>,
+    pub argon2: Argon2Settings,
+    pub password_hash_concurrency: usize,
+    pub password_policy: PasswordPolicy,
+    /// Local list or filter of breached password hashes; see `breach.rs`.
+    pub breached_passwords_path: Option<String>,
+    pub refresh_token_expiration_secs: u32,
+    pub storage: StorageBackend,
+    pub mail: MailBackend,
+    /// Sender of every message, e.g. `Auth API <no-reply@example.com>`.
+    pub mail_from: String,
+    /// Base URL of the frontend, used to build the links in emails.
+    pub app_url: String,
+    pub password_reset_ttl_secs: u32,
+    pub email_verification_ttl_secs: u32,
+    /// Minimum time between two verification emails to the same account.
+    pub verification_resend_interval_secs: u32,
+    pub unverified_login: UnverifiedLogin,
+    /// Registering an existing email answers exactly like a new one and mails
+    /// the owner instead, so the endpoint can't be used to probe for accounts.
+    pub conceal_existing_accounts: bool,
+    pub email_local_part_folding: LocalPartFolding,
+    /// Name authenticator apps show next to the account, e.g. `Example Corp`.
+    pub mfa_issuer: String,
+    /// How long a login has to present its second factor after the password.
+    pub mfa_challenge_ttl_secs: u32,
+    /// Domain passkeys are scoped to; the frontend must be served from it or a subdomain.
+    pub webauthn_rp_id: String,
+    /// Name browsers show when creating a passkey.
+    pub webauthn_rp_name: String,
+    /// Origins the browser may report in WebAuthn responses.
+    pub webauthn_origins: Vec<String>,
+    pub webauthn_challenge_ttl_secs: u32,
+    /// Public clients from `OAUTH_CLIENTS`, added to the client registry at
+    /// startup unless a client with the same id is already registered.
+    pub oauth_clients: Vec<OAuthClient>,
+    /// How long an authorization code can be exchanged; codes are meant to be redeemed at once.
+    pub oauth_code_ttl_secs: u32,
+    /// How long the user has to approve a device authorization request.
+    pub oauth_device_code_ttl_secs: u32,
+    /// Seconds a device must wait between polls of the token endpoint.
+    pub oauth_device_poll_interval_secs: u32,
 }
 
 pub fn load_env() -> Config {
     dotenv().ok();
 
-    let jwt_salt_str = std::env::var("JWT_SALT")
-        .unwrap_or_else(|_| "your-super-secret-salt-here-change-in-production".to_string());
-    
-    // Hash the salt string to get exactly 16 bytes
-    let mut hasher = Sha256::new();
-    hasher.update(jwt_salt_str.as_bytes());
-    let hash_result = hasher.finalize();
-    let mut jwt_salt = [0u8; 16];
-    jwt_salt.copy_from_slice(&hash_result[..16]);
-
     let jwt_secret = std::env::var("JWT_SECRET")
         .unwrap_or_else(|_| "your-super-secret-jwt-key-here-change-in-production".to_string());
+    let jwt_algorithm = match std::env::var("JWT_ALGORITHM")
+        .unwrap_or_else(|_| "HS256".to_string())
+        .as_str()
+    {
+        "HS256" => Algorithm::HS256,
+        "RS256" => Algorithm::RS256,
+        "ES256" => Algorithm::ES256,
+        "EdDSA" => Algorithm::EdDSA,
+        other => panic!("Unsupported JWT_ALGORITHM '{}', expected HS256, RS256, ES256 or EdDSA", other),
+    };
+    let jwt_private_key_path = std::env::var("JWT_PRIVATE_KEY_PATH").ok();
+    let jwt_key_id = std::env::var("JWT_KEY_ID").ok();
+    // Give every environment its own values so tokens minted for one are rejected by another
+    let jwt_issuer = std::env::var("JWT_ISSUER").unwrap_or_else(|_| "http://localhost:3000".to_string());
+    let jwt_audience = std::env::var("JWT_AUDIENCE").unwrap_or_else(|_| "auth-api".to_string());
+
+    let signing_key_encryption_key = std::env::var("SIGNING_KEY_ENCRYPTION_KEY").ok().filter(|k| !k.is_empty());
+
     let jwt_expiration_secs = std::env::var("JWT_EXPIRATION_SECS")
+        .unwrap_or_else(|_| "900".to_string())
+        .parse::<u32>()
+        .unwrap_or(900);
+    let refresh_token_expiration_secs = std::env::var("REFRESH_TOKEN_EXPIRATION_SECS")
+        .unwrap_or_else(|_| "2592000".to_string())
+        .parse::<u32>()
+        .unwrap_or(2592000);
+
+    // Defaults follow the OWASP recommendation for Argon2id (19 MiB, 2 passes, 1 lane)
+    let argon2 = Argon2Settings {
+        memory_kib: std::env::var("ARGON2_MEMORY_KIB")
+            .unwrap_or_else(|_| "19456".to_string())
+            .parse::<u32>()
+            .unwrap_or(19456),
+        iterations: std::env::var("ARGON2_ITERATIONS")
+            .unwrap_or_else(|_| "2".to_string())
+            .parse::<u32>()
+            .unwrap_or(2),
+        parallelism: std::env::var("ARGON2_PARALLELISM")
+            .unwrap_or_else(|_| "1".to_string())
+            .parse::<u32>()
+            .unwrap_or(1),
+    };
+    if let Err(e) = argon2::Params::new(argon2.memory_kib, argon2.iterations, argon2.parallelism, None) {
+        panic!("Invalid Argon2 parameters: {}", e);
+    }
+
+    // Each hash in flight holds a blocking thread and `memory_kib` of memory
+    let default_concurrency = std::thread::available_parallelism().map_or(4, |n| n.get());
+    let password_hash_concurrency = std::env::var("PASSWORD_HASH_CONCURRENCY")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(default_concurrency);
+
+    let env_flag = |name: &str, default: bool| {
+        std::env::var(name).map_or(default, |v| v == "true" || v == "1")
+    };
+    let password_policy = PasswordPolicy {
+        min_length: std::env::var("PASSWORD_MIN_LENGTH")
+            .unwrap_or_else(|_| "8".to_string())
+            .parse::<usize>()
+            .unwrap_or(8),
+        max_length: std::env::var("PASSWORD_MAX_LENGTH")
+            .unwrap_or_else(|_| "128".to_string())
+            .parse::<usize>()
+            .unwrap_or(128),
+        require_lowercase: env_flag("PASSWORD_REQUIRE_LOWERCASE", false),
+        require_uppercase: env_flag("PASSWORD_REQUIRE_UPPERCASE", false),
+        require_digit: env_flag("PASSWORD_REQUIRE_DIGIT", false),
+        require_symbol: env_flag("PASSWORD_REQUIRE_SYMBOL", false),
+        disallow_personal_info: env_flag("PASSWORD_DISALLOW_PERSONAL_INFO", true),
+    };
+    if password_policy.min_length > password_policy.max_length {
+        panic!("PASSWORD_MIN_LENGTH must not exceed PASSWORD_MAX_LENGTH");
+    }
+    let breached_passwords_path = std::env::var("BREACHED_PASSWORDS_PATH").ok().filter(|p| !p.is_empty());
+
+    let database_url = std::env::var("DATABASE_URL").ok();
+    let default_store = if database_url.is_some() { "postgres" } else { "memory" };
+
+    let storage = match std::env::var("USER_STORE")
+        .unwrap_or_else(|_| default_store.to_string())
+        .to_lowercase()
+        .as_str()
+    {
+        "memory" => StorageBackend::Memory,
+        "sqlite" => StorageBackend::Sqlite {
+            path: std::env::var("SQLITE_PATH").unwrap_or_else(|_| "auth.db".to_string()),
+        },
+        "postgres" => StorageBackend::Postgres {
+            database_url: database_url.expect("DATABASE_URL must be set when USER_STORE=postgres"),
+            max_connections: std::env::var("DATABASE_MAX_CONNECTIONS")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse::<u32>()
+                .unwrap_or(10),
+        },
+        other => panic!("Unsupported USER_STORE '{}', expected 'memory', 'sqlite' or 'postgres'", other),
+    };
+    if signing_key_encryption_key.is_none() && !matches!(storage, StorageBackend::Memory) {
+        panic!(
+            "SIGNING_KEY_ENCRYPTION_KEY must be set when USER_STORE={}: generate one with `openssl rand -base64 32`. \
+             Signing keys and TOTP secrets stored in the clear by earlier versions are encrypted with it at startup.",
+            storage.name()
+        );
+    }
+
+    let mail = match std::env::var("MAILER")
+        .unwrap_or_else(|_| "log".to_string())
+        .to_lowercase()
+        .as_str()
+    {
+        "log" => MailBackend::Log,
+        "file" => MailBackend::File {
+            path: std::env::var("MAIL_OUTBOX_PATH").unwrap_or_else(|_| "outbox.jsonl".to_string()),
+        },
+        "memory" => MailBackend::Memory,
+        "smtp" => {
+            let tls = match std::env::var("SMTP_TLS")
+                .unwrap_or_else(|_| "starttls".to_string())
+                .to_lowercase()
+                .as_str()
+            {
+                "starttls" => SmtpTls::StartTls,
+                "tls" => SmtpTls::Wrapper,
+                "none" => SmtpTls::None,
+                other => panic!("Unsupported SMTP_TLS '{}', expected 'starttls', 'tls' or 'none'", other),
+            };
+            let default_port = match tls {
+                SmtpTls::StartTls => 587,
+                SmtpTls::Wrapper => 465,
+                SmtpTls::None => 25,
+            };
+            MailBackend::Smtp(SmtpSettings {
+                host: std::env::var("SMTP_HOST").expect("SMTP_HOST must be set when MAILER=smtp"),
+                port: std::env::var("SMTP_PORT")
+                    .ok()
+                    .and_then(|p| p.parse::<u16>().ok())
+                    .unwrap_or(default_port),
+                tls,
+                username: std::env::var("SMTP_USERNAME").ok(),
+                password: std::env::var("SMTP_PASSWORD").ok(),
+            })
+        }
+        other => panic!("Unsupported MAILER '{}', expected 'log', 'file', 'memory' or 'smtp'", other),
+    };
+    let mail_from = std::env::var("MAIL_FROM").unwrap_or_else(|_| "Auth API <no-reply@localhost>".to_string());
+    let app_url = std::env::var("APP_URL")
+        .unwrap_or_else(|_| "http://localhost:5173".to_string())
+        .trim_end_matches('/')
+        .to_string();
+    let password_reset_ttl_secs = std::env::var("PASSWORD_RESET_TTL_SECS")
+        .unwrap_or_else(|_| "3600".to_string())
+        .parse::<u32>()
+        .unwrap_or(3600);
+    let email_verification_ttl_secs = std::env::var("EMAIL_VERIFICATION_TTL_SECS")
         .unwrap_or_else(|_| "86400".to_string())
         .parse::<u32>()
         .unwrap_or(86400);
+    let verification_resend_interval_secs = std::env::var("VERIFICATION_RESEND_INTERVAL_SECS")
+        .unwrap_or_else(|_| "60".to_string())
+        .parse::<u32>()
+        .unwrap_or(60);
+    let unverified_login = match std::env::var("UNVERIFIED_LOGIN")
+        .unwrap_or_else(|_| "restricted".to_string())
+        .to_lowercase()
+        .as_str()
+    {
+        "allow" => UnverifiedLogin::Allow,
+        "restricted" => UnverifiedLogin::Restricted,
+        other => panic!("Unsupported UNVERIFIED_LOGIN '{}', expected 'allow' or 'restricted'", other),
+    };
+
+    let conceal_existing_accounts = env_flag("CONCEAL_EXISTING_ACCOUNTS", false);
+    let email_local_part_folding = match std::env::var("EMAIL_LOCAL_PART_FOLDING")
+        .unwrap_or_else(|_| "lowercase".to_string())
+        .to_lowercase()
+        .as_str()
+    {
+        "none" => LocalPartFolding::None,
+        "lowercase" => LocalPartFolding::Lowercase,
+        other => panic!("Unsupported EMAIL_LOCAL_PART_FOLDING '{}', expected 'none' or 'lowercase'", other),
+    };
+    let mfa_issuer = std::env::var("MFA_ISSUER").unwrap_or_else(|_| "Auth API".to_string());
+    let mfa_challenge_ttl_secs = std::env::var("MFA_CHALLENGE_TTL_SECS")
+        .unwrap_or_else(|_| "300".to_string())
+        .parse::<u32>()
+        .unwrap_or(300);
+
+    // Passkeys are bound to the frontend's host unless told otherwise
+    let app_host = app_url
+        .split("://")
+        .nth(1)
+        .and_then(|rest| rest.split(['/', ':']).next())
+        .unwrap_or("localhost")
+        .to_string();
+    let webauthn_rp_id = std::env::var("WEBAUTHN_RP_ID").unwrap_or(app_host);
+    let webauthn_rp_name = std::env::var("WEBAUTHN_RP_NAME").unwrap_or_else(|_| "Auth API".to_string());
+    let webauthn_origins = std::env::var("WEBAUTHN_ORIGINS")
+        .map(|origins| {
+            origins
+                .split(',')
+                .map(|o| o.trim().trim_end_matches('/').to_string())
+                .filter(|o| !o.is_empty())
+                .collect()
+        })
+        .unwrap_or_else(|_| vec![app_url.clone()]);
+    let webauthn_challenge_ttl_secs = std::env::var("WEBAUTHN_CHALLENGE_TTL_SECS")
+        .unwrap_or_else(|_| "300".to_string())
+        .parse::<u32>()
+        .unwrap_or(300);
+
+    // `client_id=redirect_uri [redirect_uri...]`, clients separated by `;`
+    let oauth_clients = std::env::var("OAUTH_CLIENTS")
+        .unwrap_or_default()
+        .split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (client_id, uris) = entry
+                .split_once('=')
+                .unwrap_or_else(|| panic!("Invalid OAUTH_CLIENTS entry '{}', expected client_id=redirect_uri", entry));
+            let redirect_uris: Vec<String> = uris.split_whitespace().map(str::to_string).collect();
+            if client_id.trim().is_empty() || redirect_uris.is_empty() {
+                panic!("Invalid OAUTH_CLIENTS entry '{}', expected client_id=redirect_uri", entry);
+            }
+            OAuthClient {
+                client_id: client_id.trim().to_string(),
+                name: client_id.trim().to_string(),
+                secret_hash: None,
+                grant_types: vec![GrantType::AuthorizationCode],
+                redirect_uris,
+                scopes: SUPPORTED_SCOPES.iter().map(|s| s.to_string()).collect(),
+                created_at: chrono::Utc::now(),
+            }
+        })
+        .collect();
+    let oauth_code_ttl_secs = std::env::var("OAUTH_CODE_TTL_SECS")
+        .unwrap_or_else(|_| "60".to_string())
+        .parse::<u32>()
+        .unwrap_or(60);
+    let oauth_device_code_ttl_secs = std::env::var("OAUTH_DEVICE_CODE_TTL_SECS")
+        .unwrap_or_else(|_| "600".to_string())
+        .parse::<u32>()
+        .unwrap_or(600);
+    let oauth_device_poll_interval_secs = std::env::var("OAUTH_DEVICE_POLL_INTERVAL_SECS")
+        .unwrap_or_else(|_| "5".to_string())
+        .parse::<u32>()
+        .unwrap_or(5);
 
     Config {
-        jwt_salt,
         jwt_secret,
+        jwt_algorithm,
+        jwt_private_key_path,
+        jwt_key_id,
+        jwt_issuer,
+        jwt_audience,
         jwt_expiration_secs,
+        signing_key_encryption_key,
+        argon2,
+        password_hash_concurrency,
+        password_policy,
+        breached_passwords_path,
+        refresh_token_expiration_secs,
+        storage,
+        mail,
+        mail_from,
+        app_url,
+        password_reset_ttl_secs,
+        email_verification_ttl_secs,
+        verification_resend_interval_secs,
+        unverified_login,
+        conceal_existing_accounts,
+        email_local_part_folding,
+        mfa_issuer,
+        mfa_challenge_ttl_secs,
+        webauthn_rp_id,
+        webauthn_rp_name,
+        webauthn_origins,
+        webauthn_challenge_ttl_secs,
+        oauth_clients,
+        oauth_code_ttl_secs,
+        oauth_device_code_ttl_secs,
+        oauth_device_poll_interval_secs,
     }
 }
 
-pub fn is_valid_email(email: &str) -> bool {
-    // Simple regex, use a crate like "validator" for production
-    email.contains('@') && email.contains('.')
-}
\ No newline at end of file
+/// Generates an opaque, URL-safe token with 256 bits of randomness.
+pub fn generate_opaque_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Hashes an opaque token for storage, so a leaked table can't be replayed.
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Compares secrets without leaking through timing how much of them matched.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
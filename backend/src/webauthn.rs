@@ -0,0 +1,264 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ciborium::value::Value;
+use jsonwebtoken::{crypto, Algorithm, DecodingKey};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+use crate::models::{AuthenticationCredential, RegistrationCredential};
+
+#[derive(Debug, Clone)]
+pub struct WebauthnError(pub String);
+
+impl fmt::Display for WebauthnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid WebAuthn response: {}", self.0)
+    }
+}
+
+impl std::error::Error for WebauthnError {}
+
+/// COSE algorithm identifiers we offer at registration, in order of preference.
+pub const COSE_ES256: i64 = -7;
+pub const COSE_EDDSA: i64 = -8;
+pub const COSE_RS256: i64 = -257;
+pub const SUPPORTED_ALGORITHMS: [i64; 3] = [COSE_ES256, COSE_EDDSA, COSE_RS256];
+
+// Authenticator data flags (WebAuthn section 6.1)
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_ATTESTED_CREDENTIAL: u8 = 0x40;
+
+/// Who responses must be addressed to: the RP ID whose hash the authenticator
+/// signs, and the origins the browser may report.
+pub struct RelyingParty<'a> {
+    pub id: &'a str,
+    pub origins: &'a [String],
+}
+
+/// A credential that passed every registration check.
+pub struct VerifiedRegistration {
+    pub challenge: String,
+    /// Base64url-encoded credential id.
+    pub credential_id: String,
+    pub public_key: Vec<u8>,
+    pub sign_count: u32,
+}
+
+/// An assertion whose signature checked out against the stored public key.
+pub struct VerifiedAssertion {
+    pub challenge: String,
+    pub sign_count: u32,
+    pub user_verified: bool,
+}
+
+#[derive(Deserialize)]
+struct ClientData {
+    #[serde(rename = "type")]
+    kind: String,
+    challenge: String,
+    origin: String,
+    #[serde(rename = "crossOrigin", default)]
+    cross_origin: bool,
+}
+
+struct AuthenticatorData {
+    rp_id_hash: [u8; 32],
+    flags: u8,
+    sign_count: u32,
+    /// Credential id and COSE public key, present at registration.
+    attested: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+fn decode(field: &str, value: &str) -> Result<Vec<u8>, WebauthnError> {
+    URL_SAFE_NO_PAD
+        .decode(value.trim_end_matches('='))
+        .map_err(|_| WebauthnError(format!("{} is not base64url", field)))
+}
+
+/// Checks `clientDataJSON` (WebAuthn section 7.1 steps 5-12) and returns the
+/// challenge it answers together with its raw bytes.
+fn check_client_data(
+    rp: &RelyingParty,
+    encoded: &str,
+    expected_type: &str,
+) -> Result<(String, Vec<u8>), WebauthnError> {
+    let raw = decode("clientDataJSON", encoded)?;
+    let client_data: ClientData =
+        serde_json::from_slice(&raw).map_err(|e| WebauthnError(format!("malformed clientDataJSON: {}", e)))?;
+
+    if client_data.kind != expected_type {
+        return Err(WebauthnError(format!("expected type {}, got {}", expected_type, client_data.kind)));
+    }
+    if !rp.origins.contains(&client_data.origin) {
+        return Err(WebauthnError(format!("origin {} is not allowed", client_data.origin)));
+    }
+    if client_data.cross_origin {
+        return Err(WebauthnError("cross-origin ceremonies are not allowed".to_string()));
+    }
+    Ok((client_data.challenge, raw))
+}
+
+fn parse_authenticator_data(data: &[u8]) -> Result<AuthenticatorData, WebauthnError> {
+    let truncated = || WebauthnError("authenticator data is truncated".to_string());
+    if data.len() < 37 {
+        return Err(truncated());
+    }
+    let rp_id_hash: [u8; 32] = data[..32].try_into().expect("length checked");
+    let flags = data[32];
+    let sign_count = u32::from_be_bytes(data[33..37].try_into().expect("length checked"));
+
+    let attested = if flags & FLAG_ATTESTED_CREDENTIAL != 0 {
+        // AAGUID (16 bytes), then a length-prefixed credential id, then the COSE key
+        let rest = data.get(37 + 16..).ok_or_else(truncated)?;
+        let id_len = u16::from_be_bytes(rest.get(..2).ok_or_else(truncated)?.try_into().expect("length checked")) as usize;
+        let credential_id = rest.get(2..2 + id_len).ok_or_else(truncated)?.to_vec();
+
+        // The key is a CBOR item of unknown length; extensions may follow it
+        let mut key_bytes = &rest[2 + id_len..];
+        let before = key_bytes.len();
+        let _: Value = ciborium::de::from_reader(&mut key_bytes)
+            .map_err(|e| WebauthnError(format!("malformed credential public key: {}", e)))?;
+        let consumed = before - key_bytes.len();
+        Some((credential_id, rest[2 + id_len..2 + id_len + consumed].to_vec()))
+    } else {
+        None
+    };
+
+    Ok(AuthenticatorData { rp_id_hash, flags, sign_count, attested })
+}
+
+fn check_rp_id_hash(rp: &RelyingParty, data: &AuthenticatorData) -> Result<(), WebauthnError> {
+    if data.rp_id_hash[..] != Sha256::digest(rp.id.as_bytes())[..] {
+        return Err(WebauthnError(format!("credential is not scoped to {}", rp.id)));
+    }
+    if data.flags & FLAG_USER_PRESENT == 0 {
+        return Err(WebauthnError("user was not present".to_string()));
+    }
+    Ok(())
+}
+
+/// Runs the registration checks of WebAuthn section 7.1. The caller still has
+/// to match the returned challenge against one it issued.
+///
+/// We ask for `attestation: "none"`, so attestation statements are not
+/// verified: any authenticator the user owns is accepted.
+pub fn verify_registration(
+    rp: &RelyingParty,
+    credential: &RegistrationCredential,
+) -> Result<VerifiedRegistration, WebauthnError> {
+    if credential.kind != "public-key" {
+        return Err(WebauthnError(format!("unexpected credential type {}", credential.kind)));
+    }
+    let (challenge, _) = check_client_data(rp, &credential.response.client_data_json, "webauthn.create")?;
+
+    let attestation = decode("attestationObject", &credential.response.attestation_object)?;
+    let attestation: Value = ciborium::de::from_reader(attestation.as_slice())
+        .map_err(|e| WebauthnError(format!("malformed attestationObject: {}", e)))?;
+    let auth_data = map_get(&attestation, &Value::Text("authData".to_string()))
+        .and_then(Value::as_bytes)
+        .ok_or_else(|| WebauthnError("attestationObject has no authData".to_string()))?;
+
+    let data = parse_authenticator_data(auth_data)?;
+    check_rp_id_hash(rp, &data)?;
+    let (credential_id, public_key) = data
+        .attested
+        .ok_or_else(|| WebauthnError("no attested credential data".to_string()))?;
+
+    let credential_id = URL_SAFE_NO_PAD.encode(credential_id);
+    if credential_id != credential.raw_id.trim_end_matches('=') {
+        return Err(WebauthnError("rawId does not match the attested credential".to_string()));
+    }
+    // Rejects keys we couldn't verify signatures with later
+    cose_decoding_key(&public_key)?;
+
+    Ok(VerifiedRegistration {
+        challenge,
+        credential_id,
+        public_key,
+        sign_count: data.sign_count,
+    })
+}
+
+/// Runs the authentication checks of WebAuthn section 7.2 against the stored
+/// `public_key`. The caller still has to match the challenge and the
+/// signature counter.
+pub fn verify_assertion(
+    rp: &RelyingParty,
+    credential: &AuthenticationCredential,
+    public_key: &[u8],
+) -> Result<VerifiedAssertion, WebauthnError> {
+    if credential.kind != "public-key" {
+        return Err(WebauthnError(format!("unexpected credential type {}", credential.kind)));
+    }
+    let (challenge, client_data) = check_client_data(rp, &credential.response.client_data_json, "webauthn.get")?;
+
+    let auth_data = decode("authenticatorData", &credential.response.authenticator_data)?;
+    let data = parse_authenticator_data(&auth_data)?;
+    check_rp_id_hash(rp, &data)?;
+
+    // The signature covers authenticatorData || SHA-256(clientDataJSON)
+    let mut signed = auth_data;
+    signed.extend_from_slice(&Sha256::digest(&client_data));
+    let signature = decode("signature", &credential.response.signature)?;
+
+    let (key, algorithm) = cose_decoding_key(public_key)?;
+    let signature = match algorithm {
+        // WebAuthn ES256 signatures are DER; jsonwebtoken wants the fixed r || s form
+        Algorithm::ES256 => p256::ecdsa::Signature::from_der(&signature)
+            .map_err(|_| WebauthnError("malformed ES256 signature".to_string()))?
+            .to_bytes()
+            .to_vec(),
+        _ => signature,
+    };
+    let valid = crypto::verify(&URL_SAFE_NO_PAD.encode(signature), &signed, &key, algorithm)
+        .map_err(|e| WebauthnError(format!("cannot check signature: {}", e)))?;
+    if !valid {
+        return Err(WebauthnError("signature does not verify".to_string()));
+    }
+
+    Ok(VerifiedAssertion {
+        challenge,
+        sign_count: data.sign_count,
+        user_verified: data.flags & FLAG_USER_VERIFIED != 0,
+    })
+}
+
+fn map_get<'a>(map: &'a Value, key: &Value) -> Option<&'a Value> {
+    map.as_map()?.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+}
+
+/// Turns a COSE_Key into a key jsonwebtoken can verify signatures with.
+fn cose_decoding_key(cose: &[u8]) -> Result<(DecodingKey, Algorithm), WebauthnError> {
+    let key: Value = ciborium::de::from_reader(cose)
+        .map_err(|e| WebauthnError(format!("malformed credential public key: {}", e)))?;
+    let param = |label: i64| map_get(&key, &Value::Integer(label.into()));
+    let integer = |label: i64| param(label).and_then(Value::as_integer).map(i128::from);
+    let bytes = |label: i64| {
+        param(label)
+            .and_then(Value::as_bytes)
+            .map(|b| URL_SAFE_NO_PAD.encode(b))
+            .ok_or_else(|| WebauthnError(format!("credential public key lacks parameter {}", label)))
+    };
+    let unusable = |e: jsonwebtoken::errors::Error| WebauthnError(format!("unusable credential public key: {}", e));
+
+    // Labels from RFC 9053: 1 kty, 3 alg, -1 crv / n, -2 x / e, -3 y
+    match (integer(1), integer(3).map(|alg| alg as i64), integer(-1)) {
+        (Some(2), Some(COSE_ES256), Some(1)) => Ok((
+            DecodingKey::from_ec_components(&bytes(-2)?, &bytes(-3)?).map_err(unusable)?,
+            Algorithm::ES256,
+        )),
+        (Some(1), Some(COSE_EDDSA), Some(6)) => Ok((
+            DecodingKey::from_ed_components(&bytes(-2)?).map_err(unusable)?,
+            Algorithm::EdDSA,
+        )),
+        (Some(3), Some(COSE_RS256), _) => Ok((
+            DecodingKey::from_rsa_components(&bytes(-1)?, &bytes(-2)?).map_err(unusable)?,
+            Algorithm::RS256,
+        )),
+        (kty, alg, _) => Err(WebauthnError(format!(
+            "unsupported credential key (kty {:?}, alg {:?})",
+            kty, alg
+        ))),
+    }
+}
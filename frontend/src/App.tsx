@@ -6,11 +6,14 @@ import { Toaster as Sonner } from "@/components/ui/sonner";
 import { AuthProvider } from "@/hooks/useAuth";
 import ProtectedRoute from "@/components/ProtectedRoute";
 import Login from "./pages/Login";
+import LoginMfa from "./pages/LoginMfa";
 import Register from "./pages/Register";
 import Profile from "./pages/Profile";
 import { ThemeProvider } from "@/hooks/useTheme";
 import Navbar from "@/components/Navbar";
 import AdminDashboard from "./pages/AdminDashboard";
+import OAuthAuthorize from "./pages/OAuthAuthorize";
+import DeviceAuthorize from "./pages/DeviceAuthorize";
 
 const queryClient = new QueryClient();
 const App = () => (
@@ -25,6 +28,7 @@ const App = () => (
             <div className="pt-16"> {/* Add padding for fixed navbar */}
               <Routes>
                 <Route path="/login" element={<Login />} />
+                <Route path="/login/mfa" element={<LoginMfa />} />
                 <Route path="/register" element={<Register />} />
                 <Route 
                   path="/profile" 
@@ -42,6 +46,22 @@ const App = () => (
                     </ProtectedRoute>
                   } 
                 />
+                <Route 
+                  path="/oauth/authorize" 
+                  element={
+                    <ProtectedRoute>
+                      <OAuthAuthorize />
+                    </ProtectedRoute>
+                  } 
+                />
+                <Route 
+                  path="/device" 
+                  element={
+                    <ProtectedRoute>
+                      <DeviceAuthorize />
+                    </ProtectedRoute>
+                  } 
+                />
                 <Route path="/" element={<Navigate to="/profile" replace />} />
                 <Route path="*" element={<Navigate to="/login" replace />} />
               </Routes>
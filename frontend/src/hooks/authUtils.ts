@@ -1,35 +1,50 @@
-import { User } from '../../../ts-client/api';
+import { LoginResponse, User } from '../../ts-client/api';
 
 export const TOKEN_KEY = 'auth_token';
+export const REFRESH_TOKEN_KEY = 'auth_refresh_token';
 
 export const getToken = (): string | null => {
   return localStorage.getItem(TOKEN_KEY);
 };
 
+export const getRefreshToken = (): string | null => {
+  return localStorage.getItem(REFRESH_TOKEN_KEY);
+};
+
+export const storeTokens = (tokens: LoginResponse) => {
+  localStorage.setItem(TOKEN_KEY, tokens.access_token);
+  localStorage.setItem(REFRESH_TOKEN_KEY, tokens.refresh_token);
+};
+
 export const clearAuthData = () => {
   localStorage.removeItem(TOKEN_KEY);
+  localStorage.removeItem(REFRESH_TOKEN_KEY);
 };
 
-export const isTokenExpired = (token: string): boolean => {
+// Whether the token expires within `seconds` (or already has)
+export const expiresWithin = (token: string, seconds: number): boolean => {
   if (!token) return true;
   try {
     const payload = JSON.parse(atob(token.split('.')[1]));
     const currentTime = Date.now() / 1000;
-    return payload.exp < currentTime;
+    return payload.exp < currentTime + seconds;
   } catch {
     return true;
   }
 };
 
+export const isTokenExpired = (token: string): boolean => expiresWithin(token, 0);
+
 export function decodeUserFromToken(token: string): User | null {
   try {
     const payload = JSON.parse(atob(token.split('.')[1]));
     return {
-      id: parseInt(payload.sub) || 0, // Convert JWT subject (user ID) to number
+      id: payload.sub || "", // JWT subject is the user's UUID
       email: payload.email || "",
       first_name: payload.first_name || "",
       last_name: payload.last_name || "",
       role: payload.role || "User",
+      email_verified: payload.email_verified ?? true, // tokens from before verification existed omit it
       password: "", // password is not included in the JWT, so we return an empty string
     };
   } catch {
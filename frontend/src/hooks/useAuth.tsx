@@ -1,12 +1,13 @@
 import { createContext, useContext, useState, useEffect, ReactNode, useCallback } from 'react';
 import { useToast } from '@/hooks/use-toast';
-import { AuthApi, Configuration, User, LoginRequest, RegisterRequest } from '../../ts-client/api';
-import { getToken, clearAuthData, isTokenExpired, decodeUserFromToken, TOKEN_KEY } from './authUtils';
+import { AuthApi, Configuration, User, LoginRequest, LoginResponse, MfaChallengeResponse, MfaLoginRequest, RegisterRequest } from '../../ts-client/api';
+import { getToken, getRefreshToken, storeTokens, clearAuthData, expiresWithin, isTokenExpired, decodeUserFromToken } from './authUtils';
 import { useNavigate } from "react-router-dom";
 
 interface AuthContextType {
   user: User | null;
-  login: (credentials: LoginRequest) => Promise<void>;
+  login: (credentials: LoginRequest, redirectTo?: string) => Promise<void>;
+  verifyMfa: (request: MfaLoginRequest, redirectTo?: string) => Promise<void>;
   register: (userData: RegisterRequest) => Promise<void>;
   logout: () => void;
   isLoading: boolean;
@@ -14,10 +15,36 @@ interface AuthContextType {
 
 const AuthContext = createContext<AuthContextType | undefined>(undefined);
 
+const API_BASE_URL = import.meta.env.VITE_API_BASE_URL || 'http://localhost:3000';
+
 const api = new AuthApi(new Configuration({ 
-  basePath: import.meta.env.VITE_API_BASE_URL || 'http://localhost:3000' 
+  basePath: API_BASE_URL 
 }));
 
+// Renew access tokens this long before they expire
+const REFRESH_MARGIN_SECS = 120;
+
+// Trades the refresh token for new tokens; returns the new access token, or
+// null if the session can't be renewed. Refresh tokens are single-use and
+// presenting one twice ends the session, so tabs take turns and reuse a token
+// another tab just got.
+const refreshTokens = async (): Promise<string | null> => {
+  const run = async () => {
+    const token = getToken();
+    if (token && !expiresWithin(token, REFRESH_MARGIN_SECS)) return token;
+    const refreshToken = getRefreshToken();
+    if (!refreshToken) return null;
+    try {
+      const response = await api.refresh({ refresh_token: refreshToken });
+      storeTokens(response.data);
+      return response.data.access_token;
+    } catch {
+      return null;
+    }
+  };
+  return navigator.locks ? navigator.locks.request('auth-refresh', run) : run();
+};
+
 export const AuthProvider = ({ children }: { children: ReactNode }) => {
   const [user, setUser] = useState<User | null>(null);
   const [isLoading, setIsLoading] = useState(true);
@@ -25,16 +52,29 @@ export const AuthProvider = ({ children }: { children: ReactNode }) => {
   const navigate = useNavigate();
 
   useEffect(() => {
-    const token = getToken();
-    if (token && !isTokenExpired(token)) {
-      setUser(decodeUserFromToken(token));
-    } else {
-      clearAuthData();
-    }
-    setIsLoading(false);
+    const restore = async () => {
+      const token = getToken();
+      const current = token && !isTokenExpired(token) ? token : await refreshTokens();
+      if (current) {
+        setUser(decodeUserFromToken(current));
+      } else {
+        clearAuthData();
+      }
+      setIsLoading(false);
+    };
+    restore();
   }, []);
 
   const logout = useCallback(() => {
+    // End the session server-side too, so its refresh token can't be used again
+    const token = getToken();
+    if (token && !isTokenExpired(token)) {
+      fetch(`${API_BASE_URL}/logout`, {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json', Authorization: `Bearer ${token}` },
+        body: JSON.stringify({ refresh_token: getRefreshToken() }),
+      }).catch(() => {});
+    }
     clearAuthData();
     setUser(null);
     toast({
@@ -46,9 +86,14 @@ export const AuthProvider = ({ children }: { children: ReactNode }) => {
 
   useEffect(() => {
     if (!user) return;
-    const checkTokenExpiry = () => {
+    const checkTokenExpiry = async () => {
       const token = getToken();
-      if (token && isTokenExpired(token)) {
+      if (!token || !expiresWithin(token, REFRESH_MARGIN_SECS)) return;
+      const renewed = await refreshTokens();
+      if (renewed) {
+        setUser(decodeUserFromToken(renewed));
+      } else if (isTokenExpired(token)) {
+        // Failed refreshes are retried on the next check until the token runs out
         logout();
         toast({
           title: "Session Expired",
@@ -62,18 +107,27 @@ export const AuthProvider = ({ children }: { children: ReactNode }) => {
     return () => clearInterval(interval);
   }, [user, toast, logout]);
 
-  const login = async (credentials: LoginRequest) => {
+  const startSession = (tokens: LoginResponse, redirectTo: string) => {
+    storeTokens(tokens);
+    setUser(decodeUserFromToken(tokens.access_token));
+    toast({
+      title: "Welcome back!",
+      description: "You have been successfully logged in."
+    });
+    navigate(redirectTo);
+  };
+
+  const login = async (credentials: LoginRequest, redirectTo = "/profile") => {
     setIsLoading(true);
     try {
       const response = await api.login(credentials);
-      localStorage.setItem(TOKEN_KEY, response.data.access_token);
-      setUser(decodeUserFromToken(response.data.access_token));
-      toast({
-        title: "Welcome back!",
-        description: "You have been successfully logged in."
-      });
-      console.log("Navigating to /profile");
-      navigate("/profile");
+      const data = response.data as LoginResponse | MfaChallengeResponse;
+      if ('mfa_required' in data) {
+        // The password was right; the login completes with a code at /login/mfa
+        navigate("/login/mfa", { state: { mfaToken: data.mfa_token, from: redirectTo } });
+        return;
+      }
+      startSession(data, redirectTo);
     } catch (error) {
       toast({
         title: "Login Failed",
@@ -87,11 +141,36 @@ export const AuthProvider = ({ children }: { children: ReactNode }) => {
     }
   };
 
+  const verifyMfa = async (request: MfaLoginRequest, redirectTo = "/profile") => {
+    setIsLoading(true);
+    try {
+      const res = await fetch(`${API_BASE_URL}/login/mfa`, {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify(request),
+      });
+      const data = await res.json();
+      if (!res.ok) throw new Error(data.error || 'Invalid code');
+      startSession(data as LoginResponse, redirectTo);
+    } finally {
+      setIsLoading(false);
+    }
+  };
+
   const register = async (userData: RegisterRequest) => {
     setIsLoading(true);
     try {
       const response = await api.register(userData);
-      localStorage.setItem(TOKEN_KEY, response.data.access_token);
+      if (!response.data.access_token) {
+        // The backend may conceal whether the email was taken; log in separately
+        toast({
+          title: "Registration Received",
+          description: response.data.message
+        });
+        navigate("/login");
+        return;
+      }
+      storeTokens(response.data);
       setUser(decodeUserFromToken(response.data.access_token));
       toast({
         title: "Account Created",
@@ -111,7 +190,7 @@ export const AuthProvider = ({ children }: { children: ReactNode }) => {
   };
 
   return (
-    <AuthContext.Provider value={{ user, login, register, logout, isLoading }}>
+    <AuthContext.Provider value={{ user, login, verifyMfa, register, logout, isLoading }}>
       {children}
     </AuthContext.Provider>
   );
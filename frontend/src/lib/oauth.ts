@@ -0,0 +1,12 @@
+// What each OAuth scope lets a client see, as shown on consent pages
+export const SCOPE_DESCRIPTIONS: Record<string, string> = {
+  openid: "Confirm who you are when you sign in to it",
+  profile: "See your name",
+  email: "See your email address and whether it is verified",
+};
+
+export const describeScope = (scope: string) => SCOPE_DESCRIPTIONS[scope] ?? scope;
+
+// Space-separated scopes, defaulting like the backend does
+export const splitScopes = (scope: string | null | undefined) =>
+  (scope || "profile").split(" ").filter(Boolean);
@@ -0,0 +1,157 @@
+/* eslint-disable @typescript-eslint/no-explicit-any */
+import { useCallback, useEffect, useState } from 'react';
+import { useSearchParams } from 'react-router-dom';
+import { Card, CardHeader, CardTitle, CardContent } from '@/components/ui/card';
+import { Label } from '@/components/ui/label';
+import { Input } from '@/components/ui/input';
+import { Button } from '@/components/ui/button';
+import { useToast } from '@/hooks/use-toast';
+import { useAuth } from '@/hooks/useAuth';
+import { getToken } from '@/hooks/authUtils';
+import { describeScope, splitScopes } from '@/lib/oauth';
+import { MonitorSmartphone, Check, X } from 'lucide-react';
+
+const API_BASE_URL = import.meta.env.VITE_API_BASE_URL || 'http://localhost:3000';
+
+interface DeviceRequest {
+  client_id: string;
+  client_name: string;
+  scope: string;
+  expires_at: string;
+}
+
+const DeviceAuthorize = () => {
+  const { user } = useAuth();
+  const { toast } = useToast();
+  const [searchParams] = useSearchParams();
+  const [userCode, setUserCode] = useState(searchParams.get('user_code') || '');
+  const [request, setRequest] = useState<DeviceRequest | null>(null);
+  const [answer, setAnswer] = useState<boolean | null>(null);
+  const [loading, setLoading] = useState(false);
+
+  const lookUp = useCallback(async (code: string) => {
+    setLoading(true);
+    try {
+      const res = await fetch(`${API_BASE_URL}/oauth/device?user_code=${encodeURIComponent(code)}`, {
+        headers: { Authorization: `Bearer ${getToken()}` },
+      });
+      const data = await res.json();
+      if (!res.ok) throw new Error(data.error || 'Invalid or expired user code');
+      setRequest(data);
+    } catch (err: any) {
+      toast({ title: 'Code not found', description: err.message, variant: 'destructive' });
+    } finally {
+      setLoading(false);
+    }
+  }, [toast]);
+
+  // The complete verification URI carries the code, so there is nothing to type
+  useEffect(() => {
+    const code = searchParams.get('user_code');
+    if (code) lookUp(code);
+  }, [searchParams, lookUp]);
+
+  const decide = async (approve: boolean) => {
+    setLoading(true);
+    try {
+      const res = await fetch(`${API_BASE_URL}/oauth/device`, {
+        method: 'POST',
+        headers: {
+          'Content-Type': 'application/json',
+          Authorization: `Bearer ${getToken()}`,
+        },
+        body: JSON.stringify({ user_code: userCode, approve }),
+      });
+      const data = await res.json();
+      if (!res.ok) throw new Error(data.error || 'Invalid or expired user code');
+      setAnswer(approve);
+    } catch (err: any) {
+      toast({ title: 'Authorization failed', description: err.message, variant: 'destructive' });
+    } finally {
+      setLoading(false);
+    }
+  };
+
+  const handleSubmit = (e: React.FormEvent) => {
+    e.preventDefault();
+    lookUp(userCode);
+  };
+
+  return (
+    <div className="min-h-screen flex items-center justify-center bg-gradient-to-br from-gray-50 to-purple-50 dark:from-gray-900 dark:to-gray-800">
+      <Card className="w-full max-w-md rounded-2xl shadow-lg border-0 bg-white dark:bg-gray-900">
+        <CardHeader className="flex flex-col items-center gap-2 pt-8 pb-2">
+          <MonitorSmartphone className="h-10 w-10 text-purple-500" />
+          <CardTitle className="text-2xl font-bold text-purple-700 dark:text-purple-300 text-center">
+            {request ? <>Allow <span className="font-mono">{request.client_name}</span> on your device?</> : 'Connect a device'}
+          </CardTitle>
+          <p className="text-gray-500 dark:text-gray-300 text-sm">Signed in as {user?.email}</p>
+        </CardHeader>
+        <CardContent className="space-y-5 pt-2 pb-6 px-8">
+          {answer !== null ? (
+            <p className="text-center text-gray-600 dark:text-gray-300">
+              {answer ? 'Device approved. You can return to it now.' : 'Device denied. It will not get access to your account.'}
+            </p>
+          ) : request ? (
+            <>
+              <div>
+                <p className="font-semibold text-gray-700 dark:text-gray-200 mb-2">It will be able to:</p>
+                <ul className="space-y-1 text-gray-600 dark:text-gray-300">
+                  {splitScopes(request.scope).map(scope => (
+                    <li key={scope} className="flex items-center gap-2">
+                      <Check className="h-4 w-4 text-green-500" /> {describeScope(scope)}
+                    </li>
+                  ))}
+                </ul>
+              </div>
+              <p className="text-xs text-gray-500 dark:text-gray-400">
+                Only continue if the device shows the code <span className="font-mono">{userCode}</span>.
+              </p>
+              <div className="flex gap-3">
+                <Button
+                  variant="outline"
+                  className="flex-1 flex items-center justify-center gap-2"
+                  disabled={loading}
+                  onClick={() => decide(false)}
+                >
+                  <X className="h-4 w-4" /> Deny
+                </Button>
+                <Button
+                  className="flex-1 flex items-center justify-center gap-2 text-white bg-gradient-to-r from-blue-500 to-purple-500 hover:from-purple-500 hover:to-blue-500"
+                  disabled={loading}
+                  onClick={() => decide(true)}
+                >
+                  <Check className="h-4 w-4" /> Allow
+                </Button>
+              </div>
+            </>
+          ) : (
+            <form className="space-y-5" onSubmit={handleSubmit} aria-label="Device code form">
+              <div>
+                <Label htmlFor="user_code" className="font-semibold dark:text-gray-200">Code shown on your device</Label>
+                <Input
+                  id="user_code"
+                  value={userCode}
+                  onChange={e => setUserCode(e.target.value)}
+                  placeholder="ABCD-EFGH"
+                  required
+                  autoFocus
+                  className="mt-1 font-mono uppercase tracking-widest text-center"
+                />
+              </div>
+              <Button
+                type="submit"
+                className="w-full py-2 font-semibold text-white bg-gradient-to-r from-blue-500 to-purple-500 hover:from-purple-500 hover:to-blue-500"
+                disabled={loading}
+              >
+                {loading ? 'Checking...' : 'Continue'}
+              </Button>
+            </form>
+          )}
+        </CardContent>
+      </Card>
+    </div>
+  );
+};
+
+export default DeviceAuthorize;
@@ -0,0 +1,85 @@
+/* eslint-disable @typescript-eslint/no-explicit-any */
+import { useState } from 'react';
+import { Navigate, useLocation, useNavigate } from 'react-router-dom';
+import { Card, CardHeader, CardTitle, CardContent } from "@/components/ui/card";
+import { Label } from "@/components/ui/label";
+import { Input } from "@/components/ui/input";
+import { Button } from "@/components/ui/button";
+import { useToast } from "@/hooks/use-toast";
+import { ShieldCheck } from "lucide-react";
+import { useAuth } from "@/hooks/useAuth";
+
+// Second step of a login for accounts with two-factor authentication
+const LoginMfa = () => {
+  const [code, setCode] = useState("");
+  const [loading, setLoading] = useState(false);
+  const { toast } = useToast();
+  const { verifyMfa } = useAuth();
+  const navigate = useNavigate();
+  const location = useLocation();
+  const { mfaToken, from } = (location.state as { mfaToken?: string; from?: string } | null) ?? {};
+
+  if (!mfaToken) {
+    return <Navigate to="/login" replace />;
+  }
+
+  const handleSubmit = async (e: React.FormEvent) => {
+    e.preventDefault();
+    setLoading(true);
+    try {
+      await verifyMfa({ mfa_token: mfaToken, code }, from);
+    } catch (err: any) {
+      // Each challenge allows one attempt, so a wrong code means starting over
+      toast({
+        title: "Verification failed",
+        description: err?.message || 'Invalid code',
+        variant: "destructive"
+      });
+      navigate("/login", { replace: true, state: { from } });
+    } finally {
+      setLoading(false);
+    }
+  };
+
+  return (
+    <div className="relative min-h-screen flex items-center justify-center bg-gradient-to-b from-[#f5f7fa] to-[#c3cfe2] dark:from-gray-900 dark:to-gray-800 transition-colors duration-300">
+      <Card className="w-full max-w-md rounded-2xl shadow-lg border-0 p-0 bg-white dark:bg-gray-900 transition-colors duration-300">
+        <CardHeader className="flex flex-col items-center gap-2 bg-transparent pt-8 pb-2">
+          <div className="flex items-center justify-center h-14 w-14 rounded-full bg-gradient-to-br from-purple-400 to-blue-400 dark:from-purple-700 dark:to-blue-800 mb-2">
+            <ShieldCheck className="h-8 w-8 text-white" />
+          </div>
+          <CardTitle className="text-2xl font-extrabold text-purple-700 dark:text-purple-300">Two-Factor Authentication</CardTitle>
+          <p className="text-gray-500 dark:text-gray-300 text-sm text-center">Enter the code from your authenticator app, or one of your recovery codes</p>
+        </CardHeader>
+        <CardContent className="pt-2 pb-6 px-8">
+          <form className="space-y-5" onSubmit={handleSubmit} autoComplete="off" aria-label="Two-factor form">
+            <div>
+              <Label htmlFor="code" className="font-semibold dark:text-gray-200">Code</Label>
+              <Input
+                id="code"
+                value={code}
+                onChange={e => setCode(e.target.value)}
+                placeholder="123456"
+                autoComplete="one-time-code"
+                required
+                autoFocus
+                className="mt-1 font-mono tracking-widest text-center bg-white dark:bg-gray-800 border border-gray-200 dark:border-gray-700 text-gray-900 dark:text-gray-100"
+                aria-label="Authentication code"
+              />
+            </div>
+            <Button
+              type="submit"
+              className="w-full py-2 font-semibold text-white bg-gradient-to-r from-blue-500 to-purple-500 hover:from-purple-500 hover:to-blue-500 transition-all duration-200 shadow-md"
+              disabled={loading}
+              aria-busy={loading}
+            >
+              {loading ? "Verifying..." : "Verify"}
+            </Button>
+          </form>
+        </CardContent>
+      </Card>
+    </div>
+  );
+};
+
+export default LoginMfa;
@@ -0,0 +1,121 @@
+/* eslint-disable @typescript-eslint/no-explicit-any */
+import { useMemo, useState } from 'react';
+import { useSearchParams } from 'react-router-dom';
+import { Card, CardHeader, CardTitle, CardContent } from '@/components/ui/card';
+import { Button } from '@/components/ui/button';
+import { useToast } from '@/hooks/use-toast';
+import { useAuth } from '@/hooks/useAuth';
+import { getToken } from '@/hooks/authUtils';
+import { describeScope, splitScopes } from '@/lib/oauth';
+import { KeyRound, Check, X } from 'lucide-react';
+
+// Parameters of the authorization request, as GET /oauth/authorize passed them on
+const REQUEST_PARAMS = [
+  'response_type',
+  'client_id',
+  'redirect_uri',
+  'scope',
+  'state',
+  'code_challenge',
+  'code_challenge_method',
+  'nonce',
+];
+
+const OAuthAuthorize = () => {
+  const { user } = useAuth();
+  const { toast } = useToast();
+  const [searchParams] = useSearchParams();
+  const [submitting, setSubmitting] = useState(false);
+
+  const request = useMemo(() => {
+    const params: Record<string, string> = {};
+    for (const name of REQUEST_PARAMS) {
+      const value = searchParams.get(name);
+      if (value !== null) params[name] = value;
+    }
+    return params;
+  }, [searchParams]);
+
+  const decide = async (approve: boolean) => {
+    setSubmitting(true);
+    try {
+      const res = await fetch(`${import.meta.env.VITE_API_BASE_URL || 'http://localhost:3000'}/oauth/authorize`, {
+        method: 'POST',
+        headers: {
+          'Content-Type': 'application/json',
+          Authorization: `Bearer ${getToken()}`,
+        },
+        body: JSON.stringify({ ...request, approve }),
+      });
+      const data = await res.json();
+      if (!res.ok) throw new Error(data.error || 'The authorization request is invalid');
+      // Back to the client, with a code or an error
+      window.location.assign(data.redirect_to);
+    } catch (err: any) {
+      toast({ title: 'Authorization failed', description: err.message, variant: 'destructive' });
+      setSubmitting(false);
+    }
+  };
+
+  if (!request.client_id || !request.redirect_uri) {
+    return (
+      <div className="min-h-screen flex items-center justify-center bg-gradient-to-br from-gray-50 to-purple-50 dark:from-gray-900 dark:to-gray-800">
+        <Card className="w-full max-w-md rounded-2xl shadow-lg border-0 bg-white dark:bg-gray-900">
+          <CardHeader className="flex flex-col items-center gap-2">
+            <KeyRound className="h-10 w-10 text-purple-500" />
+            <CardTitle className="text-2xl font-bold text-purple-700 dark:text-purple-300">Invalid request</CardTitle>
+          </CardHeader>
+          <CardContent>
+            <p className="text-gray-600 dark:text-gray-300 text-center">This page is opened by an application asking for access to your account.</p>
+          </CardContent>
+        </Card>
+      </div>
+    );
+  }
+
+  return (
+    <div className="min-h-screen flex items-center justify-center bg-gradient-to-br from-gray-50 to-purple-50 dark:from-gray-900 dark:to-gray-800">
+      <Card className="w-full max-w-md rounded-2xl shadow-lg border-0 bg-white dark:bg-gray-900">
+        <CardHeader className="flex flex-col items-center gap-2 pt-8 pb-2">
+          <KeyRound className="h-10 w-10 text-purple-500" />
+          <CardTitle className="text-2xl font-bold text-purple-700 dark:text-purple-300 text-center">
+            Allow <span className="font-mono">{request.client_id}</span> to access your account?
+          </CardTitle>
+          <p className="text-gray-500 dark:text-gray-300 text-sm">Signed in as {user?.email}</p>
+        </CardHeader>
+        <CardContent className="space-y-5 pt-2 pb-6 px-8">
+          <div>
+            <p className="font-semibold text-gray-700 dark:text-gray-200 mb-2">It will be able to:</p>
+            <ul className="space-y-1 text-gray-600 dark:text-gray-300">
+              {splitScopes(request.scope).map(scope => (
+                <li key={scope} className="flex items-center gap-2">
+                  <Check className="h-4 w-4 text-green-500" /> {describeScope(scope)}
+                </li>
+              ))}
+            </ul>
+          </div>
+          <p className="text-xs text-gray-500 dark:text-gray-400 break-all">You will be sent back to {request.redirect_uri}</p>
+          <div className="flex gap-3">
+            <Button
+              variant="outline"
+              className="flex-1 flex items-center justify-center gap-2"
+              disabled={submitting}
+              onClick={() => decide(false)}
+            >
+              <X className="h-4 w-4" /> Deny
+            </Button>
+            <Button
+              className="flex-1 flex items-center justify-center gap-2 text-white bg-gradient-to-r from-blue-500 to-purple-500 hover:from-purple-500 hover:to-blue-500"
+              disabled={submitting}
+              onClick={() => decide(true)}
+            >
+              <Check className="h-4 w-4" /> Allow
+            </Button>
+          </div>
+        </CardContent>
+      </Card>
+    </div>
+  );
+};
+
+export default OAuthAuthorize;
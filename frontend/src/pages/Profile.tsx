@@ -108,10 +108,7 @@ const Profile = () => {
             </div>
             <Button
               className="w-full mt-4 py-2 font-semibold text-white bg-gradient-to-r from-pink-500 to-red-500 hover:from-red-500 hover:to-pink-500 transition-all duration-200 shadow-md flex items-center justify-center gap-2"
-              onClick={() => {
-                localStorage.removeItem('auth_token');
-                logout();
-              }}
+              onClick={logout}
               aria-label="Sign out securely"
             >
               <LogOut className="inline h-5 w-5 mr-1" /> Sign Out Securely
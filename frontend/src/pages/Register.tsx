@@ -16,6 +16,7 @@ const Register = () => {
   const [email, setEmail] = useState("");
   const [password, setPassword] = useState("");
   const [confirmPassword, setConfirmPassword] = useState("");
+  const [passwordViolations, setPasswordViolations] = useState<string[]>([]);
   const [loading, setLoading] = useState(false);
   const navigate = useNavigate();
   const { toast } = useToast();
@@ -34,6 +35,7 @@ const Register = () => {
     }
 
     setLoading(true);
+    setPasswordViolations([]);
     try {
       const userData: RegisterRequest = {
         first_name: firstName,
@@ -46,6 +48,10 @@ const Register = () => {
       await register(userData);
       // Navigation is handled by the useAuth hook
     } catch (err: any) {
+      const violations = err?.response?.data?.violations;
+      if (Array.isArray(violations)) {
+        setPasswordViolations(violations.map((v: { message: string }) => v.message));
+      }
       toast({ 
         title: "Registration failed", 
         description: err?.response?.data?.error || 'Registration failed', 
@@ -126,6 +132,11 @@ const Register = () => {
                   aria-label="Password"
                 />
               </div>
+              {passwordViolations.length > 0 && (
+                <ul className="text-sm text-red-600 dark:text-red-400 list-disc pl-5" aria-live="polite">
+                  {passwordViolations.map(message => <li key={message}>{message}</li>)}
+                </ul>
+              )}
             </div>
             <div className="space-y-2">
               <Label htmlFor="confirm_password" className="dark:text-gray-200">Confirm Password</Label>
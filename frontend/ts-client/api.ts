@@ -54,12 +54,24 @@ export interface LoginResponse {
      * @memberof LoginResponse
      */
     'access_token': string;
+    /**
+     * access token lifetime in seconds
+     * @type {number}
+     * @memberof LoginResponse
+     */
+    'expires_in': number;
     /**
      * 
      * @type {string}
      * @memberof LoginResponse
      */
     'message': string;
+    /**
+     * 
+     * @type {string}
+     * @memberof LoginResponse
+     */
+    'refresh_token': string;
     /**
      * 
      * @type {string}
@@ -67,6 +79,69 @@ export interface LoginResponse {
      */
     'token_type': string;
 }
+/**
+ * 
+ * @export
+ * @interface MfaChallengeResponse
+ */
+export interface MfaChallengeResponse {
+    /**
+     * 
+     * @type {number}
+     * @memberof MfaChallengeResponse
+     */
+    'expires_in': number;
+    /**
+     * 
+     * @type {string}
+     * @memberof MfaChallengeResponse
+     */
+    'message': string;
+    /**
+     * Always `true`: the login continues at POST /login/mfa.
+     * @type {boolean}
+     * @memberof MfaChallengeResponse
+     */
+    'mfa_required': boolean;
+    /**
+     * Single-use token to send to POST /login/mfa with the code.
+     * @type {string}
+     * @memberof MfaChallengeResponse
+     */
+    'mfa_token': string;
+}
+/**
+ * 
+ * @export
+ * @interface MfaLoginRequest
+ */
+export interface MfaLoginRequest {
+    /**
+     * Current authenticator code or an unused recovery code.
+     * @type {string}
+     * @memberof MfaLoginRequest
+     */
+    'code': string;
+    /**
+     * 
+     * @type {string}
+     * @memberof MfaLoginRequest
+     */
+    'mfa_token': string;
+}
+/**
+ * 
+ * @export
+ * @interface RefreshRequest
+ */
+export interface RefreshRequest {
+    /**
+     * 
+     * @type {string}
+     * @memberof RefreshRequest
+     */
+    'refresh_token': string;
+}
 /**
  * 
  * @export
@@ -124,10 +199,10 @@ export interface RegisterResponse {
     'first_name': string;
     /**
      * 
-     * @type {number}
+     * @type {string}
      * @memberof RegisterResponse
      */
-    'id': number;
+    'id': string;
     /**
      * 
      * @type {string}
@@ -161,6 +236,12 @@ export interface User {
      * @memberof User
      */
     'email': string;
+    /**
+     * Set once the user followed the link mailed to `email`.
+     * @type {boolean}
+     * @memberof User
+     */
+    'email_verified': boolean;
     /**
      * 
      * @type {string}
@@ -169,10 +250,10 @@ export interface User {
     'first_name': string;
     /**
      * 
-     * @type {number}
+     * @type {string}
      * @memberof User
      */
-    'id': number;
+    'id': string;
     /**
      * 
      * @type {string}
@@ -206,6 +287,12 @@ export interface UserResponse {
      * @memberof UserResponse
      */
     'email': string;
+    /**
+     * 
+     * @type {boolean}
+     * @memberof UserResponse
+     */
+    'email_verified': boolean;
     /**
      * 
      * @type {string}
@@ -214,10 +301,10 @@ export interface UserResponse {
     'first_name': string;
     /**
      * 
-     * @type {number}
+     * @type {string}
      * @memberof UserResponse
      */
-    'id': number;
+    'id': string;
     /**
      * 
      * @type {string}
@@ -275,6 +362,41 @@ export const AuthApiAxiosParamCreator = function (configuration?: Configuration)
                 options: localVarRequestOptions,
             };
         },
+        /**
+         * 
+         * @param {RefreshRequest} refreshRequest 
+         * @param {*} [options] Override http request option.
+         * @throws {RequiredError}
+         */
+        refresh: async (refreshRequest: RefreshRequest, options: RawAxiosRequestConfig = {}): Promise<RequestArgs> => {
+            // verify required parameter 'refreshRequest' is not null or undefined
+            assertParamExists('refresh', 'refreshRequest', refreshRequest)
+            const localVarPath = `/token/refresh`;
+            // use dummy base URL string because the URL constructor only accepts absolute URLs.
+            const localVarUrlObj = new URL(localVarPath, DUMMY_BASE_URL);
+            let baseOptions;
+            if (configuration) {
+                baseOptions = configuration.baseOptions;
+            }
+
+            const localVarRequestOptions = { method: 'POST', ...baseOptions, ...options};
+            const localVarHeaderParameter = {} as any;
+            const localVarQueryParameter = {} as any;
+
+
+    
+            localVarHeaderParameter['Content-Type'] = 'application/json';
+
+            setSearchParams(localVarUrlObj, localVarQueryParameter);
+            let headersFromBaseOptions = baseOptions && baseOptions.headers ? baseOptions.headers : {};
+            localVarRequestOptions.headers = {...localVarHeaderParameter, ...headersFromBaseOptions, ...options.headers};
+            localVarRequestOptions.data = serializeDataIfNeeded(refreshRequest, localVarRequestOptions, configuration)
+
+            return {
+                url: toPathString(localVarUrlObj),
+                options: localVarRequestOptions,
+            };
+        },
         /**
          * 
          * @param {RegisterRequest} registerRequest 
@@ -332,6 +454,18 @@ export const AuthApiFp = function(configuration?: Configuration) {
             const localVarOperationServerBasePath = operationServerMap['AuthApi.login']?.[localVarOperationServerIndex]?.url;
             return (axios, basePath) => createRequestFunction(localVarAxiosArgs, globalAxios, BASE_PATH, configuration)(axios, localVarOperationServerBasePath || basePath);
         },
+        /**
+         * 
+         * @param {RefreshRequest} refreshRequest 
+         * @param {*} [options] Override http request option.
+         * @throws {RequiredError}
+         */
+        async refresh(refreshRequest: RefreshRequest, options?: RawAxiosRequestConfig): Promise<(axios?: AxiosInstance, basePath?: string) => AxiosPromise<LoginResponse>> {
+            const localVarAxiosArgs = await localVarAxiosParamCreator.refresh(refreshRequest, options);
+            const localVarOperationServerIndex = configuration?.serverIndex ?? 0;
+            const localVarOperationServerBasePath = operationServerMap['AuthApi.refresh']?.[localVarOperationServerIndex]?.url;
+            return (axios, basePath) => createRequestFunction(localVarAxiosArgs, globalAxios, BASE_PATH, configuration)(axios, localVarOperationServerBasePath || basePath);
+        },
         /**
          * 
          * @param {RegisterRequest} registerRequest 
@@ -363,6 +497,15 @@ export const AuthApiFactory = function (configuration?: Configuration, basePath?
         login(loginRequest: LoginRequest, options?: RawAxiosRequestConfig): AxiosPromise<LoginResponse> {
             return localVarFp.login(loginRequest, options).then((request) => request(axios, basePath));
         },
+        /**
+         * 
+         * @param {RefreshRequest} refreshRequest 
+         * @param {*} [options] Override http request option.
+         * @throws {RequiredError}
+         */
+        refresh(refreshRequest: RefreshRequest, options?: RawAxiosRequestConfig): AxiosPromise<LoginResponse> {
+            return localVarFp.refresh(refreshRequest, options).then((request) => request(axios, basePath));
+        },
         /**
          * 
          * @param {RegisterRequest} registerRequest 
@@ -393,6 +536,17 @@ export class AuthApi extends BaseAPI {
         return AuthApiFp(this.configuration).login(loginRequest, options).then((request) => request(this.axios, this.basePath));
     }
 
+    /**
+     * 
+     * @param {RefreshRequest} refreshRequest 
+     * @param {*} [options] Override http request option.
+     * @throws {RequiredError}
+     * @memberof AuthApi
+     */
+    public refresh(refreshRequest: RefreshRequest, options?: RawAxiosRequestConfig) {
+        return AuthApiFp(this.configuration).refresh(refreshRequest, options).then((request) => request(this.axios, this.basePath));
+    }
+
     /**
      * 
      * @param {RegisterRequest} registerRequest 